@@ -2,6 +2,7 @@ pub mod camera;
 pub mod film;
 pub mod projective;
 pub mod write_image;
+pub mod read_image;
 
 pub use camera::{Camera, CameraSample};
 pub use projective::ProjectiveCamera;
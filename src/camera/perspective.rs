@@ -72,16 +72,21 @@ impl PerspectiveCamera {
         ret
     }
 
-    fn create_perspective(fov: Float, n: Float, f: Float) -> Transform {
+    pub fn create_perspective(fov: Float, n: Float, f: Float) -> Transform {
         let z_scale = f / (f - n);
         let z_translate = -f * n /(f - n);
 
-        let scaling = scale(&Vector3::new(1.0, 1.0, z_scale));
-        let translation = translate(&Vector3::new(0.0, 0.0, z_translate));
+        // last row copies z into w so transform_point does the perspective divide
+        let persp = Transform::from_matrix_unchecked(na::Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, z_scale, z_translate,
+            0.0, 0.0, 1.0, 0.0
+        ));
         
         let inv_tan_angle = 1.0 / (fov.to_radians() / 2.0).tan();
         let other_scaling = scale(&Vector3::new(inv_tan_angle, inv_tan_angle, 1.0));
-        other_scaling * scaling * translation
+        other_scaling * persp
     }
 }
 
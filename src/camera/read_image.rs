@@ -0,0 +1,59 @@
+use exr::prelude::*;
+use std::path::Path;
+use crate::common::*;
+
+// Images are returned top row first, as linear RGB with their resolution
+
+pub fn read_png_image(file_path_str: &str, srgb: bool) -> std::result::Result<(Vec<Spectrum>, usize, usize), String> {
+    let img = match image::open(file_path_str) {
+        Ok(img) => img.to_rgb32f(),
+        Err(e) => return Err(format!("Error reading image from file {}: {:?}", file_path_str, e))
+    };
+
+    let width = img.width() as usize;
+    let height = img.height() as usize;
+
+    let pixels = img.pixels().map(|p| {
+        if srgb {
+            Spectrum::new(srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2]))
+        } else {
+            Spectrum::new(p[0], p[1], p[2])
+        }
+    }).collect();
+
+    Ok((pixels, width, height))
+}
+
+pub fn read_exr_image(file_path_str: &str) -> std::result::Result<(Vec<Spectrum>, usize, usize), String> {
+    let file_path = Path::new(file_path_str);
+
+    let image = read_first_rgba_layer_from_file(
+        file_path,
+        |resolution, _| {
+            (vec![Spectrum::new(0.0, 0.0, 0.0); resolution.width() * resolution.height()], resolution.width())
+        },
+        |(pixels, width), position, (r, g, b, _a): (f32, f32, f32, f32)| {
+            pixels[position.y() * *width + position.x()] = Spectrum::new(r, g, b);
+        }
+    );
+
+    match image {
+        Ok(image) => {
+            let (pixels, width) = image.layer_data.channel_data.pixels;
+            let height = pixels.len() / width.max(1);
+            Ok((pixels, width, height))
+        },
+        Err(e) => Err(format!("Error reading image from file {}: {:?}", file_path_str, e))
+    }
+}
+
+// Picks the reader from the extension, 8 bit formats are assumed to be sRGB encoded
+pub fn read_image(file_path_str: &str) -> std::result::Result<(Vec<Spectrum>, usize, usize), String> {
+    let extension = Path::new(file_path_str).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+
+    if extension == "exr" {
+        read_exr_image(file_path_str)
+    } else {
+        read_png_image(file_path_str, true)
+    }
+}
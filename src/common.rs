@@ -11,6 +11,8 @@ pub use crate::spectrum::*;
 pub use crate::camera::*;
 pub use crate::math::*;
 pub use crate::sampler::*;
+pub use crate::light::*;
+pub use crate::texture::*;
 
 // can set it between f32 and f64 here, just like pbr-book does
 pub type Float = f32;
@@ -26,6 +28,7 @@ pub type Point3 = na::Point3<Float>;
 pub type Vector2 = na::Vector2<Float>;
 pub type Vector3 = na::Vector3<Float>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransportMode {
     Radiance,
     Importance
@@ -70,4 +73,50 @@ pub fn sample_concentric_disc(u: &Point2) -> Point2 {
     }
 
     r * Point2::new(theta.cos(), theta.sin())
-}
\ No newline at end of file
+}
+pub fn uniform_sample_hemisphere(u: &Point2) -> Vector3 {
+    let z = u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_hemisphere_pdf() -> Float {
+    1.0 / (2.0 * PI)
+}
+
+pub fn uniform_sample_sphere(u: &Point2) -> Vector3 {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> Float {
+    1.0 / (4.0 * PI)
+}
+
+pub fn uniform_sample_cone(u: &Point2, cos_theta_max: Float) -> Vector3 {
+    let cos_theta = (1.0 - u.x) + u.x * cos_theta_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = u.y * 2.0 * PI;
+
+    Vector3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: Float) -> Float {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+pub fn cosine_sample_hemisphere(u: &Point2) -> Vector3 {
+    let d = sample_concentric_disc(u);
+    let z = (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt();
+
+    Vector3::new(d.x, d.y, z)
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: Float) -> Float {
+    cos_theta * (1.0 / PI)
+}
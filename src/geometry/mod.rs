@@ -3,12 +3,12 @@ pub mod bounding_box_2;
 pub mod bounding_box_3;
 pub mod helpers;
 
-pub use ray::{Ray, RayDifferential, offset_ray_origin};
+pub use ray::{Ray, RayDifferential, RayType, RAY_VISIBILITY_ALL, offset_ray_origin};
 pub use bounding_box_2::Bounds2f;
 pub use bounding_box_3::Bounds3f;
 pub use helpers::{ceil, floor, min, max};
 
-use crate::common::{Arc, Transform, Vector3};
+use crate::common::{Arc, Transform, Vector3, Float, PI};
 
 pub fn face_forward(n: &Vector3, v: &Vector3) -> Vector3 {
     return if n.dot(v) < 0.0 {
@@ -43,7 +43,9 @@ pub fn apply_transform_to_ray(r: &Ray, t: &Arc<Transform>) -> Ray {
     let r_o = t.transform_point(&r.o);
     let r_d = t.transform_vector(&r.d);
 
-    Ray::init(&r_o, &r_d, Some(r.t_max), Some(r.time), r.medium.clone())
+    let mut ray = Ray::init(&r_o, &r_d, Some(r.t_max), Some(r.time), r.medium.clone());
+    ray.ray_type = r.ray_type;
+    ray
 }
 
 pub fn apply_transform_to_ray_differential(r: &RayDifferential, t: &Arc<Transform>) -> RayDifferential { 
@@ -64,3 +66,29 @@ pub fn apply_transform_to_ray_differential(r: &RayDifferential, t: &Arc<Transfor
         has_differentials
     }
 }
+
+pub fn coordinate_system(v1: &Vector3, v2: &mut Vector3, v3: &mut Vector3) {
+    *v2 = if v1.x.abs() > v1.y.abs() {
+        Vector3::new(-v1.z, 0.0, v1.x) / (v1.x * v1.x + v1.z * v1.z).sqrt()
+    } else {
+        Vector3::new(0.0, v1.z, -v1.y) / (v1.y * v1.y + v1.z * v1.z).sqrt()
+    };
+    *v3 = v1.cross(v2);
+}
+
+pub fn spherical_direction(sin_theta: Float, cos_theta: Float, phi: Float) -> Vector3 {
+    Vector3::new(sin_theta.clamp(-1.0, 1.0) * phi.cos(), sin_theta.clamp(-1.0, 1.0) * phi.sin(), cos_theta.clamp(-1.0, 1.0))
+}
+
+pub fn spherical_direction_in_frame(sin_theta: Float, cos_theta: Float, phi: Float, x: &Vector3, y: &Vector3, z: &Vector3) -> Vector3 {
+    sin_theta * phi.cos() * x + sin_theta * phi.sin() * y + cos_theta * z
+}
+
+pub fn spherical_theta(v: &Vector3) -> Float {
+    v.z.clamp(-1.0, 1.0).acos()
+}
+
+pub fn spherical_phi(v: &Vector3) -> Float {
+    let p = v.y.atan2(v.x);
+    if p < 0.0 { p + 2.0 * PI } else { p }
+}
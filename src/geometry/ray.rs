@@ -1,13 +1,26 @@
 use crate::common::*;
 use std::ops::Mul;
 
+// What a ray is used for, primitives can be hidden from some of these
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayType {
+    Camera = 1,
+    Shadow = 2,
+    Diffuse = 4,
+    Specular = 8,
+    Transmission = 16
+}
+
+pub const RAY_VISIBILITY_ALL: u32 = 31;
+
 #[derive(Debug, Clone)]
 pub struct Ray {
     pub o: na::Point3<Float>,
     pub d: na::Vector3<Float>,
     pub t_max: Float,
     pub time: Float,
-    pub medium: Option<Arc<dyn Medium>>
+    pub medium: Option<Arc<dyn Medium>>,
+    pub ray_type: RayType
 }
 
 impl Ray {
@@ -17,7 +30,8 @@ impl Ray {
             time: 0.0,
             o: na::Point3::new(0.0, 0.0, 0.0),
             d: na::Vector3::new(0.0, 0.0, 0.0),
-            medium: None
+            medium: None,
+            ray_type: RayType::Camera
         }
     }
 
@@ -33,7 +47,8 @@ impl Ray {
                 Some(t) => t,
                 None => 0.0
             },
-            medium: medium
+            medium: medium,
+            ray_type: RayType::Camera
         }
     }
 
@@ -101,11 +116,12 @@ impl RayDifferential {
     }
 }
 
-pub fn offset_ray_origin(p: &Point3, p_error: &Vector3, n: &Vector3, wo: &Vector3) -> Point3 {
+// moves p past its error bounds along n, to the side w points to
+pub fn offset_ray_origin(p: &Point3, p_error: &Vector3, n: &Vector3, w: &Vector3) -> Point3 {
     let d = n.abs().dot(p_error);
-    let mut offset = d * Vector3::new(d, d, d);
+    let mut offset = d * n;
 
-    if n.dot(wo) < 0.0 {
+    if n.dot(w) < 0.0 {
         offset = -offset;
     }
 
@@ -129,7 +145,9 @@ impl Mul<&Ray> for Transform {
         let o_new = self * rhs.o;
         let d_new = self * rhs.d;
 
-        Ray::init(&o_new, &d_new, Some(rhs.t_max), Some(rhs.time), rhs.medium.clone())
+        let mut ray = Ray::init(&o_new, &d_new, Some(rhs.t_max), Some(rhs.time), rhs.medium.clone());
+        ray.ray_type = rhs.ray_type;
+        ray
     }
 }
 
@@ -63,23 +63,30 @@ impl Interaction {
         !self.is_surface_interaction()
     }
 
+    // the caller sets ray_type to Specular or Transmission when that is what the BSDF sampled
     pub fn spawn_ray(&self, d: &Vector3) -> Ray {
-        let o = offset_ray_origin(&self.p, &self.p_error, &self.n, &self.wo);
-        Ray::init(&o, d, Some(INFINITY), Some(self.time), None)
+        let o = offset_ray_origin(&self.p, &self.p_error, &self.n, d);
+        let mut ray = Ray::init(&o, d, Some(INFINITY), Some(self.time), None);
+        ray.ray_type = RayType::Diffuse;
+        ray
     }
 
     pub fn spawn_ray_to(&self, p2: &Point3) -> Ray {
-        let o = offset_ray_origin(&self.p, &self.p_error, &self.n, &self.wo);
+        let o = offset_ray_origin(&self.p, &self.p_error, &self.n, &(p2 - self.p));
         let d= p2 - o;
-        Ray::init(&o, &d, Some(1.0 - EPSILON), Some(self.time), None)
+        let mut ray = Ray::init(&o, &d, Some(1.0 - EPSILON), Some(self.time), None);
+        ray.ray_type = RayType::Shadow;
+        ray
     }
 
     pub fn spawn_ray_to_intersection(&self, it: &Self) -> Ray {
-        let o = offset_ray_origin(&self.p, &self.p_error, &self.n, &self.wo);
-        let p = offset_ray_origin(&it.p, &it.p_error, &it.n, &it.wo);
+        let o = offset_ray_origin(&self.p, &self.p_error, &self.n, &(it.p - self.p));
+        let p = offset_ray_origin(&it.p, &it.p_error, &it.n, &(o - it.p));
         let d = p - o;
 
-        Ray::init(&o, &d, Some(1.0 - EPSILON), Some(self.time), None)
+        let mut ray = Ray::init(&o, &d, Some(1.0 - EPSILON), Some(self.time), None);
+        ray.ray_type = RayType::Shadow;
+        ray
     }
 
     pub fn get_medium(&self, w: &Vector3) -> Option<Arc<dyn Medium>> {
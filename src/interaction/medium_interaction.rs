@@ -0,0 +1,17 @@
+use crate::common::*;
+
+// A scattering event inside a participating medium
+#[derive(Debug, Clone)]
+pub struct MediumInteraction {
+    pub interaction: Interaction,
+    pub phase: Arc<dyn PhaseFunction>
+}
+
+impl MediumInteraction {
+    pub fn init(p: &Point3, wo: &Vector3, time: Float, medium: Arc<dyn Medium>, phase: Arc<dyn PhaseFunction>) -> Self {
+        Self {
+            interaction: Interaction::init_no_n(p, wo, time, Some(MediumInterface::init_one(medium))),
+            phase
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub mod interaction;
 pub mod surface_interaction;
+pub mod medium_interaction;
 
 pub use interaction::Interaction;
-pub use surface_interaction::SurfaceInteraction;
\ No newline at end of file
+pub use surface_interaction::SurfaceInteraction;
+pub use medium_interaction::MediumInteraction;
@@ -39,8 +39,9 @@ pub struct SurfaceInteraction {
 
     pub shape: Option<Arc<dyn Shape>>,
     pub primitive: Option<Arc<dyn Primitive>>,
-    pub bsdf: Option<Arc<dyn BSDF>>,
-    pub bssrdf: Option<Arc<dyn BSDF>>,
+    pub object_name: Option<Arc<str>>,
+    pub bsdf: Option<Arc<BSDF>>,
+    pub bssrdf: Option<Arc<dyn BSSRDF>>,
     
     pub dpdx: Vector3, pub dpdy: Vector3,
     pub dudx: Float, pub dvdx: Float,
@@ -60,6 +61,7 @@ impl SurfaceInteraction {
 
             shape: None,
             primitive: None,
+            object_name: None,
             bsdf: None, bssrdf: None,
 
             dpdx: Vector3::new(0.0, 0.0, 0.0), dpdy: Vector3::new(0.0, 0.0, 0.0),
@@ -96,6 +98,7 @@ impl SurfaceInteraction {
 
             shape,
             primitive: None,
+            object_name: None,
             bsdf: None, bssrdf: None,
 
             dpdx: Vector3::new(0.0, 0.0, 0.0), dpdy: Vector3::new(0.0, 0.0, 0.0),
@@ -109,7 +112,7 @@ impl SurfaceInteraction {
 
         if let Some(shape) = &self.shape {
             if shape.reverse_orientation() ^ shape.transform_swaps_handedness() {
-                self.shading.n *= 1.0;
+                self.shading.n *= -1.0;
             }
         }
 
@@ -125,12 +128,65 @@ impl SurfaceInteraction {
         self.shading.dndv = dndv.clone();
     }
 
-    pub fn compute_scattering_function(&mut self, _ray: &RayDifferential, _allow_multiple_lobes: bool, _mode: TransportMode) {
-        // TODO
+    // asks the material of the hit primitive to set bsdf and bssrdf
+    pub fn compute_scattering_function(&mut self, ray: &RayDifferential, allow_multiple_lobes: bool, mode: TransportMode) {
+        self.compute_differential(ray);
+
+        if let Some(primitive) = self.primitive.clone() {
+            primitive.compute_scattering_function(self, mode, allow_multiple_lobes);
+        }
     }
 
-    pub fn compute_differential(&self, _r: &RayDifferential) {
-        // TODO
+    // estimates how p and uv change across a pixel from the offset rays of the differential
+    pub fn compute_differential(&mut self, ray: &RayDifferential) {
+        if !ray.has_differentials || !self.estimate_differentials(ray) {
+            self.dudx = 0.0;
+            self.dvdx = 0.0;
+            self.dudy = 0.0;
+            self.dvdy = 0.0;
+            self.dpdx = Vector3::new(0.0, 0.0, 0.0);
+            self.dpdy = Vector3::new(0.0, 0.0, 0.0);
+        }
+    }
+
+    fn estimate_differentials(&mut self, ray: &RayDifferential) -> bool {
+        // intersect the offset rays with the tangent plane
+        let n = self.interaction.n;
+        let p = self.interaction.p;
+        let d = n.dot(&p.coords);
+        let tx = -(n.dot(&ray.rx_origin.coords) - d) / n.dot(&ray.rx_direction);
+        let ty = -(n.dot(&ray.ry_origin.coords) - d) / n.dot(&ray.ry_direction);
+        if !tx.is_finite() || !ty.is_finite() {
+            return false;
+        }
+        let px = ray.rx_origin + tx * ray.rx_direction;
+        let py = ray.ry_origin + ty * ray.ry_direction;
+        self.dpdx = px - p;
+        self.dpdy = py - p;
+
+        // solve dp = dpdu du + dpdv dv in the two dimensions the normal is least aligned with
+        let dim = if n.x.abs() > n.y.abs() && n.x.abs() > n.z.abs() {
+            [1, 2]
+        } else if n.y.abs() > n.z.abs() {
+            [0, 2]
+        } else {
+            [0, 1]
+        };
+        let a = [[self.dpdu[dim[0]], self.dpdv[dim[0]]], [self.dpdu[dim[1]], self.dpdv[dim[1]]]];
+        let det = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+        if det.abs() < 1e-10 {
+            return false;
+        }
+
+        let solve = |b: [Float; 2]| -> (Float, Float) {
+            let x0 = (a[1][1] * b[0] - a[0][1] * b[1]) / det;
+            let x1 = (a[0][0] * b[1] - a[1][0] * b[0]) / det;
+            if x0.is_finite() && x1.is_finite() { (x0, x1) } else { (0.0, 0.0) }
+        };
+        (self.dudx, self.dvdx) = solve([self.dpdx[dim[0]], self.dpdx[dim[1]]]);
+        (self.dudy, self.dvdy) = solve([self.dpdy[dim[0]], self.dpdy[dim[1]]]);
+
+        true
     }
 
     pub fn le(&self, _w: &Vector3) {
@@ -175,6 +231,7 @@ impl Mul<&SurfaceInteraction> for Transform {
         let mut ret = SurfaceInteraction::init(&p, &p_error, &uv, &wo, &dpdu, &dpdv, &dndu, &dndv, time, shape);
 
         ret.interaction.medium_interface = mi;
+        ret.object_name = rhs.object_name.clone();
         ret.shading.n = sha_n;
         ret.shading.dpdu = sha_dpdu;
         ret.shading.dpdv = sha_dpdv;
@@ -0,0 +1,133 @@
+use crate::common::*;
+
+// Emits uniform radiance from the surface of a shape
+#[derive(Debug, Clone)]
+pub struct DiffuseAreaLight {
+    light_to_world: Arc<Transform>,
+    world_to_light: Arc<Transform>,
+    medium_interface: Option<MediumInterface>,
+
+    l_emit: Spectrum,
+    shape: Arc<dyn Shape>,
+    two_sided: bool,
+    area: Float
+}
+
+impl DiffuseAreaLight {
+    // A power strength is spread over the shape, so resizing it keeps the power and changes the
+    // radiance. Radiance strengths keep the radiance instead.
+    pub fn init(medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength, shape: Arc<dyn Shape>, two_sided: bool) -> Result<Self, String> {
+        strength.check("DiffuseAreaLight", true, false, true)?;
+
+        let mut ret = Self {
+            light_to_world: shape.object_to_world(),
+            world_to_light: shape.world_to_object(),
+            medium_interface,
+
+            l_emit: strength.emission(&color),
+            area: shape.area(),
+            shape,
+            two_sided
+        };
+
+        ret.l_emit *= strength.power_scale(&ret.power());
+
+        Ok(ret)
+    }
+}
+
+impl AreaLight for DiffuseAreaLight {
+    fn l(&self, intr: &Interaction, w: &Vector3) -> Spectrum {
+        if self.two_sided || intr.n.dot(w) > 0.0 {
+            self.l_emit
+        } else {
+            Spectrum::new(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+impl Light for DiffuseAreaLight {
+    fn flags(&self) -> u32 { LightFlags::Area as u32 }
+    fn n_samples(&self) -> usize { 1 }
+    fn light_to_world(&self) -> Arc<Transform> { self.light_to_world.clone() }
+    fn world_to_light(&self) -> Arc<Transform> { self.world_to_light.clone() }
+    fn medium_interface(&self) -> Option<MediumInterface> { self.medium_interface.clone() }
+
+    fn sample_li(&self, reference: &Interaction, u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum {
+        let mut p_shape = self.shape.sample_ref(reference, u);
+        p_shape.medium_interface = self.medium_interface.clone();
+
+        if (p_shape.p - reference.p).norm_squared() == 0.0 {
+            *pdf = 0.0;
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        *wi = (p_shape.p - reference.p).normalize();
+        *pdf = self.shape.pdf_ref(reference, wi);
+        let l = self.l(&p_shape, &(-*wi));
+        *vis = VisibilityTester::init(reference.clone(), p_shape);
+
+        l
+    }
+
+    fn pdf_li(&self, reference: &Interaction, wi: &Vector3) -> Float {
+        self.shape.pdf_ref(reference, wi)
+    }
+
+    fn power(&self) -> Spectrum {
+        self.l_emit * self.area * PI * if self.two_sided { 2.0 } else { 1.0 }
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = self.l_emit.max() * self.area * PI * if self.two_sided { 2.0 } else { 1.0 };
+        let cone = DirectionCone::entire_sphere();
+
+        Some(LightBounds::init(&self.shape.world_bound(), &cone.w, phi, cone.cos_theta, (PI / 2.0).cos(), self.two_sided))
+    }
+
+    fn sample_le(&self, u1: &Point2, u2: &Point2, time: Float) -> LeSample {
+        let mut p_shape = self.shape.sample(u1);
+        p_shape.time = time;
+        p_shape.medium_interface = self.medium_interface.clone();
+        let pdf_pos = self.shape.pdf(&p_shape);
+
+        // cosine weighted, over both hemispheres for two sided lights
+        let mut w;
+        let pdf_dir;
+        if self.two_sided {
+            let mut u = *u2;
+            if u.x < 0.5 {
+                u.x = (u.x * 2.0).min(ONE_MINUS_EPSILON);
+                w = cosine_sample_hemisphere(&u);
+            } else {
+                u.x = ((u.x - 0.5) * 2.0).min(ONE_MINUS_EPSILON);
+                w = cosine_sample_hemisphere(&u);
+                w.z *= -1.0;
+            }
+            pdf_dir = 0.5 * cosine_hemisphere_pdf(w.z.abs());
+        } else {
+            w = cosine_sample_hemisphere(u2);
+            pdf_dir = cosine_hemisphere_pdf(w.z);
+        }
+
+        let mut v1 = Vector3::new(0.0, 0.0, 0.0);
+        let mut v2 = Vector3::new(0.0, 0.0, 0.0);
+        coordinate_system(&p_shape.n, &mut v1, &mut v2);
+        w = w.x * v1 + w.y * v2 + w.z * p_shape.n;
+
+        let ray = p_shape.spawn_ray(&w);
+        let le = self.l(&p_shape, &w);
+
+        LeSample { ray, n_light: p_shape.n, pdf_pos, pdf_dir, le }
+    }
+
+    fn pdf_le(&self, ray: &Ray, n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        let it = Interaction::init(&ray.o, &Vector3::new(0.0, 0.0, 0.0), n_light, &Vector3::new(0.0, 0.0, 0.0), ray.time, self.medium_interface.clone());
+        *pdf_pos = self.shape.pdf(&it);
+        *pdf_dir = if self.two_sided {
+            0.5 * cosine_hemisphere_pdf(n_light.dot(&ray.d).abs())
+        } else {
+            cosine_hemisphere_pdf(n_light.dot(&ray.d))
+        };
+    }
+}
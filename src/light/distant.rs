@@ -0,0 +1,87 @@
+use crate::common::*;
+
+// Light arriving from a single direction, like the sun
+#[derive(Debug, Clone)]
+pub struct DistantLight {
+    light_to_world: Arc<Transform>,
+    world_to_light: Arc<Transform>,
+    medium_interface: Option<MediumInterface>,
+
+    // irradiance on a surface facing the light
+    l: Spectrum,
+    // world space direction towards the light
+    w_light: Vector3,
+    world_center: Point3,
+    world_radius: Float
+}
+
+impl DistantLight {
+    // w points towards the light, in light space
+    pub fn init(light_to_world: Arc<Transform>, medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength, w: &Vector3) -> Result<Self, String> {
+        strength.check("DistantLight", false, true, false)?;
+
+        let world_to_light = Arc::from(light_to_world.inverse());
+        let w_light = light_to_world.transform_vector(w).normalize();
+
+        Ok(Self {
+            light_to_world,
+            world_to_light,
+            medium_interface,
+
+            l: strength.emission(&color),
+            w_light,
+            world_center: Point3::new(0.0, 0.0, 0.0),
+            world_radius: 0.0
+        })
+    }
+}
+
+impl Light for DistantLight {
+    fn flags(&self) -> u32 { LightFlags::DeltaDirection as u32 }
+    fn n_samples(&self) -> usize { 1 }
+    fn light_to_world(&self) -> Arc<Transform> { self.light_to_world.clone() }
+    fn world_to_light(&self) -> Arc<Transform> { self.world_to_light.clone() }
+    fn medium_interface(&self) -> Option<MediumInterface> { self.medium_interface.clone() }
+
+    fn preprocess(&mut self, world_bound: &Bounds3f) {
+        world_bound.bounding_sphere(&mut self.world_center, &mut self.world_radius);
+    }
+
+    fn sample_li(&self, reference: &Interaction, _u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum {
+        *wi = self.w_light;
+        *pdf = 1.0;
+
+        let p_outside = reference.p + self.w_light * (2.0 * self.world_radius);
+        *vis = VisibilityTester::init(reference.clone(), Interaction::init_minimal(&p_outside, reference.time, self.medium_interface.clone()));
+
+        self.l
+    }
+
+    fn pdf_li(&self, _reference: &Interaction, _wi: &Vector3) -> Float {
+        0.0
+    }
+
+    // power falling on a disc the size of the scene
+    fn power(&self) -> Spectrum {
+        self.l * PI * self.world_radius * self.world_radius
+    }
+
+    fn sample_le(&self, u1: &Point2, _u2: &Point2, time: Float) -> LeSample {
+        // start on a disc perpendicular to the light, outside the scene
+        let mut v1 = Vector3::new(0.0, 0.0, 0.0);
+        let mut v2 = Vector3::new(0.0, 0.0, 0.0);
+        coordinate_system(&self.w_light, &mut v1, &mut v2);
+        let cd = sample_concentric_disc(u1);
+        let p_disk = self.world_center + self.world_radius * (cd.x * v1 + cd.y * v2);
+
+        let ray = Ray::init(&(p_disk + self.world_radius * self.w_light), &(-self.w_light), Some(INFINITY), Some(time), None);
+        let pdf_pos = 1.0 / (PI * self.world_radius * self.world_radius);
+
+        LeSample { n_light: ray.d, ray, pdf_pos, pdf_dir: 1.0, le: self.l }
+    }
+
+    fn pdf_le(&self, _ray: &Ray, _n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        *pdf_pos = 1.0 / (PI * self.world_radius * self.world_radius);
+        *pdf_dir = 0.0;
+    }
+}
@@ -0,0 +1,95 @@
+use crate::common::*;
+
+// Point light whose intensity varies with direction according to measured photometric data
+#[derive(Debug, Clone)]
+pub struct GoniometricLight {
+    light_to_world: Arc<Transform>,
+    world_to_light: Arc<Transform>,
+    medium_interface: Option<MediumInterface>,
+
+    p_light: Point3,
+    // radiometric intensity of one candela in the light's colour
+    i: Spectrum,
+    profile: Arc<IesProfile>
+}
+
+impl GoniometricLight {
+    // color only sets the hue, the profile gives the candela values. A Scale strength multiplies
+    // them, a power rescales the distribution so it emits exactly that much.
+    pub fn init(light_to_world: Arc<Transform>, medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength, profile: Arc<IesProfile>) -> Result<Self, String> {
+        strength.check("GoniometricLight", true, false, false)?;
+
+        let world_to_light = Arc::from(light_to_world.inverse());
+        let p_light = light_to_world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+
+        let mut i = match strength {
+            LightStrength::Scale(k) => photometric_normalize(&color.spectrum()) * k,
+            _ => strength.emission(&color)
+        };
+        i *= strength.power_scale(&(i * profile.flux()));
+
+        Ok(Self {
+            light_to_world,
+            world_to_light,
+            medium_interface,
+
+            p_light,
+            i,
+            profile
+        })
+    }
+
+    pub fn profile(&self) -> Arc<IesProfile> {
+        self.profile.clone()
+    }
+
+    // radiometric intensity towards the world space direction w
+    pub fn intensity(&self, w: &Vector3) -> Spectrum {
+        let wl = self.world_to_light.transform_vector(w);
+
+        self.i * self.profile.evaluate(&wl)
+    }
+}
+
+impl Light for GoniometricLight {
+    fn flags(&self) -> u32 { LightFlags::DeltaPosition as u32 }
+    fn n_samples(&self) -> usize { 1 }
+    fn light_to_world(&self) -> Arc<Transform> { self.light_to_world.clone() }
+    fn world_to_light(&self) -> Arc<Transform> { self.world_to_light.clone() }
+    fn medium_interface(&self) -> Option<MediumInterface> { self.medium_interface.clone() }
+
+    fn sample_li(&self, reference: &Interaction, _u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum {
+        *wi = (self.p_light - reference.p).normalize();
+        *pdf = 1.0;
+        *vis = VisibilityTester::init(reference.clone(), Interaction::init_minimal(&self.p_light, reference.time, self.medium_interface.clone()));
+
+        self.intensity(&(-*wi)) / (self.p_light - reference.p).norm_squared()
+    }
+
+    fn pdf_li(&self, _reference: &Interaction, _wi: &Vector3) -> Float {
+        0.0
+    }
+
+    fn power(&self) -> Spectrum {
+        self.i * self.profile.flux()
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.i.max() * self.profile.max_candela();
+
+        Some(LightBounds::init(&Bounds3f::init_one(&self.p_light), &Vector3::new(0.0, 0.0, 1.0), phi, PI.cos(), (PI / 2.0).cos(), false))
+    }
+
+    fn sample_le(&self, u1: &Point2, _u2: &Point2, time: Float) -> LeSample {
+        let medium = self.medium_interface.as_ref().and_then(|mi| mi.inside.clone());
+        let ray = Ray::init(&self.p_light, &uniform_sample_sphere(u1), Some(INFINITY), Some(time), medium);
+        let le = self.intensity(&ray.d);
+
+        LeSample { n_light: ray.d, ray, pdf_pos: 1.0, pdf_dir: uniform_sphere_pdf(), le }
+    }
+
+    fn pdf_le(&self, _ray: &Ray, _n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        *pdf_pos = 0.0;
+        *pdf_dir = uniform_sphere_pdf();
+    }
+}
@@ -0,0 +1,340 @@
+use std::fs;
+
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IesPhotometricType {
+    C = 1,
+    B = 2,
+    A = 3
+}
+
+// Photometric data from an IES LM-63 file. Only type C goniometry is supported, which
+// is what nearly every architectural luminaire is measured with.
+//
+// Luminaire space has +z up, so vertical angle 0 (nadir) looks down -z. Horizontal
+// angle 0 lies along +x and 90 along +y.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    pub photometric_type: IesPhotometricType,
+    pub n_lamps: usize,
+    pub lumens_per_lamp: Float,
+    pub candela_multiplier: Float,
+    pub ballast_factor: Float,
+    pub input_watts: Float,
+
+    // degrees, both sorted ascending as the standard requires
+    pub vertical_angles: Vec<Float>,
+    pub horizontal_angles: Vec<Float>,
+    // candela[h * vertical_angles.len() + v], multiplier not applied
+    pub candela: Vec<Float>
+}
+
+impl IesProfile {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read IES file {}: {}", path, e))?;
+
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+
+        // skip the IESNA line and the [KEYWORD] header until the TILT line
+        let tilt = loop {
+            match lines.next() {
+                Some(line) => {
+                    let line = line.trim();
+                    if let Some(rest) = line.strip_prefix("TILT") {
+                        break rest.trim_start_matches(|c: char| c == '=' || c.is_whitespace()).to_string();
+                    }
+                },
+                None => return Err("IES file has no TILT line".to_string())
+            }
+        };
+
+        let rest: Vec<&str> = lines.collect();
+        let mut tokens = rest.iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|t| !t.is_empty());
+
+        let mut next = || -> Result<Float, String> {
+            match tokens.next() {
+                Some(t) => t.parse::<Float>().map_err(|_| format!("Invalid number in IES data: {}", t)),
+                None => Err("Unexpected end of IES data".to_string())
+            }
+        };
+
+        // tilt data only matters for lamps measured at an angle, skip past it
+        if tilt == "INCLUDE" {
+            let _lamp_to_luminaire_geometry = next()?;
+            let n_pairs = next()? as usize;
+            for _ in 0..(2 * n_pairs) {
+                next()?;
+            }
+        }
+
+        let n_lamps = next()? as usize;
+        let lumens_per_lamp = next()?;
+        let candela_multiplier = next()?;
+        let n_vertical = next()? as usize;
+        let n_horizontal = next()? as usize;
+        let photometric_type = match next()? as i32 {
+            1 => IesPhotometricType::C,
+            2 => IesPhotometricType::B,
+            3 => IesPhotometricType::A,
+            t => return Err(format!("Unknown IES photometric type {}", t))
+        };
+        let _units_type = next()?;
+        let _width = next()?;
+        let _length = next()?;
+        let _height = next()?;
+
+        let ballast_factor = next()?;
+        let _ballast_lamp_factor = next()?;
+        let input_watts = next()?;
+
+        if photometric_type != IesPhotometricType::C {
+            return Err("Only type C IES photometry is supported".to_string());
+        }
+        if n_vertical == 0 || n_horizontal == 0 {
+            return Err("IES file has an empty angle table".to_string());
+        }
+
+        let mut vertical_angles = Vec::with_capacity(n_vertical);
+        for _ in 0..n_vertical {
+            vertical_angles.push(next()?);
+        }
+        let mut horizontal_angles = Vec::with_capacity(n_horizontal);
+        for _ in 0..n_horizontal {
+            horizontal_angles.push(next()?);
+        }
+        let mut candela = Vec::with_capacity(n_vertical * n_horizontal);
+        for _ in 0..(n_vertical * n_horizontal) {
+            candela.push(next()?);
+        }
+
+        if vertical_angles.windows(2).any(|w| w[0] > w[1]) || horizontal_angles.windows(2).any(|w| w[0] > w[1]) {
+            return Err("IES angles must be in ascending order".to_string());
+        }
+
+        Ok(Self {
+            photometric_type,
+            n_lamps,
+            lumens_per_lamp,
+            candela_multiplier,
+            ballast_factor,
+            input_watts,
+
+            vertical_angles,
+            horizontal_angles,
+            candela
+        })
+    }
+
+    fn scale(&self) -> Float {
+        self.candela_multiplier * self.ballast_factor
+    }
+
+    // folds a horizontal angle into the range covered by the table using its symmetry
+    fn fold_horizontal(&self, phi: Float) -> Float {
+        let first = self.horizontal_angles[0];
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+
+        if first == 90.0 && last == 270.0 {
+            // symmetric about the 90-270 plane
+            if phi < 90.0 { 180.0 - phi } else if phi > 270.0 { 540.0 - phi } else { phi }
+        } else if last == 90.0 {
+            // quadrant symmetric
+            let phi = phi % 180.0;
+            if phi > 90.0 { 180.0 - phi } else { phi }
+        } else if last == 180.0 {
+            // symmetric about the 0-180 plane
+            if phi > 180.0 { 360.0 - phi } else { phi }
+        } else {
+            phi
+        }
+    }
+
+    // index i such that angles[i] <= x <= angles[i + 1] and the lerp weight, None if out of range
+    fn find_interval(angles: &[Float], x: Float) -> Option<(usize, Float)> {
+        let n = angles.len();
+        if x < angles[0] || x > angles[n - 1] {
+            return None;
+        }
+        if n == 1 {
+            return Some((0, 0.0));
+        }
+
+        let i = angles.partition_point(|a| *a <= x).clamp(1, n - 1) - 1;
+        let width = angles[i + 1] - angles[i];
+        let t = if width > 0.0 { (x - angles[i]) / width } else { 0.0 };
+
+        Some((i, t.clamp(0.0, 1.0)))
+    }
+
+    fn candela_at(&self, h: usize, v: usize) -> Float {
+        self.candela[h * self.vertical_angles.len() + v]
+    }
+
+    fn evaluate_vertical(&self, h: usize, theta: Float) -> Float {
+        match Self::find_interval(&self.vertical_angles, theta) {
+            Some((v, t)) => {
+                let v1 = (v + 1).min(self.vertical_angles.len() - 1);
+                lerp(t, self.candela_at(h, v), self.candela_at(h, v1))
+            },
+            None => 0.0
+        }
+    }
+
+    // luminous intensity in candela, angles in degrees
+    pub fn evaluate_angles(&self, theta: Float, phi: Float) -> Float {
+        let n_h = self.horizontal_angles.len();
+        let last = self.horizontal_angles[n_h - 1];
+
+        let value = if n_h == 1 || last == 0.0 {
+            self.evaluate_vertical(0, theta)
+        } else {
+            let phi = self.fold_horizontal(phi.rem_euclid(360.0));
+            match Self::find_interval(&self.horizontal_angles, phi) {
+                Some((h, t)) => {
+                    let h1 = (h + 1).min(n_h - 1);
+                    lerp(t, self.evaluate_vertical(h, theta), self.evaluate_vertical(h1, theta))
+                },
+                None => 0.0
+            }
+        };
+
+        value * self.scale()
+    }
+
+    // luminous intensity in candela towards w, given in luminaire space
+    pub fn evaluate(&self, w: &Vector3) -> Float {
+        let w = w.normalize();
+        let theta = (-w.z).clamp(-1.0, 1.0).acos().to_degrees();
+        let phi = spherical_phi(&w).to_degrees();
+
+        self.evaluate_angles(theta, phi)
+    }
+
+    pub fn max_candela(&self) -> Float {
+        self.candela.iter().fold(0.0 as Float, |a, b| a.max(*b)) * self.scale()
+    }
+
+    // total luminous flux of the distribution in lumens
+    pub fn flux(&self) -> Float {
+        const N_THETA: usize = 256;
+        const N_PHI: usize = 512;
+
+        let d_theta = PI / N_THETA as Float;
+        let d_phi = 2.0 * PI / N_PHI as Float;
+
+        let mut sum = 0.0;
+        for i in 0..N_THETA {
+            let theta = (i as Float + 0.5) * d_theta;
+            let mut ring = 0.0;
+            for j in 0..N_PHI {
+                let phi = (j as Float + 0.5) * d_phi;
+                ring += self.evaluate_angles(theta.to_degrees(), phi.to_degrees());
+            }
+            sum += ring * theta.sin();
+        }
+
+        sum * d_theta * d_phi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an isotropic 100 cd lamp over the lower hemisphere and half of the upper one, doubled by
+    // the multiplier
+    const HEMISPHERE: &str = "IESNA:LM-63-2002
+[TEST] hemisphere
+TILT=NONE
+1 1000 2.0 4 1 1 2 0 0 0
+1.0 1.0 60
+0 45 90 135
+0
+100 100 100 0";
+
+    #[test]
+    fn parses_header_and_tables() {
+        let profile = IesProfile::parse(HEMISPHERE).unwrap();
+
+        assert_eq!(profile.photometric_type, IesPhotometricType::C);
+        assert_eq!(profile.n_lamps, 1);
+        assert_eq!(profile.lumens_per_lamp, 1000.0);
+        assert_eq!(profile.input_watts, 60.0);
+        assert_eq!(profile.vertical_angles, vec![0.0, 45.0, 90.0, 135.0]);
+        assert_eq!(profile.horizontal_angles, vec![0.0]);
+        assert_eq!(profile.max_candela(), 200.0);
+
+        // nadir looks down -z, and angles past the table are dark
+        assert_eq!(profile.evaluate(&Vector3::new(0.0, 0.0, -1.0)), 200.0);
+        assert_eq!(profile.evaluate_angles(112.5, 30.0), 100.0);
+        assert_eq!(profile.evaluate(&Vector3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn skips_included_tilt_and_commas() {
+        let text = "IESNA91
+TILT=INCLUDE
+1
+3
+0, 45, 90
+1, 0.9, 0.8
+1 -1 1 2 2 1 1 0 0 0
+1 1 10
+0 180
+0 90
+10 20
+30 40";
+        let profile = IesProfile::parse(text).unwrap();
+
+        assert_eq!(profile.horizontal_angles, vec![0.0, 90.0]);
+        assert_eq!(profile.candela, vec![10.0, 20.0, 30.0, 40.0]);
+        // quadrant symmetry folds 180 onto 0 and 270 onto 90
+        assert_eq!(profile.evaluate_angles(0.0, 180.0), 10.0);
+        assert_eq!(profile.evaluate_angles(180.0, 270.0), 40.0);
+        assert_eq!(profile.evaluate_angles(90.0, 45.0), 25.0);
+    }
+
+    #[test]
+    fn flux_matches_the_integral() {
+        // 200 cd over the lower hemisphere, then falling linearly to 0 at 135 degrees, where
+        // int_0^a (1 - x / a) cos x dx = (1 - cos a) / a
+        let profile = IesProfile::parse(HEMISPHERE).unwrap();
+        let a = PI / 4.0;
+        let expected = 2.0 * PI * 200.0 * (1.0 + (1.0 - a.cos()) / a);
+
+        let flux = profile.flux();
+        assert!((flux - expected).abs() < 1e-3 * expected, "{} vs {}", flux, expected);
+    }
+
+    #[test]
+    fn goniometric_light_emits_the_profile_flux() {
+        let profile = Arc::new(IesProfile::parse(HEMISPHERE).unwrap());
+        let white = LightColor::Rgb(Spectrum::new(1.0, 1.0, 1.0));
+        let identity = Arc::new(Transform::identity());
+
+        // candela values as given, so the light emits the profile's flux in lumens
+        let light = GoniometricLight::init(identity.clone(), None, white, LightStrength::Scale(1.0), profile.clone()).unwrap();
+        let lumens = spectrum_to_photometric(&light.power());
+        assert!((lumens - profile.flux()).abs() < 1e-3 * lumens, "{} vs {}", lumens, profile.flux());
+
+        let light = GoniometricLight::init(identity, None, white, LightStrength::Lumens(1000.0), profile).unwrap();
+        let lumens = spectrum_to_photometric(&light.power());
+        assert!((lumens - 1000.0).abs() < 1e-2, "{}", lumens);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(IesProfile::parse("IESNA:LM-63-2002\n1 1000 1 1 1 1 2 0 0 0").is_err());
+        assert!(IesProfile::parse(&HEMISPHERE.replace("4 1 1 2", "4 1 2 2")).is_err());
+        assert!(IesProfile::parse(&HEMISPHERE.replace("0 45 90 135", "0 90 45 135")).is_err());
+        assert!(IesProfile::parse(&HEMISPHERE.replace("100 100 100 0", "100 100 100")).is_err());
+        assert!(IesProfile::parse(&HEMISPHERE.replace("4 1 1 2", "0 1 1 2")).is_err());
+    }
+}
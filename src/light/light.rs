@@ -0,0 +1,54 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightFlags {
+    DeltaPosition = 1,
+    DeltaDirection = 2,
+    Area = 4,
+    Infinite = 8
+}
+
+pub fn is_delta_light(flags: u32) -> bool {
+    flags & LightFlags::DeltaPosition as u32 != 0 || flags & LightFlags::DeltaDirection as u32 != 0
+}
+
+// A ray leaving a light, with the densities of its origin and direction
+#[derive(Debug, Clone)]
+pub struct LeSample {
+    pub ray: Ray,
+    pub n_light: Vector3,
+    pub pdf_pos: Float,
+    pub pdf_dir: Float,
+    pub le: Spectrum
+}
+
+pub trait Light: Debug {
+    // bitwise or of LightFlags
+    fn flags(&self) -> u32;
+    fn n_samples(&self) -> usize;
+    fn light_to_world(&self) -> Arc<Transform>;
+    fn world_to_light(&self) -> Arc<Transform>;
+    fn medium_interface(&self) -> Option<MediumInterface>;
+
+    // called once the scene is known, before the light is shared
+    fn preprocess(&mut self, _world_bound: &Bounds3f) {}
+
+    fn sample_li(&self, reference: &Interaction, u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum;
+    fn pdf_li(&self, reference: &Interaction, wi: &Vector3) -> Float;
+
+    // total emitted power, used to pick lights proportionally
+    fn power(&self) -> Spectrum;
+
+    // bounds used by the light BVH, None for lights at infinity
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    // radiance along rays that escape the scene, only infinite lights return non zero
+    fn le(&self, _ray: &RayDifferential) -> Spectrum {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample_le(&self, u1: &Point2, u2: &Point2, time: Float) -> LeSample;
+    fn pdf_le(&self, ray: &Ray, n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float);
+}
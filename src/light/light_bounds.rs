@@ -0,0 +1,179 @@
+use crate::common::*;
+
+// Set of directions within acos(cos_theta) of w
+#[derive(Debug, Clone, Copy)]
+pub struct DirectionCone {
+    pub w: Vector3,
+    pub cos_theta: Float
+}
+
+impl DirectionCone {
+    pub fn init(w: &Vector3, cos_theta: Float) -> Self {
+        Self {
+            w: w.normalize(),
+            cos_theta
+        }
+    }
+
+    pub fn entire_sphere() -> Self {
+        Self {
+            w: Vector3::new(0.0, 0.0, 1.0),
+            cos_theta: -1.0
+        }
+    }
+
+    // cone of directions from p that can reach the bounds
+    pub fn bound_subtended_directions(b: &Bounds3f, p: &Point3) -> Self {
+        let mut center = Point3::new(0.0, 0.0, 0.0);
+        let mut radius = 0.0;
+        b.bounding_sphere(&mut center, &mut radius);
+
+        let dist2 = (p - center).norm_squared();
+        if dist2 < radius * radius {
+            return Self::entire_sphere();
+        }
+
+        let sin2_theta_max = radius * radius / dist2;
+        let cos_theta_max = (1.0 - sin2_theta_max).max(0.0).sqrt();
+
+        Self::init(&(center - p), cos_theta_max)
+    }
+
+    pub fn union(a: &Self, b: &Self) -> Self {
+        let theta_a = a.cos_theta.clamp(-1.0, 1.0).acos();
+        let theta_b = b.cos_theta.clamp(-1.0, 1.0).acos();
+        let theta_d = a.w.dot(&b.w).clamp(-1.0, 1.0).acos();
+
+        // one cone already contains the other
+        if (theta_d + theta_b).min(PI) <= theta_a {
+            return *a;
+        }
+        if (theta_d + theta_a).min(PI) <= theta_b {
+            return *b;
+        }
+
+        let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+        if theta_o >= PI {
+            return Self::entire_sphere();
+        }
+
+        // rotate a.w towards b.w so the new cone touches the far sides of both
+        let theta_r = theta_o - theta_a;
+        let wr = a.w.cross(&b.w);
+        if wr.norm_squared() == 0.0 {
+            return Self::entire_sphere();
+        }
+        let rotation = na::Rotation3::from_axis_angle(&na::Unit::new_normalize(wr), theta_r);
+
+        Self::init(&(rotation * a.w), theta_o.cos())
+    }
+}
+
+fn cos_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+fn sin_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+fn safe_sqrt(x: Float) -> Float {
+    x.max(0.0).sqrt()
+}
+
+// Spatial and directional bounds of the emission of one or more lights.
+// Light leaves in directions within theta_o of w, and falls off to zero by theta_o + theta_e.
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    pub bounds: Bounds3f,
+    pub w: Vector3,
+    // scalar bound on emitted power
+    pub phi: Float,
+    pub cos_theta_o: Float,
+    pub cos_theta_e: Float,
+    pub two_sided: bool
+}
+
+impl LightBounds {
+    pub fn init(bounds: &Bounds3f, w: &Vector3, phi: Float, cos_theta_o: Float, cos_theta_e: Float, two_sided: bool) -> Self {
+        Self {
+            bounds: *bounds,
+            w: w.normalize(),
+            phi,
+            cos_theta_o,
+            cos_theta_e,
+            two_sided
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        na::center(&self.bounds.p_min, &self.bounds.p_max)
+    }
+
+    pub fn union(a: &Self, b: &Self) -> Self {
+        if a.phi == 0.0 {
+            return *b;
+        }
+        if b.phi == 0.0 {
+            return *a;
+        }
+
+        let cone = DirectionCone::union(&DirectionCone::init(&a.w, a.cos_theta_o), &DirectionCone::init(&b.w, b.cos_theta_o));
+
+        Self {
+            bounds: Bounds3f::union(&a.bounds, &b.bounds),
+            w: cone.w,
+            phi: a.phi + b.phi,
+            cos_theta_o: cone.cos_theta,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+            two_sided: a.two_sided || b.two_sided
+        }
+    }
+
+    // conservative estimate of the contribution to a point p with normal n, n may be zero
+    pub fn importance(&self, p: &Point3, n: &Vector3) -> Float {
+        let pc = self.centroid();
+        let d2 = (p - pc).norm_squared().max(self.bounds.diagonal().norm() / 2.0);
+
+        let wi = (p - pc).normalize();
+        let mut cos_theta_w = self.w.dot(&wi);
+        if self.two_sided {
+            cos_theta_w = cos_theta_w.abs();
+        }
+        if cos_theta_w.is_nan() {
+            cos_theta_w = 1.0;
+        }
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+
+        // angle subtended by the bounds as seen from p
+        let cos_theta_b = DirectionCone::bound_subtended_directions(&self.bounds, p).cos_theta;
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+
+        // smallest angle between the emission cone and the direction to p
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.phi * cos_theta_p / d2;
+
+        // smallest incident angle at p
+        if *n != Vector3::new(0.0, 0.0, 0.0) {
+            let cos_theta_i = wi.dot(n).abs();
+            let sin_theta_i = safe_sqrt(1.0 - cos_theta_i * cos_theta_i);
+            let cos_theta_p_i = cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+            importance *= cos_theta_p_i;
+        }
+
+        importance.max(0.0)
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+
+use crate::common::*;
+
+#[derive(Debug, Clone)]
+struct LightBVHNode {
+    light_bounds: LightBounds,
+    // light index for leaves, second child for interior nodes. The first child always follows its parent
+    child_or_light_index: usize,
+    is_leaf: bool
+}
+
+// Picks lights by descending a BVH over their LightBounds, choosing each child by its estimated
+// importance to the shading point. Lights without bounds (infinite lights) are sampled uniformly
+// alongside the root.
+#[derive(Debug)]
+pub struct BVHLightSampler {
+    lights: Vec<Arc<dyn Light>>,
+    infinite_lights: Vec<Arc<dyn Light>>,
+    nodes: Vec<LightBVHNode>,
+    // path from the root to each light, bit i set means take the second child at depth i
+    light_to_bit_trail: HashMap<usize, u64>
+}
+
+impl BVHLightSampler {
+    pub fn init(lights: &[Arc<dyn Light>]) -> Self {
+        let mut ret = Self {
+            lights: lights.to_vec(),
+            infinite_lights: Vec::new(),
+            nodes: Vec::new(),
+            light_to_bit_trail: HashMap::new()
+        };
+
+        let mut bvh_lights: Vec<(usize, LightBounds)> = Vec::new();
+        for (i, light) in lights.iter().enumerate() {
+            match light.bounds() {
+                Some(light_bounds) => {
+                    if light_bounds.phi > 0.0 {
+                        bvh_lights.push((i, light_bounds));
+                    }
+                },
+                None => ret.infinite_lights.push(light.clone())
+            }
+        }
+
+        if !bvh_lights.is_empty() {
+            let n = bvh_lights.len();
+            ret.build_bvh(&mut bvh_lights, 0, n, 0, 0);
+        }
+
+        ret
+    }
+
+    fn empty_light_bounds() -> LightBounds {
+        LightBounds::init(&Bounds3f::new(), &Vector3::new(0.0, 0.0, 1.0), 0.0, 1.0, 1.0, false)
+    }
+
+    // surface area orientation heuristic, cost of a node holding the lights in b
+    fn evaluate_cost(b: &LightBounds, bounds: &Bounds3f, dim: usize) -> Float {
+        let theta_o = b.cos_theta_o.clamp(-1.0, 1.0).acos();
+        let theta_e = b.cos_theta_e.clamp(-1.0, 1.0).acos();
+        let theta_w = (theta_o + theta_e).min(PI);
+        let sin_theta_o = (1.0 - b.cos_theta_o * b.cos_theta_o).max(0.0).sqrt();
+
+        // solid angle measure of the emission cone and its falloff
+        let m_omega = 2.0 * PI * (1.0 - b.cos_theta_o) +
+            PI / 2.0 * (2.0 * theta_w * sin_theta_o - (theta_o - 2.0 * theta_w).cos() - 2.0 * theta_o * sin_theta_o + b.cos_theta_o);
+
+        // penalise thin slabs
+        let diag = bounds.diagonal();
+        let kr = diag.max() / diag[dim];
+
+        b.phi * m_omega * kr * b.bounds.surface_area()
+    }
+
+    fn build_bvh(&mut self, bvh_lights: &mut Vec<(usize, LightBounds)>, start: usize, end: usize, bit_trail: u64, depth: usize) -> (usize, LightBounds) {
+        assert!(depth < 64, "Light BVH is too deep for the bit trail");
+
+        if end - start == 1 {
+            let node_index = self.nodes.len();
+            let (light_index, light_bounds) = bvh_lights[start];
+            self.nodes.push(LightBVHNode { light_bounds, child_or_light_index: light_index, is_leaf: true });
+            self.light_to_bit_trail.insert(light_key(&self.lights[light_index]), bit_trail);
+
+            return (node_index, light_bounds);
+        }
+
+        let mut bounds = Bounds3f::new();
+        let mut centroid_bounds = Bounds3f::new();
+        for (_, light_bounds) in &bvh_lights[start..end] {
+            bounds = Bounds3f::union(&bounds, &light_bounds.bounds);
+            centroid_bounds = Bounds3f::union_pt(&centroid_bounds, &light_bounds.centroid());
+        }
+
+        const N_BUCKETS: usize = 12;
+        let bucket_of = |c: &Point3, dim: usize| -> usize {
+            ((N_BUCKETS as Float * centroid_bounds.offset(c)[dim]) as usize).min(N_BUCKETS - 1)
+        };
+
+        let mut min_cost = INFINITY;
+        let mut min_cost_split: Option<(usize, usize)> = None;
+        for dim in 0..3 {
+            if centroid_bounds.p_max[dim] == centroid_bounds.p_min[dim] {
+                continue;
+            }
+
+            let mut bucket_light_bounds = [Self::empty_light_bounds(); N_BUCKETS];
+            for (_, light_bounds) in &bvh_lights[start..end] {
+                let b = bucket_of(&light_bounds.centroid(), dim);
+                bucket_light_bounds[b] = LightBounds::union(&bucket_light_bounds[b], light_bounds);
+            }
+
+            for i in 0..(N_BUCKETS - 1) {
+                let mut b0 = Self::empty_light_bounds();
+                let mut b1 = Self::empty_light_bounds();
+                for bucket in &bucket_light_bounds[0..=i] {
+                    b0 = LightBounds::union(&b0, bucket);
+                }
+                for bucket in &bucket_light_bounds[(i + 1)..N_BUCKETS] {
+                    b1 = LightBounds::union(&b1, bucket);
+                }
+
+                let cost = Self::evaluate_cost(&b0, &bounds, dim) + Self::evaluate_cost(&b1, &bounds, dim);
+                if cost > 0.0 && cost < min_cost {
+                    min_cost = cost;
+                    min_cost_split = Some((dim, i));
+                }
+            }
+        }
+
+        let mut mid = (start + end) / 2;
+        match min_cost_split {
+            Some((dim, bucket)) => {
+                let mut left = start;
+                for i in start..end {
+                    if bucket_of(&bvh_lights[i].1.centroid(), dim) <= bucket {
+                        bvh_lights.swap(i, left);
+                        left += 1;
+                    }
+                }
+                if left != start && left != end {
+                    mid = left;
+                }
+            },
+            None => {
+                // point lights have no area so every split costs nothing, use the spatial median
+                let dim = centroid_bounds.max_extent();
+                bvh_lights[start..end].select_nth_unstable_by(mid - start, |a, b| {
+                    a.1.centroid()[dim].partial_cmp(&b.1.centroid()[dim]).unwrap()
+                });
+            }
+        }
+
+        let node_index = self.nodes.len();
+        self.nodes.push(LightBVHNode { light_bounds: Self::empty_light_bounds(), child_or_light_index: 0, is_leaf: false });
+
+        let (_, lb0) = self.build_bvh(bvh_lights, start, mid, bit_trail, depth + 1);
+        let (child1, lb1) = self.build_bvh(bvh_lights, mid, end, bit_trail | (1u64 << depth), depth + 1);
+
+        let light_bounds = LightBounds::union(&lb0, &lb1);
+        self.nodes[node_index] = LightBVHNode { light_bounds, child_or_light_index: child1, is_leaf: false };
+
+        (node_index, light_bounds)
+    }
+
+    fn p_infinite(&self) -> Float {
+        let n_bvh = if self.nodes.is_empty() { 0.0 } else { 1.0 };
+        let n_infinite = self.infinite_lights.len() as Float;
+
+        if n_infinite + n_bvh == 0.0 { 0.0 } else { n_infinite / (n_infinite + n_bvh) }
+    }
+}
+
+impl LightSampler for BVHLightSampler {
+    fn sample(&self, ctx: &LightSampleContext, u: Float) -> Option<SampledLight> {
+        let p_infinite = self.p_infinite();
+
+        if u < p_infinite {
+            let n = self.infinite_lights.len();
+            let idx = ((u / p_infinite * n as Float) as usize).min(n - 1);
+            return Some(SampledLight { light: self.infinite_lights[idx].clone(), p: p_infinite / n as Float });
+        }
+
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut u = ((u - p_infinite) / (1.0 - p_infinite)).min(ONE_MINUS_EPSILON);
+        let mut node_index = 0usize;
+        let mut pmf = 1.0 - p_infinite;
+
+        loop {
+            let node = &self.nodes[node_index];
+            if node.is_leaf {
+                if node_index > 0 || node.light_bounds.importance(&ctx.p, &ctx.ns) > 0.0 {
+                    return Some(SampledLight { light: self.lights[node.child_or_light_index].clone(), p: pmf });
+                }
+                return None;
+            }
+
+            let children = [node_index + 1, node.child_or_light_index];
+            let ci = [
+                self.nodes[children[0]].light_bounds.importance(&ctx.p, &ctx.ns),
+                self.nodes[children[1]].light_bounds.importance(&ctx.p, &ctx.ns)
+            ];
+            if ci[0] == 0.0 && ci[1] == 0.0 {
+                return None;
+            }
+
+            let p0 = ci[0] / (ci[0] + ci[1]);
+            if u < p0 {
+                node_index = children[0];
+                u = (u / p0).min(ONE_MINUS_EPSILON);
+                pmf *= p0;
+            } else {
+                node_index = children[1];
+                u = ((u - p0) / (1.0 - p0)).min(ONE_MINUS_EPSILON);
+                pmf *= 1.0 - p0;
+            }
+        }
+    }
+
+    fn pmf(&self, ctx: &LightSampleContext, light: &Arc<dyn Light>) -> Float {
+        let mut bit_trail = match self.light_to_bit_trail.get(&light_key(light)) {
+            Some(bit_trail) => *bit_trail,
+            None => {
+                // infinite lights, or lights that emit nothing
+                if light.bounds().is_some() || self.infinite_lights.is_empty() {
+                    return 0.0;
+                }
+                return self.p_infinite() / self.infinite_lights.len() as Float;
+            }
+        };
+
+        let mut pmf = 1.0 - self.p_infinite();
+        let mut node_index = 0usize;
+
+        loop {
+            let node = &self.nodes[node_index];
+            if node.is_leaf {
+                return pmf;
+            }
+
+            let children = [node_index + 1, node.child_or_light_index];
+            let ci = [
+                self.nodes[children[0]].light_bounds.importance(&ctx.p, &ctx.ns),
+                self.nodes[children[1]].light_bounds.importance(&ctx.p, &ctx.ns)
+            ];
+            let child = (bit_trail & 1) as usize;
+            if ci[child] == 0.0 {
+                return 0.0;
+            }
+
+            pmf *= ci[child] / (ci[0] + ci[1]);
+            node_index = children[child];
+            bit_trail >>= 1;
+        }
+    }
+
+    fn sample_no_ctx(&self, u: Float) -> Option<SampledLight> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let n = self.lights.len();
+        let idx = ((u * n as Float) as usize).min(n - 1);
+
+        Some(SampledLight { light: self.lights[idx].clone(), p: 1.0 / n as Float })
+    }
+
+    fn pmf_no_ctx(&self, _light: &Arc<dyn Light>) -> Float {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+
+        1.0 / self.lights.len() as Float
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene_lights() -> Vec<Arc<dyn Light>> {
+        let white = LightColor::Rgb(Spectrum::new(1.0, 1.0, 1.0));
+        let mut lights: Vec<Arc<dyn Light>> = Vec::new();
+        for i in 0..12 {
+            let x = (i % 4) as Float * 2.0 - 3.0;
+            let y = (i / 4) as Float * 3.0 - 3.0;
+            let z = if i % 3 == 0 { -1.0 } else { 1.0 + i as Float * 0.25 };
+            let strength = LightStrength::Watts(1.0 + i as Float);
+            lights.push(Arc::from(PointLight::init(Arc::from(translate(&Vector3::new(x, y, z))), None, white, strength).unwrap()));
+        }
+        let strength = LightStrength::Irradiance(1.0);
+        lights.push(Arc::from(DistantLight::init(Arc::from(Transform::identity()), None, white, strength, &Vector3::new(0.0, 0.0, 1.0)).unwrap()));
+
+        lights
+    }
+
+    #[test]
+    fn pmf_agrees_with_sample() {
+        let lights = scene_lights();
+        let sampler = BVHLightSampler::init(&lights);
+
+        for (p, n) in [(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0)), (Point3::new(2.0, 1.0, 0.5), Vector3::new(0.6, 0.0, -0.8))] {
+            let ctx = LightSampleContext::init(&p, &n, &n);
+
+            let samples = 40000;
+            let mut counts = vec![0; lights.len()];
+            for i in 0..samples {
+                if let Some(sampled) = sampler.sample(&ctx, (i as Float + 0.5) / samples as Float) {
+                    let idx = lights.iter().position(|l| light_key(l) == light_key(&sampled.light)).unwrap();
+                    let pmf = sampler.pmf(&ctx, &sampled.light);
+                    assert!((sampled.p - pmf).abs() < 1e-4 * pmf.max(1.0), "light {}: sampled {} pmf {}", idx, sampled.p, pmf);
+                    counts[idx] += 1;
+                }
+            }
+
+            let mut total = 0.0;
+            for (idx, light) in lights.iter().enumerate() {
+                let pmf = sampler.pmf(&ctx, light);
+                let frequency = counts[idx] as Float / samples as Float;
+                assert!((frequency - pmf).abs() < 1e-3, "light {}: frequency {} pmf {}", idx, frequency, pmf);
+                total += pmf;
+            }
+            assert!(total <= 1.0 + 1e-4, "{}", total);
+        }
+    }
+
+    #[test]
+    fn infinite_lights_share_the_root() {
+        let lights = scene_lights();
+        let sampler = BVHLightSampler::init(&lights);
+        let ctx = LightSampleContext::init(&Point3::new(0.0, 0.0, 0.0), &Vector3::new(0.0, 0.0, 1.0), &Vector3::new(0.0, 0.0, 1.0));
+
+        // one distant light against the BVH root
+        let distant = lights.last().unwrap();
+        assert!((sampler.pmf(&ctx, distant) - 0.5).abs() < 1e-6);
+        assert_eq!(light_key(&sampler.sample(&ctx, 0.25).unwrap().light), light_key(distant));
+
+        assert!(BVHLightSampler::init(&[]).sample(&ctx, 0.5).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::common::*;
+
+#[derive(Debug, Clone)]
+struct LinkList {
+    // when set only these names are linked
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>
+}
+
+impl LinkList {
+    fn new() -> Self {
+        Self {
+            include: None,
+            exclude: HashSet::new()
+        }
+    }
+
+    fn allows(&self, name: Option<&str>) -> bool {
+        if let Some(include) = &self.include {
+            match name {
+                Some(name) if include.contains(name) => {},
+                _ => return false
+            }
+        }
+
+        match name {
+            Some(name) => !self.exclude.contains(name),
+            None => true
+        }
+    }
+}
+
+// Restricts which lights illuminate which objects. Links can be set from either side, a light
+// lists the objects it lights and an object lists the lights it receives. Both have to agree.
+// Objects are named through GeometricPrimitive::set_name, lights are named here.
+#[derive(Debug, Clone)]
+pub struct LightLinking {
+    light_names: HashMap<usize, String>,
+    light_links: HashMap<String, LinkList>,
+    object_links: HashMap<String, LinkList>
+}
+
+impl Default for LightLinking {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LightLinking {
+    pub fn new() -> Self {
+        Self {
+            light_names: HashMap::new(),
+            light_links: HashMap::new(),
+            object_links: HashMap::new()
+        }
+    }
+
+    pub fn set_light_name(&mut self, light: &Arc<dyn Light>, name: &str) {
+        self.light_names.insert(light_key(light), name.to_string());
+    }
+
+    pub fn light_name(&self, light: &Arc<dyn Light>) -> Option<&str> {
+        self.light_names.get(&light_key(light)).map(|n| n.as_str())
+    }
+
+    // the light only illuminates objects that were included
+    pub fn include_object(&mut self, light_name: &str, object_name: &str) {
+        let link = self.light_links.entry(light_name.to_string()).or_insert_with(LinkList::new);
+        link.include.get_or_insert_with(HashSet::new).insert(object_name.to_string());
+    }
+
+    pub fn exclude_object(&mut self, light_name: &str, object_name: &str) {
+        let link = self.light_links.entry(light_name.to_string()).or_insert_with(LinkList::new);
+        link.exclude.insert(object_name.to_string());
+    }
+
+    // the object is only illuminated by lights that were included
+    pub fn include_light(&mut self, object_name: &str, light_name: &str) {
+        let link = self.object_links.entry(object_name.to_string()).or_insert_with(LinkList::new);
+        link.include.get_or_insert_with(HashSet::new).insert(light_name.to_string());
+    }
+
+    pub fn exclude_light(&mut self, object_name: &str, light_name: &str) {
+        let link = self.object_links.entry(object_name.to_string()).or_insert_with(LinkList::new);
+        link.exclude.insert(light_name.to_string());
+    }
+
+    pub fn illuminates(&self, light: &Arc<dyn Light>, object_name: Option<&str>) -> bool {
+        let light_name = self.light_name(light);
+
+        if let Some(link) = light_name.and_then(|n| self.light_links.get(n)) {
+            if !link.allows(object_name) {
+                return false;
+            }
+        }
+
+        if let Some(link) = object_name.and_then(|n| self.object_links.get(n)) {
+            if !link.allows(light_name) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // integrators zero the contribution of sampled lights that fail this
+    pub fn illuminates_surface(&self, light: &Arc<dyn Light>, isect: &SurfaceInteraction) -> bool {
+        self.illuminates(light, isect.object_name.as_deref())
+    }
+}
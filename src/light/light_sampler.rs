@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use crate::common::*;
+
+// Point being shaded, n and ns are zero for points in media
+#[derive(Debug, Clone, Copy)]
+pub struct LightSampleContext {
+    pub p: Point3,
+    pub n: Vector3,
+    pub ns: Vector3
+}
+
+impl LightSampleContext {
+    pub fn init(p: &Point3, n: &Vector3, ns: &Vector3) -> Self {
+        Self {
+            p: *p,
+            n: *n,
+            ns: *ns
+        }
+    }
+
+    pub fn from_interaction(it: &Interaction) -> Self {
+        Self::init(&it.p, &it.n, &it.n)
+    }
+
+    pub fn from_surface_interaction(si: &SurfaceInteraction) -> Self {
+        Self::init(&si.interaction.p, &si.interaction.n, &si.shading.n)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SampledLight {
+    pub light: Arc<dyn Light>,
+    pub p: Float
+}
+
+// lights are identified by the address of their allocation
+pub fn light_key(light: &Arc<dyn Light>) -> usize {
+    Arc::as_ptr(light) as *const () as usize
+}
+
+pub trait LightSampler: Debug {
+    fn sample(&self, ctx: &LightSampleContext, u: Float) -> Option<SampledLight>;
+    fn pmf(&self, ctx: &LightSampleContext, light: &Arc<dyn Light>) -> Float;
+
+    // for when there is no reference point, e.g. when starting light paths
+    fn sample_no_ctx(&self, u: Float) -> Option<SampledLight>;
+    fn pmf_no_ctx(&self, light: &Arc<dyn Light>) -> Float;
+}
+
+#[derive(Debug, Clone)]
+pub struct UniformLightSampler {
+    lights: Vec<Arc<dyn Light>>
+}
+
+impl UniformLightSampler {
+    pub fn init(lights: &[Arc<dyn Light>]) -> Self {
+        Self {
+            lights: lights.to_vec()
+        }
+    }
+}
+
+impl LightSampler for UniformLightSampler {
+    fn sample(&self, _ctx: &LightSampleContext, u: Float) -> Option<SampledLight> {
+        self.sample_no_ctx(u)
+    }
+
+    fn pmf(&self, _ctx: &LightSampleContext, light: &Arc<dyn Light>) -> Float {
+        self.pmf_no_ctx(light)
+    }
+
+    fn sample_no_ctx(&self, u: Float) -> Option<SampledLight> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let n = self.lights.len();
+        let idx = ((u * n as Float) as usize).min(n - 1);
+
+        Some(SampledLight { light: self.lights[idx].clone(), p: 1.0 / n as Float })
+    }
+
+    fn pmf_no_ctx(&self, _light: &Arc<dyn Light>) -> Float {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+
+        1.0 / self.lights.len() as Float
+    }
+}
+
+// Picks lights proportionally to their emitted power
+#[derive(Debug, Clone)]
+pub struct PowerLightSampler {
+    lights: Vec<Arc<dyn Light>>,
+    light_to_index: HashMap<usize, usize>,
+    alias_table: Option<AliasTable>
+}
+
+impl PowerLightSampler {
+    pub fn init(lights: &[Arc<dyn Light>]) -> Self {
+        let mut light_to_index = HashMap::new();
+        let mut light_power: Vec<Float> = Vec::with_capacity(lights.len());
+
+        for (i, light) in lights.iter().enumerate() {
+            light_to_index.insert(light_key(light), i);
+            light_power.push(rgb_y(&light.power()).max(0.0));
+        }
+
+        // fall back to uniform if nothing emits
+        if light_power.iter().sum::<Float>() == 0.0 {
+            light_power.iter_mut().for_each(|p| *p = 1.0);
+        }
+
+        let alias_table = if lights.is_empty() { None } else { Some(AliasTable::init(&light_power)) };
+
+        Self {
+            lights: lights.to_vec(),
+            light_to_index,
+            alias_table
+        }
+    }
+}
+
+impl LightSampler for PowerLightSampler {
+    fn sample(&self, _ctx: &LightSampleContext, u: Float) -> Option<SampledLight> {
+        self.sample_no_ctx(u)
+    }
+
+    fn pmf(&self, _ctx: &LightSampleContext, light: &Arc<dyn Light>) -> Float {
+        self.pmf_no_ctx(light)
+    }
+
+    fn sample_no_ctx(&self, u: Float) -> Option<SampledLight> {
+        let alias_table = self.alias_table.as_ref()?;
+        let (idx, p) = alias_table.sample(u);
+
+        Some(SampledLight { light: self.lights[idx].clone(), p })
+    }
+
+    fn pmf_no_ctx(&self, light: &Arc<dyn Light>) -> Float {
+        match (&self.alias_table, self.light_to_index.get(&light_key(light))) {
+            (Some(alias_table), Some(idx)) => alias_table.pmf(*idx),
+            _ => 0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point_light(p: Vector3, watts: Float) -> Arc<dyn Light> {
+        let white = LightColor::Rgb(Spectrum::new(1.0, 1.0, 1.0));
+        Arc::from(PointLight::init(Arc::from(translate(&p)), None, white, LightStrength::Watts(watts)).unwrap())
+    }
+
+    #[test]
+    fn power_pmf_follows_the_light_powers() {
+        let lights: Vec<Arc<dyn Light>> = [1.0, 2.0, 3.0, 4.0].iter()
+            .map(|watts| point_light(Vector3::new(0.0, 0.0, 0.0), *watts))
+            .collect();
+        let sampler = PowerLightSampler::init(&lights);
+
+        for (light, watts) in lights.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((sampler.pmf_no_ctx(light) - watts / 10.0).abs() < 1e-5);
+        }
+
+        // stratified samples pick each light in proportion to its pmf, and report that pmf
+        let n = 10000;
+        let mut counts = [0; 4];
+        for i in 0..n {
+            let sampled = sampler.sample_no_ctx((i as Float + 0.5) / n as Float).unwrap();
+            let idx = lights.iter().position(|l| light_key(l) == light_key(&sampled.light)).unwrap();
+            assert!((sampled.p - sampler.pmf_no_ctx(&sampled.light)).abs() < 1e-5);
+            counts[idx] += 1;
+        }
+        for (count, watts) in counts.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((*count as Float / n as Float - watts / 10.0).abs() < 1e-3, "{:?}", counts);
+        }
+
+        let unknown = point_light(Vector3::new(0.0, 0.0, 0.0), 1.0);
+        assert_eq!(sampler.pmf_no_ctx(&unknown), 0.0);
+    }
+
+    #[test]
+    fn power_falls_back_to_uniform() {
+        let white = LightColor::Rgb(Spectrum::new(1.0, 1.0, 1.0));
+        let dark: Vec<Arc<dyn Light>> = (0..4)
+            .map(|_| Arc::from(PointLight::init(Arc::from(Transform::identity()), None, white, LightStrength::Scale(0.0)).unwrap()) as Arc<dyn Light>)
+            .collect();
+        let sampler = PowerLightSampler::init(&dark);
+        for light in &dark {
+            assert!((sampler.pmf_no_ctx(light) - 0.25).abs() < 1e-6);
+        }
+
+        assert!(PowerLightSampler::init(&[]).sample_no_ctx(0.5).is_none());
+        assert!(UniformLightSampler::init(&[]).sample_no_ctx(0.5).is_none());
+    }
+}
@@ -0,0 +1,29 @@
+pub mod light;
+pub mod photometry;
+pub mod ies;
+pub mod light_bounds;
+pub mod light_sampler;
+pub mod light_bvh;
+pub mod light_linking;
+
+pub use light::{Light, LightFlags, LeSample, is_delta_light};
+pub use photometry::{K_M, spectrum_to_photometric, photometric_normalize, radiometric_normalize, LightColor, LightStrength};
+pub use ies::{IesProfile, IesPhotometricType};
+pub use light_bounds::{LightBounds, DirectionCone};
+pub use light_sampler::{LightSampler, LightSampleContext, SampledLight, UniformLightSampler, PowerLightSampler, light_key};
+pub use light_bvh::BVHLightSampler;
+pub use light_linking::LightLinking;
+
+pub mod point;
+pub mod goniometric;
+pub mod projection;
+pub mod spot;
+pub mod distant;
+pub mod diffuse_area;
+
+pub use point::PointLight;
+pub use goniometric::GoniometricLight;
+pub use projection::ProjectionLight;
+pub use spot::SpotLight;
+pub use distant::DistantLight;
+pub use diffuse_area::DiffuseAreaLight;
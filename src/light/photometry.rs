@@ -0,0 +1,114 @@
+use crate::common::*;
+
+// Spectra carried by lights are radiometric (W/sr, W/m^2/sr). Photometric quantities
+// (candela, lumens, nits) are converted by weighting with the luminance of the spectrum.
+
+// maximum luminous efficacy, lumens per watt
+pub const K_M: Float = 683.0;
+
+// luminous quantity of a radiometric spectrum, e.g. W/sr -> cd
+pub fn spectrum_to_photometric(s: &Spectrum) -> Float {
+    K_M * rgb_y(s)
+}
+
+// spectrum with the colour of s whose luminous quantity is 1, e.g. 1 cd, 1 lm
+pub fn photometric_normalize(s: &Spectrum) -> Spectrum {
+    let y = spectrum_to_photometric(s);
+    if y > 0.0 {
+        s / y
+    } else {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+}
+
+// spectrum with the colour of s and unit luminance, in W for powers or W/m^2/sr for radiance
+pub fn radiometric_normalize(s: &Spectrum) -> Spectrum {
+    let y = rgb_y(s);
+    if y > 0.0 {
+        s / y
+    } else {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightColor {
+    // linear RGB
+    Rgb(Spectrum),
+    // colour temperature in Kelvin
+    Blackbody(Float)
+}
+
+impl LightColor {
+    pub fn spectrum(&self) -> Spectrum {
+        match self {
+            LightColor::Rgb(s) => *s,
+            LightColor::Blackbody(t) => blackbody_rgb(*t)
+        }
+    }
+}
+
+// How bright a light is. The colour only sets the hue for everything except Scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightStrength {
+    // multiplies the colour as is
+    Scale(Float),
+    // total emitted power, independent of the light's size or cone angle
+    Watts(Float),
+    Lumens(Float),
+    // arriving at a surface facing a distant light
+    Irradiance(Float),
+    Lux(Float),
+    // leaving an emitting surface
+    Radiance(Float),
+    Nits(Float)
+}
+
+impl LightStrength {
+    pub fn is_power(&self) -> bool {
+        matches!(self, LightStrength::Watts(_) | LightStrength::Lumens(_))
+    }
+
+    // Emitted spectrum, for powers this is the unit luminance colour and the light rescales it
+    // with power_scale once it knows how much power that emits
+    pub fn emission(&self, color: &LightColor) -> Spectrum {
+        let s = color.spectrum();
+
+        match *self {
+            LightStrength::Scale(k) => s * k,
+            LightStrength::Watts(_) | LightStrength::Lumens(_) => radiometric_normalize(&s),
+            LightStrength::Irradiance(v) | LightStrength::Radiance(v) => radiometric_normalize(&s) * v,
+            LightStrength::Lux(v) | LightStrength::Nits(v) => photometric_normalize(&s) * v
+        }
+    }
+
+    // factor taking a light that emits power to the requested power, 1 for other strengths
+    pub fn power_scale(&self, power: &Spectrum) -> Float {
+        let emitted = match self {
+            LightStrength::Watts(_) => rgb_y(power),
+            LightStrength::Lumens(_) => spectrum_to_photometric(power),
+            _ => return 1.0
+        };
+
+        match *self {
+            LightStrength::Watts(target) | LightStrength::Lumens(target) if emitted > 0.0 => target / emitted,
+            _ => 0.0
+        }
+    }
+
+    // lights call this with the kinds of strength they can honour, scale is always allowed
+    pub fn check(&self, light: &str, power: bool, irradiance: bool, radiance: bool) -> Result<(), String> {
+        let ok = match self {
+            LightStrength::Scale(_) => true,
+            LightStrength::Watts(_) | LightStrength::Lumens(_) => power,
+            LightStrength::Irradiance(_) | LightStrength::Lux(_) => irradiance,
+            LightStrength::Radiance(_) | LightStrength::Nits(_) => radiance
+        };
+
+        if !ok {
+            return Err(format!("{} cannot be specified with {:?}", light, self));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::common::*;
+
+#[derive(Debug, Clone)]
+pub struct PointLight {
+    light_to_world: Arc<Transform>,
+    world_to_light: Arc<Transform>,
+    medium_interface: Option<MediumInterface>,
+
+    p_light: Point3,
+    i: Spectrum
+}
+
+impl PointLight {
+    pub fn init(light_to_world: Arc<Transform>, medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength) -> Result<Self, String> {
+        strength.check("PointLight", true, false, false)?;
+
+        let world_to_light = Arc::from(light_to_world.inverse());
+        let p_light = light_to_world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+
+        let mut i = strength.emission(&color);
+        i *= strength.power_scale(&(4.0 * PI * i));
+
+        Ok(Self {
+            light_to_world,
+            world_to_light,
+            medium_interface,
+
+            p_light,
+            i
+        })
+    }
+}
+
+impl Light for PointLight {
+    fn flags(&self) -> u32 { LightFlags::DeltaPosition as u32 }
+    fn n_samples(&self) -> usize { 1 }
+    fn light_to_world(&self) -> Arc<Transform> { self.light_to_world.clone() }
+    fn world_to_light(&self) -> Arc<Transform> { self.world_to_light.clone() }
+    fn medium_interface(&self) -> Option<MediumInterface> { self.medium_interface.clone() }
+
+    fn sample_li(&self, reference: &Interaction, _u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum {
+        *wi = (self.p_light - reference.p).normalize();
+        *pdf = 1.0;
+        *vis = VisibilityTester::init(reference.clone(), Interaction::init_minimal(&self.p_light, reference.time, self.medium_interface.clone()));
+
+        self.i / (self.p_light - reference.p).norm_squared()
+    }
+
+    fn pdf_li(&self, _reference: &Interaction, _wi: &Vector3) -> Float {
+        0.0
+    }
+
+    fn power(&self) -> Spectrum {
+        4.0 * PI * self.i
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.i.max();
+
+        Some(LightBounds::init(&Bounds3f::init_one(&self.p_light), &Vector3::new(0.0, 0.0, 1.0), phi, PI.cos(), (PI / 2.0).cos(), false))
+    }
+
+    fn sample_le(&self, u1: &Point2, _u2: &Point2, time: Float) -> LeSample {
+        let medium = self.medium_interface.as_ref().and_then(|mi| mi.inside.clone());
+        let ray = Ray::init(&self.p_light, &uniform_sample_sphere(u1), Some(INFINITY), Some(time), medium);
+
+        LeSample { n_light: ray.d, ray, pdf_pos: 1.0, pdf_dir: uniform_sphere_pdf(), le: self.i }
+    }
+
+    fn pdf_le(&self, _ray: &Ray, _n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        *pdf_pos = 0.0;
+        *pdf_dir = uniform_sphere_pdf();
+    }
+}
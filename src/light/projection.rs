@@ -0,0 +1,196 @@
+use crate::common::*;
+use read_image::read_image;
+
+// Point light that projects an image into the scene like a slide projector. The light looks
+// down +z of light space, the same as a perspective camera.
+#[derive(Debug, Clone)]
+pub struct ProjectionLight {
+    light_to_world: Arc<Transform>,
+    world_to_light: Arc<Transform>,
+    medium_interface: Option<MediumInterface>,
+
+    p_light: Point3,
+    // intensity for a pixel value of one
+    i: Spectrum,
+
+    // top row first
+    image: Vec<Spectrum>,
+    width: usize,
+    height: usize,
+
+    light_projection: Transform,
+    screen_bounds: Bounds2f,
+    hither: Float,
+    cos_total_width: Float,
+    // integral of the image over the solid angle it covers
+    image_solid_angle_integral: Spectrum
+}
+
+impl ProjectionLight {
+    // A Scale strength makes a pixel value of one that many candela, a power rescales the image
+    // so the light emits exactly that much
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(light_to_world: Arc<Transform>, medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength, image: Vec<Spectrum>, width: usize, height: usize, fov: Float) -> Result<Self, String> {
+        strength.check("ProjectionLight", true, false, false)?;
+        if width == 0 || height == 0 {
+            return Err("ProjectionLight needs a non empty image".to_string());
+        }
+        if image.len() != width * height {
+            return Err(format!("ProjectionLight image has {} pixels, not {} x {}", image.len(), width, height));
+        }
+
+        let world_to_light = Arc::from(light_to_world.inverse());
+        let p_light = light_to_world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+
+        let aspect = width as Float / height as Float;
+        let screen_bounds = if aspect > 1.0 {
+            Bounds2f::init(&Point2::new(-aspect, -1.0), &Point2::new(aspect, 1.0))
+        } else {
+            Bounds2f::init(&Point2::new(-1.0, -1.0 / aspect), &Point2::new(1.0, 1.0 / aspect))
+        };
+
+        let hither = 1e-3;
+        let light_projection = perspective::PerspectiveCamera::create_perspective(fov, hither, 1e30);
+
+        let tan_half_fov = (fov.to_radians() / 2.0).tan();
+        let tan_diag = tan_half_fov * screen_bounds.p_max.coords.norm();
+        let cos_total_width = tan_diag.atan().cos();
+
+        let mut ret = Self {
+            light_to_world,
+            world_to_light,
+            medium_interface,
+
+            p_light,
+            i: match strength {
+                LightStrength::Scale(k) => photometric_normalize(&color.spectrum()) * k,
+                _ => strength.emission(&color)
+            },
+
+            image,
+            width,
+            height,
+
+            light_projection,
+            screen_bounds,
+            hither,
+            cos_total_width,
+            image_solid_angle_integral: Spectrum::new(0.0, 0.0, 0.0)
+        };
+
+        ret.image_solid_angle_integral = ret.compute_solid_angle_integral(tan_half_fov);
+
+        ret.i *= strength.power_scale(&ret.power());
+
+        Ok(ret)
+    }
+
+    pub fn init_from_file(light_to_world: Arc<Transform>, medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength, file_path_str: &str, fov: Float) -> Result<Self, String> {
+        let (image, width, height) = read_image(file_path_str)?;
+
+        Self::init(light_to_world, medium_interface, color, strength, image, width, height, fov)
+    }
+
+    // Each pixel covers dA of the z = 1 plane, which subtends cos^3(theta) dA of solid angle
+    fn compute_solid_angle_integral(&self, tan_half_fov: Float) -> Spectrum {
+        let d_area = self.screen_bounds.area() * tan_half_fov * tan_half_fov / (self.width * self.height) as Float;
+
+        let mut sum = Spectrum::new(0.0, 0.0, 0.0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let st = Point2::new((x as Float + 0.5) / self.width as Float, 1.0 - (y as Float + 0.5) / self.height as Float);
+                let p_screen = self.screen_bounds.lerp(st);
+                let w = Vector3::new(p_screen.x * tan_half_fov, p_screen.y * tan_half_fov, 1.0);
+                let cos_theta = 1.0 / w.norm();
+
+                sum += self.image[y * self.width + x] * cos_theta.powi(3) * d_area;
+            }
+        }
+
+        sum
+    }
+
+    // bilinear lookup, s to the right and t upwards
+    fn lookup(&self, st: &Point2) -> Spectrum {
+        let x = st.x * self.width as Float - 0.5;
+        let y = (1.0 - st.y) * self.height as Float - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let dx = x - x0;
+        let dy = y - y0;
+
+        let texel = |xi: Float, yi: Float| -> Spectrum {
+            let xi = (xi.max(0.0) as usize).min(self.width - 1);
+            let yi = (yi.max(0.0) as usize).min(self.height - 1);
+            self.image[yi * self.width + xi]
+        };
+
+        (1.0 - dx) * (1.0 - dy) * texel(x0, y0) + dx * (1.0 - dy) * texel(x0 + 1.0, y0) +
+        (1.0 - dx) * dy * texel(x0, y0 + 1.0) + dx * dy * texel(x0 + 1.0, y0 + 1.0)
+    }
+
+    // radiometric intensity towards the world space direction w
+    pub fn projection(&self, w: &Vector3) -> Spectrum {
+        let wl = self.world_to_light.transform_vector(w).normalize();
+        if wl.z < self.hither {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let p = self.light_projection.transform_point(&Point3::from(wl));
+        let p_screen = Point2::new(p.x, p.y);
+        if !self.screen_bounds.inside(&p_screen) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let st = Point2::from(self.screen_bounds.offset(&p_screen));
+
+        self.lookup(&st).component_mul(&self.i)
+    }
+}
+
+impl Light for ProjectionLight {
+    fn flags(&self) -> u32 { LightFlags::DeltaPosition as u32 }
+    fn n_samples(&self) -> usize { 1 }
+    fn light_to_world(&self) -> Arc<Transform> { self.light_to_world.clone() }
+    fn world_to_light(&self) -> Arc<Transform> { self.world_to_light.clone() }
+    fn medium_interface(&self) -> Option<MediumInterface> { self.medium_interface.clone() }
+
+    fn sample_li(&self, reference: &Interaction, _u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum {
+        *wi = (self.p_light - reference.p).normalize();
+        *pdf = 1.0;
+        *vis = VisibilityTester::init(reference.clone(), Interaction::init_minimal(&self.p_light, reference.time, self.medium_interface.clone()));
+
+        self.projection(&(-*wi)) / (self.p_light - reference.p).norm_squared()
+    }
+
+    fn pdf_li(&self, _reference: &Interaction, _wi: &Vector3) -> Float {
+        0.0
+    }
+
+    fn power(&self) -> Spectrum {
+        self.image_solid_angle_integral.component_mul(&self.i)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let max_pixel = self.image.iter().fold(0.0 as Float, |a, p| a.max(p.max()));
+        let phi = 4.0 * PI * max_pixel * self.i.max();
+        let w = self.light_to_world.transform_vector(&Vector3::new(0.0, 0.0, 1.0));
+
+        Some(LightBounds::init(&Bounds3f::init_one(&self.p_light), &w, phi, self.cos_total_width, (PI / 2.0).cos(), false))
+    }
+
+    fn sample_le(&self, u1: &Point2, _u2: &Point2, time: Float) -> LeSample {
+        let medium = self.medium_interface.as_ref().and_then(|mi| mi.inside.clone());
+        let w = self.light_to_world.transform_vector(&uniform_sample_cone(u1, self.cos_total_width));
+        let ray = Ray::init(&self.p_light, &w, Some(INFINITY), Some(time), medium);
+        let le = self.projection(&ray.d);
+
+        LeSample { n_light: ray.d, ray, pdf_pos: 1.0, pdf_dir: uniform_cone_pdf(self.cos_total_width), le }
+    }
+
+    fn pdf_le(&self, ray: &Ray, _n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        *pdf_pos = 0.0;
+        let cos_theta = self.world_to_light.transform_vector(&ray.d).normalize().z;
+        *pdf_dir = if cos_theta >= self.cos_total_width { uniform_cone_pdf(self.cos_total_width) } else { 0.0 };
+    }
+}
@@ -0,0 +1,106 @@
+use crate::common::*;
+
+// Point light emitting in a cone around +z of light space
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+    light_to_world: Arc<Transform>,
+    world_to_light: Arc<Transform>,
+    medium_interface: Option<MediumInterface>,
+
+    p_light: Point3,
+    i: Spectrum,
+    cos_total_width: Float,
+    cos_falloff_start: Float
+}
+
+impl SpotLight {
+    // angles in degrees. A power strength is kept as the cone angles change.
+    pub fn init(light_to_world: Arc<Transform>, medium_interface: Option<MediumInterface>, color: LightColor, strength: LightStrength, total_width: Float, falloff_start: Float) -> Result<Self, String> {
+        strength.check("SpotLight", true, false, false)?;
+
+        let world_to_light = Arc::from(light_to_world.inverse());
+        let p_light = light_to_world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+
+        let cos_total_width = total_width.to_radians().cos();
+        let cos_falloff_start = falloff_start.min(total_width).to_radians().cos();
+
+        let mut ret = Self {
+            light_to_world,
+            world_to_light,
+            medium_interface,
+
+            p_light,
+            i: strength.emission(&color),
+            cos_total_width,
+            cos_falloff_start
+        };
+
+        ret.i *= strength.power_scale(&ret.power());
+
+        Ok(ret)
+    }
+
+    // smooth falloff between the two cone angles, w in world space
+    pub fn falloff(&self, w: &Vector3) -> Float {
+        let wl = self.world_to_light.transform_vector(w).normalize();
+        let cos_theta = wl.z;
+
+        if cos_theta < self.cos_total_width {
+            return 0.0;
+        }
+        if cos_theta >= self.cos_falloff_start {
+            return 1.0;
+        }
+
+        let delta = (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        (delta * delta) * (delta * delta)
+    }
+}
+
+impl Light for SpotLight {
+    fn flags(&self) -> u32 { LightFlags::DeltaPosition as u32 }
+    fn n_samples(&self) -> usize { 1 }
+    fn light_to_world(&self) -> Arc<Transform> { self.light_to_world.clone() }
+    fn world_to_light(&self) -> Arc<Transform> { self.world_to_light.clone() }
+    fn medium_interface(&self) -> Option<MediumInterface> { self.medium_interface.clone() }
+
+    fn sample_li(&self, reference: &Interaction, _u: &Point2, wi: &mut Vector3, pdf: &mut Float, vis: &mut VisibilityTester) -> Spectrum {
+        *wi = (self.p_light - reference.p).normalize();
+        *pdf = 1.0;
+        *vis = VisibilityTester::init(reference.clone(), Interaction::init_minimal(&self.p_light, reference.time, self.medium_interface.clone()));
+
+        self.i * self.falloff(&(-*wi)) / (self.p_light - reference.p).norm_squared()
+    }
+
+    fn pdf_li(&self, _reference: &Interaction, _wi: &Vector3) -> Float {
+        0.0
+    }
+
+    // the falloff integrates to (cos_falloff_start - cos_total_width) / 5 over cos theta
+    fn power(&self) -> Spectrum {
+        self.i * 2.0 * PI * ((1.0 - self.cos_falloff_start) + (self.cos_falloff_start - self.cos_total_width) / 5.0)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.i.max();
+        let w = self.light_to_world.transform_vector(&Vector3::new(0.0, 0.0, 1.0));
+        let cos_theta_e = (self.cos_total_width.acos() - self.cos_falloff_start.acos()).cos();
+
+        Some(LightBounds::init(&Bounds3f::init_one(&self.p_light), &w, phi, self.cos_falloff_start, cos_theta_e, false))
+    }
+
+    fn sample_le(&self, u1: &Point2, _u2: &Point2, time: Float) -> LeSample {
+        let medium = self.medium_interface.as_ref().and_then(|mi| mi.inside.clone());
+        let w = self.light_to_world.transform_vector(&uniform_sample_cone(u1, self.cos_total_width));
+        let ray = Ray::init(&self.p_light, &w, Some(INFINITY), Some(time), medium);
+        let le = self.i * self.falloff(&ray.d);
+
+        LeSample { n_light: ray.d, ray, pdf_pos: 1.0, pdf_dir: uniform_cone_pdf(self.cos_total_width), le }
+    }
+
+    fn pdf_le(&self, ray: &Ray, _n_light: &Vector3, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        *pdf_pos = 0.0;
+        let cos_theta = self.world_to_light.transform_vector(&ray.d).normalize().z;
+        *pdf_dir = if cos_theta >= self.cos_total_width { uniform_cone_pdf(self.cos_total_width) } else { 0.0 };
+    }
+}
@@ -1,3 +1,6 @@
+// each module keeps its core type in a file of the same name, camera::camera, light::light
+#![allow(clippy::module_inception)]
+
 use std::time::Instant;
 use rand::Rng;
 
@@ -10,6 +13,8 @@ pub mod math;
 pub mod spectrum;
 pub mod camera;
 pub mod sampler;
+pub mod light;
+pub mod texture;
 
 pub mod common;
 
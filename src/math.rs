@@ -56,4 +56,268 @@ pub fn quadratic(a: Float, b: Float, c: Float, t0: &mut Float, t1: &mut Float) -
     true
 }
 
-pub const PI: Float = f32::consts::PI;
\ No newline at end of file
+pub const PI: Float = f32::consts::PI;
+
+pub fn erf(x: Float) -> Float {
+    // Abramowitz and Stegun 7.1.26
+    let a1 = 0.2548296;
+    let a2 = -0.28449674;
+    let a3 = 1.4214137;
+    let a4 = -1.453152;
+    let a5 = 1.0614054;
+    let p = 0.3275911;
+
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+
+    let t = 1.0 / (1.0 + p * x);
+    let y = 1.0 - (((((a5 * t + a4) * t) + a3) * t + a2) * t + a1) * t * (-x * x).exp();
+
+    sign * y
+}
+
+pub fn erf_inv(x: Float) -> Float {
+    // Giles, approximating the inverse error function
+    let x = x.clamp(-0.99999, 0.99999);
+    let mut w = -((1.0 - x) * (1.0 + x)).ln();
+    let mut p;
+    if w < 5.0 {
+        w -= 2.5;
+        p = 2.8102264e-8;
+        p = 3.4327394e-7 + p * w;
+        p = -3.5233877e-6 + p * w;
+        p = -4.3915065e-6 + p * w;
+        p = 0.00021858087 + p * w;
+        p = -0.001253725 + p * w;
+        p = -0.0041776816 + p * w;
+        p = 0.24664073 + p * w;
+        p = 1.5014094 + p * w;
+    } else {
+        w = w.sqrt() - 3.0;
+        p = -0.00020021426;
+        p = 0.00010095056 + p * w;
+        p = 0.0013493432 + p * w;
+        p = -0.0036734284 + p * w;
+        p = 0.0057395077 + p * w;
+        p = -0.0076224613 + p * w;
+        p = 0.0094388705 + p * w;
+        p = 1.001674 + p * w;
+        p = 2.8329768 + p * w;
+    }
+
+    p * x
+}
+
+// 64 bit finalizer of MurmurHash3, spreads every input bit over the output
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+
+    v
+}
+
+// deterministic hash of a list of floats, for seeding random numbers from directions
+pub fn hash_floats(values: &[Float]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |h, v| mix_bits(h ^ v.to_bits() as u64).rotate_left(17))
+}
+
+// largest index i in [0, size - 2] with pred(i) true, for pred true up to some point and false after
+pub fn find_interval<F: Fn(usize) -> bool>(size: usize, pred: F) -> usize {
+    let mut first = 0;
+    let mut len = size;
+    while len > 0 {
+        let half = len >> 1;
+        let middle = first + half;
+        if pred(middle) {
+            first = middle + 1;
+            len -= half + 1;
+        } else {
+            len = half;
+        }
+    }
+
+    first.saturating_sub(1).min(size.saturating_sub(2))
+}
+
+// weights of the four nodes starting at offset for Catmull-Rom interpolation at x, false when x
+// is outside the nodes
+pub fn catmull_rom_weights(nodes: &[Float], x: Float, offset: &mut isize, weights: &mut [Float; 4]) -> bool {
+    let size = nodes.len();
+    if !(x >= nodes[0] && x <= nodes[size - 1]) {
+        return false;
+    }
+
+    let idx = find_interval(size, |i| nodes[i] <= x);
+    *offset = idx as isize - 1;
+    let x0 = nodes[idx];
+    let x1 = nodes[idx + 1];
+
+    let t = (x - x0) / (x1 - x0);
+    let t2 = t * t;
+    let t3 = t2 * t;
+    weights[1] = 2.0 * t3 - 3.0 * t2 + 1.0;
+    weights[2] = -2.0 * t3 + 3.0 * t2;
+
+    // the end points use one-sided differences
+    if idx > 0 {
+        let w0 = (t3 - 2.0 * t2 + t) * (x1 - x0) / (x1 - nodes[idx - 1]);
+        weights[0] = -w0;
+        weights[2] += w0;
+    } else {
+        let w0 = t3 - 2.0 * t2 + t;
+        weights[0] = 0.0;
+        weights[1] -= w0;
+        weights[2] += w0;
+    }
+    if idx + 2 < size {
+        let w3 = (t3 - t2) * (x1 - x0) / (nodes[idx + 2] - x0);
+        weights[1] -= w3;
+        weights[3] = w3;
+    } else {
+        let w3 = t3 - t2;
+        weights[1] -= w3;
+        weights[2] += w3;
+        weights[3] = 0.0;
+    }
+
+    true
+}
+
+// samples the second dimension of a spline over a 2D table with the first fixed at alpha, values
+// and cdf are laid out with nodes2 varying fastest
+pub fn sample_catmull_rom_2d(nodes1: &[Float], nodes2: &[Float], values: &[Float], cdf: &[Float], alpha: Float, u: Float) -> Float {
+    let size2 = nodes2.len();
+    let mut offset = 0;
+    let mut weights = [0.0; 4];
+    if !catmull_rom_weights(nodes1, alpha, &mut offset, &mut weights) {
+        return 0.0;
+    }
+
+    let interpolate = |array: &[Float], idx: usize| -> Float {
+        (0..4).filter(|&i| weights[i] != 0.0)
+            .map(|i| array[(offset + i as isize) as usize * size2 + idx] * weights[i])
+            .sum()
+    };
+
+    let maximum = interpolate(cdf, size2 - 1);
+    let mut u = u * maximum;
+    let idx = find_interval(size2, |i| interpolate(cdf, i) <= u);
+
+    let f0 = interpolate(values, idx);
+    let f1 = interpolate(values, idx + 1);
+    let x0 = nodes2[idx];
+    let x1 = nodes2[idx + 1];
+    let width = x1 - x0;
+    u = (u - interpolate(cdf, idx)) / width;
+
+    let d0 = if idx > 0 {
+        width * (f1 - interpolate(values, idx - 1)) / (x1 - nodes2[idx - 1])
+    } else {
+        f1 - f0
+    };
+    let d1 = if idx + 2 < size2 {
+        width * (interpolate(values, idx + 2) - f0) / (nodes2[idx + 2] - x0)
+    } else {
+        f1 - f0
+    };
+
+    // invert the integral of the segment with Newton-bisection, starting from the linear solution
+    let mut t = if f0 != f1 {
+        (f0 - (f0 * f0 + 2.0 * u * (f1 - f0)).max(0.0).sqrt()) / (f0 - f1)
+    } else {
+        u / f0
+    };
+    let mut a = 0.0;
+    let mut b = 1.0;
+    loop {
+        if !(t >= a && t <= b) {
+            t = 0.5 * (a + b);
+        }
+
+        let big_f_hat = t * (f0 + t * (0.5 * d0 + t * ((1.0 / 3.0) * (-2.0 * d0 - d1) + f1 - f0
+            + t * (0.25 * (d0 + d1) + 0.5 * (f0 - f1)))));
+        let f_hat = f0 + t * (d0 + t * (-2.0 * d0 - d1 + 3.0 * (f1 - f0) + t * (d0 + d1 + 2.0 * (f0 - f1))));
+
+        if (big_f_hat - u).abs() < 1e-6 || b - a < 1e-6 {
+            break;
+        }
+        if big_f_hat - u < 0.0 {
+            a = t;
+        } else {
+            b = t;
+        }
+        t -= (big_f_hat - u) / f_hat;
+    }
+
+    x0 + width * t
+}
+
+// integral of the spline through values, writing the running integral at every node to cdf
+pub fn integrate_catmull_rom(x: &[Float], values: &[Float], cdf: &mut [Float]) -> Float {
+    let n = x.len();
+    let mut sum = 0.0;
+    cdf[0] = 0.0;
+    for i in 0..n - 1 {
+        let (x0, x1) = (x[i], x[i + 1]);
+        let (f0, f1) = (values[i], values[i + 1]);
+        let width = x1 - x0;
+
+        let d0 = if i > 0 { width * (f1 - values[i - 1]) / (x1 - x[i - 1]) } else { f1 - f0 };
+        let d1 = if i + 2 < n { width * (values[i + 2] - f0) / (x[i + 2] - x0) } else { f1 - f0 };
+
+        sum += ((d0 - d1) * (1.0 / 12.0) + (f0 + f1) * 0.5) * width;
+        cdf[i + 1] = sum;
+    }
+
+    sum
+}
+
+// the x where the spline through monotonically increasing values reaches u
+pub fn invert_catmull_rom(x: &[Float], values: &[Float], u: Float) -> Float {
+    let n = x.len();
+    if u <= values[0] {
+        return x[0];
+    }
+    if u >= values[n - 1] {
+        return x[n - 1];
+    }
+
+    let i = find_interval(n, |i| values[i] <= u);
+    let (x0, x1) = (x[i], x[i + 1]);
+    let (f0, f1) = (values[i], values[i + 1]);
+    let width = x1 - x0;
+
+    let d0 = if i > 0 { width * (f1 - values[i - 1]) / (x1 - x[i - 1]) } else { f1 - f0 };
+    let d1 = if i + 2 < n { width * (values[i + 2] - f0) / (x[i + 2] - x0) } else { f1 - f0 };
+
+    let mut a = 0.0;
+    let mut b = 1.0;
+    let mut t: Float = 0.5;
+    loop {
+        if !(t > a && t < b) {
+            t = 0.5 * (a + b);
+        }
+
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let big_f_hat = (2.0 * t3 - 3.0 * t2 + 1.0) * f0 + (-2.0 * t3 + 3.0 * t2) * f1
+            + (t3 - 2.0 * t2 + t) * d0 + (t3 - t2) * d1;
+        let f_hat = (6.0 * t2 - 6.0 * t) * f0 + (-6.0 * t2 + 6.0 * t) * f1
+            + (3.0 * t2 - 4.0 * t + 1.0) * d0 + (3.0 * t2 - 2.0 * t) * d1;
+
+        if (big_f_hat - u).abs() < 1e-6 || b - a < 1e-6 {
+            break;
+        }
+        if big_f_hat - u < 0.0 {
+            a = t;
+        } else {
+            b = t;
+        }
+        t -= (big_f_hat - u) / f_hat;
+    }
+
+    x0 + t * width
+}
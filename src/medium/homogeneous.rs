@@ -0,0 +1,62 @@
+use crate::common::*;
+
+// Medium with the same coefficients everywhere, scattering with a Henyey-Greenstein phase function
+#[derive(Debug, Clone)]
+pub struct HomogeneousMedium {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    sigma_t: Spectrum,
+    g: Float
+}
+
+impl HomogeneousMedium {
+    pub fn init(sigma_a: &Spectrum, sigma_s: &Spectrum, g: Float) -> Self {
+        Self {
+            sigma_a: *sigma_a,
+            sigma_s: *sigma_s,
+            sigma_t: sigma_a + sigma_s,
+            g
+        }
+    }
+
+    pub fn sigma_a(&self) -> Spectrum {
+        self.sigma_a
+    }
+
+    pub fn sigma_s(&self) -> Spectrum {
+        self.sigma_s
+    }
+}
+
+impl Medium for HomogeneousMedium {
+    fn tr(&self, ray: &Ray, _rng: &mut RNG) -> Spectrum {
+        let distance = ray.t_max.min(Float::MAX) * ray.d.norm();
+
+        (-self.sigma_t * distance).map(|c| c.exp())
+    }
+
+    fn sample(&self, ray: &Ray, rng: &mut RNG, mi: &mut Option<MediumInteraction>) -> Spectrum {
+        // sample the distance by the extinction of one channel, the weight accounts for all of them
+        let channel = ((rng.uniform_float() * 3.0) as usize).min(2);
+        let length = ray.d.norm();
+        let dist = -(1.0 - rng.uniform_float()).ln() / self.sigma_t[channel];
+        let t = (dist / length).min(ray.t_max);
+        let sampled_medium = t < ray.t_max;
+
+        *mi = if sampled_medium {
+            let medium: Arc<dyn Medium> = Arc::from(self.clone());
+            Some(MediumInteraction::init(&ray.at(t), &-ray.d, ray.time, medium, Arc::from(HenyeyGreenstein::init(self.g))))
+        } else {
+            None
+        };
+
+        let tr = (-self.sigma_t * t.min(Float::MAX) * length).map(|c| c.exp());
+        let density = if sampled_medium { self.sigma_t.component_mul(&tr) } else { tr };
+        let mut pdf = (density.x + density.y + density.z) / 3.0;
+        if pdf == 0.0 {
+            pdf = 1.0;
+        }
+
+        if sampled_medium { tr.component_mul(&self.sigma_s) / pdf } else { tr / pdf }
+    }
+}
@@ -2,8 +2,13 @@
 
 use crate::common::*;
 
+// A participating medium. Randomness comes from an RNG since samplers are not object safe
 pub trait Medium: Debug {
-
+    // transmittance along the ray up to t_max
+    fn tr(&self, ray: &Ray, rng: &mut RNG) -> Spectrum;
+    // samples a scattering point before t_max, written to mi, and returns the throughput weight of
+    // the segment. mi stays None when the ray passed through
+    fn sample(&self, ray: &Ray, rng: &mut RNG, mi: &mut Option<MediumInteraction>) -> Spectrum;
 }
 
 // impl Medium {
@@ -1,3 +1,7 @@
 pub mod medium_interface;
+pub mod phase_function;
+pub mod homogeneous;
 
-pub use medium_interface::{MediumInterface, Medium};
\ No newline at end of file
+pub use medium_interface::{MediumInterface, Medium};
+pub use phase_function::{PhaseFunction, HenyeyGreenstein, phase_hg};
+pub use homogeneous::HomogeneousMedium;
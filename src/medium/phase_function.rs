@@ -0,0 +1,53 @@
+use crate::common::*;
+
+// wo and wi both point away from the scattering point
+pub trait PhaseFunction: Debug {
+    fn p(&self, wo: &Vector3, wi: &Vector3) -> Float;
+    // importance samples wi and returns p, which is also the pdf
+    fn sample_p(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2) -> Float;
+}
+
+// Henyey-Greenstein lobe, g > 0 scatters forward and g < 0 backward
+pub fn phase_hg(cos_theta: Float, g: Float) -> Float {
+    let denom = 1.0 + g * g + 2.0 * g * cos_theta;
+
+    (1.0 / (4.0 * PI)) * (1.0 - g * g) / (denom * denom.max(0.0).sqrt())
+}
+
+#[derive(Debug, Clone)]
+pub struct HenyeyGreenstein {
+    g: Float
+}
+
+impl HenyeyGreenstein {
+    pub fn init(g: Float) -> Self {
+        Self {
+            g: g.clamp(-0.99, 0.99)
+        }
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn p(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        phase_hg(wo.dot(wi), self.g)
+    }
+
+    fn sample_p(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2) -> Float {
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u.x
+        } else {
+            let sqr_term = (1.0 - g * g) / (1.0 + g - 2.0 * g * u.x);
+            -(1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        }.clamp(-1.0, 1.0);
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.y;
+        let mut v1 = Vector3::new(0.0, 0.0, 0.0);
+        let mut v2 = Vector3::new(0.0, 0.0, 0.0);
+        coordinate_system(wo, &mut v1, &mut v2);
+        *wi = spherical_direction_in_frame(sin_theta, cos_theta, phi, &v1, &v2, wo);
+
+        phase_hg(cos_theta, g)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy)]
+struct AliasBin {
+    q: Float,
+    p: Float,
+    alias: Option<usize>
+}
+
+// Samples an index proportionally to its weight in constant time (Walker/Vose alias method)
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    bins: Vec<AliasBin>
+}
+
+impl AliasTable {
+    pub fn init(weights: &[Float]) -> Self {
+        let n = weights.len();
+        let sum: f64 = weights.iter().map(|w| *w as f64).sum();
+
+        let mut bins: Vec<AliasBin> = weights.iter().map(|w| {
+            let p = if sum > 0.0 { (*w as f64 / sum) as Float } else { 1.0 / n as Float };
+            AliasBin { q: 0.0, p, alias: None }
+        }).collect();
+
+        // split into bins with less and more than the average probability
+        let mut under: Vec<(usize, f64)> = Vec::new();
+        let mut over: Vec<(usize, f64)> = Vec::new();
+        for (i, bin) in bins.iter().enumerate() {
+            let p_hat = bin.p as f64 * n as f64;
+            if p_hat < 1.0 {
+                under.push((i, p_hat));
+            } else {
+                over.push((i, p_hat));
+            }
+        }
+
+        // fill each under full bin with the excess of an over full one
+        while let (Some(un), Some(ov)) = (under.pop(), over.pop()) {
+            bins[un.0].q = un.1 as Float;
+            bins[un.0].alias = Some(ov.0);
+
+            let p_excess = un.1 + ov.1 - 1.0;
+            if p_excess < 1.0 {
+                under.push((ov.0, p_excess));
+            } else {
+                over.push((ov.0, p_excess));
+            }
+        }
+
+        // whatever is left is 1 up to round off
+        for (i, _) in over.into_iter().chain(under) {
+            bins[i].q = 1.0;
+            bins[i].alias = None;
+        }
+
+        Self {
+            bins
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.bins.len()
+    }
+
+    pub fn pmf(&self, index: usize) -> Float {
+        self.bins[index].p
+    }
+
+    // returns the sampled index and its probability
+    pub fn sample(&self, u: Float) -> (usize, Float) {
+        let (index, pmf, _) = self.sample_remapped(u);
+
+        (index, pmf)
+    }
+
+    // also returns u remapped to a fresh uniform value, for sampling within the chosen index
+    pub fn sample_remapped(&self, u: Float) -> (usize, Float, Float) {
+        let n = self.bins.len();
+        let offset = ((u * n as Float) as usize).min(n - 1);
+        let up = (u * n as Float - offset as Float).min(ONE_MINUS_EPSILON);
+
+        let bin = &self.bins[offset];
+        match bin.alias {
+            Some(alias) if up >= bin.q => {
+                let u_remapped = ((up - bin.q) / (1.0 - bin.q)).min(ONE_MINUS_EPSILON);
+                (alias, self.bins[alias].p, u_remapped)
+            },
+            _ => (offset, bin.p, (up / bin.q).min(ONE_MINUS_EPSILON))
+        }
+    }
+}
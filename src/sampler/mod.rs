@@ -2,10 +2,12 @@ pub mod sampler;
 pub mod rng;
 pub mod pixel_sampler;
 pub mod filter;
+pub mod alias_table;
 
 pub use sampler::Sampler;
 pub use rng::RNG;
 pub use filter::Filter;
+pub use alias_table::AliasTable;
 
 pub mod filter_box;
 pub mod filter_triangle;
@@ -1,9 +1,200 @@
 use crate::common::*;
 
-pub trait BSDF: Debug {
-    
+pub const MAX_BXDFS: usize = 8;
+
+// A collection of BxDFs at a surface point, evaluated in the local shading frame
+#[derive(Debug, Clone)]
+pub struct BSDF {
+    // relative index of refraction over the boundary, 1 for opaque surfaces
+    pub eta: Float,
+    // shading normal and geometric normal
+    ns: Vector3,
+    ng: Vector3,
+    ss: Vector3,
+    ts: Vector3,
+    bxdfs: Vec<Arc<dyn BxDF>>,
+    // relative probability of sampling each BxDF
+    weights: Vec<Float>
 }
 
-pub trait BSSRDF: Debug {
+impl BSDF {
+    pub fn init(si: &SurfaceInteraction, eta: Float) -> Self {
+        let ns = si.shading.n;
+        let ss = si.shading.dpdu.normalize();
+        let ts = ns.cross(&ss);
+
+        Self {
+            eta,
+            ns,
+            ng: si.interaction.n,
+            ss,
+            ts,
+            bxdfs: Vec::new(),
+            weights: Vec::new()
+        }
+    }
+
+    pub fn add(&mut self, bxdf: Arc<dyn BxDF>) {
+        self.add_weighted(bxdf, 1.0);
+    }
+
+    // the BxDF is sampled in proportion to weight, which should roughly follow its albedo
+    pub fn add_weighted(&mut self, bxdf: Arc<dyn BxDF>, weight: Float) {
+        assert!(self.bxdfs.len() < MAX_BXDFS, "Too many BxDFs in a BSDF");
+        assert!(weight > 0.0, "BxDF sampling weight must be positive");
+        self.bxdfs.push(bxdf);
+        self.weights.push(weight);
+    }
+
+    pub fn num_components(&self, flags: u32) -> usize {
+        self.bxdfs.iter().filter(|b| b.matches_flags(flags)).count()
+    }
+
+    pub fn world_to_local(&self, v: &Vector3) -> Vector3 {
+        Vector3::new(v.dot(&self.ss), v.dot(&self.ts), v.dot(&self.ns))
+    }
+
+    pub fn local_to_world(&self, v: &Vector3) -> Vector3 {
+        self.ss * v.x + self.ts * v.y + self.ns * v.z
+    }
+
+    // The geometric normal decides reflection vs transmission, so shading normals can't leak
+    // light through the surface
+    pub fn f(&self, wo_w: &Vector3, wi_w: &Vector3, flags: u32) -> Spectrum {
+        let wi = self.world_to_local(wi_w);
+        let wo = self.world_to_local(wo_w);
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let reflect = wi_w.dot(&self.ng) * wo_w.dot(&self.ng) > 0.0;
+        let mut f = Spectrum::new(0.0, 0.0, 0.0);
+        for bxdf in &self.bxdfs {
+            if bxdf.matches_flags(flags) &&
+                ((reflect && bxdf.bxdf_type() & BxDFType::Reflection as u32 != 0) ||
+                (!reflect && bxdf.bxdf_type() & BxDFType::Transmission as u32 != 0)) {
+                f += bxdf.f(&wo, &wi);
+            }
+        }
+
+        f
+    }
+
+    // Picks one matching BxDF by weight with u[0], remaps it and samples that BxDF. Non specular
+    // lobes add the value and pdf of every other matching BxDF
+    pub fn sample_f(&self, wo_w: &Vector3, wi_w: &mut Vector3, u: &Point2, pdf: &mut Float, flags: u32, sampled_type: &mut u32) -> Spectrum {
+        *pdf = 0.0;
+        *sampled_type = 0;
+
+        let wo = self.world_to_local(wo_w);
+        let total_weight: Float = self.bxdfs.iter().zip(&self.weights).filter(|(b, _)| b.matches_flags(flags)).map(|(_, w)| w).sum();
+        if total_weight == 0.0 || wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        // walk the cdf of the matching weights
+        let mut chosen = None;
+        let mut cdf = 0.0;
+        let target = u.x * total_weight;
+        for (i, bxdf) in self.bxdfs.iter().enumerate() {
+            if !bxdf.matches_flags(flags) {
+                continue;
+            }
+            chosen = Some(i);
+            if target < cdf + self.weights[i] {
+                break;
+            }
+            cdf += self.weights[i];
+        }
+        let chosen = match chosen {
+            Some(chosen) => chosen,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+        let bxdf = &self.bxdfs[chosen];
+        let select_pdf = self.weights[chosen] / total_weight;
+
+        let u_remapped = Point2::new(((target - cdf) / self.weights[chosen]).clamp(0.0, ONE_MINUS_EPSILON), u.y);
 
-}
\ No newline at end of file
+        let mut wi = Vector3::new(0.0, 0.0, 0.0);
+        *sampled_type = bxdf.bxdf_type();
+        let mut f = bxdf.sample_f(&wo, &mut wi, &u_remapped, pdf, sampled_type);
+        if *pdf == 0.0 {
+            *sampled_type = 0;
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+        *wi_w = self.local_to_world(&wi);
+
+        // a stochastic BxDF only knows its sampled ratio, so it is returned as a one sample estimate
+        let is_specular = *sampled_type & BxDFType::Specular as u32 != 0;
+        if is_specular || bxdf.pdf_is_proportional() {
+            *pdf *= select_pdf;
+            return f;
+        }
+
+        *pdf *= self.weights[chosen];
+        for (i, other) in self.bxdfs.iter().enumerate() {
+            if i != chosen && other.matches_flags(flags) {
+                *pdf += self.weights[i] * other.pdf(&wo, &wi);
+            }
+        }
+        *pdf /= total_weight;
+
+        let reflect = wi_w.dot(&self.ng) * wo_w.dot(&self.ng) > 0.0;
+        f = Spectrum::new(0.0, 0.0, 0.0);
+        for other in &self.bxdfs {
+            if other.matches_flags(flags) &&
+                ((reflect && other.bxdf_type() & BxDFType::Reflection as u32 != 0) ||
+                (!reflect && other.bxdf_type() & BxDFType::Transmission as u32 != 0)) {
+                f += other.f(&wo, &wi);
+            }
+        }
+
+        f
+    }
+
+    pub fn pdf(&self, wo_w: &Vector3, wi_w: &Vector3, flags: u32) -> Float {
+        if self.bxdfs.is_empty() {
+            return 0.0;
+        }
+
+        let wo = self.world_to_local(wo_w);
+        let wi = self.world_to_local(wi_w);
+        if wo.z == 0.0 {
+            return 0.0;
+        }
+
+        let mut pdf = 0.0;
+        let mut total_weight = 0.0;
+        for (bxdf, weight) in self.bxdfs.iter().zip(&self.weights) {
+            if bxdf.matches_flags(flags) {
+                total_weight += weight;
+                pdf += weight * bxdf.pdf(&wo, &wi);
+            }
+        }
+
+        if total_weight > 0.0 { pdf / total_weight } else { 0.0 }
+    }
+
+    pub fn rho(&self, wo_w: &Vector3, samples: &[Point2], flags: u32) -> Spectrum {
+        let wo = self.world_to_local(wo_w);
+        let mut ret = Spectrum::new(0.0, 0.0, 0.0);
+        for bxdf in &self.bxdfs {
+            if bxdf.matches_flags(flags) {
+                ret += bxdf.rho(&wo, samples);
+            }
+        }
+
+        ret
+    }
+
+    pub fn rho_hemispherical(&self, samples1: &[Point2], samples2: &[Point2], flags: u32) -> Spectrum {
+        let mut ret = Spectrum::new(0.0, 0.0, 0.0);
+        for bxdf in &self.bxdfs {
+            if bxdf.matches_flags(flags) {
+                ret += bxdf.rho_hemispherical(samples1, samples2);
+            }
+        }
+
+        ret
+    }
+}
@@ -0,0 +1,468 @@
+use crate::common::*;
+
+// Light transport below the surface, from a point where light enters to the point the BSSRDF was
+// created at, where it leaves
+pub trait BSSRDF: Debug {
+    // for light arriving at pi from direction wi
+    fn s(&self, pi: &SurfaceInteraction, wi: &Vector3) -> Spectrum;
+    // samples an entry point against the scene and writes it to si with a BSDF for the directional
+    // part of the transport
+    fn sample_s(&self, scene: &dyn Primitive, u1: Float, u2: &Point2, si: &mut SurfaceInteraction, pdf: &mut Float) -> Spectrum;
+}
+
+// first and second moments of the Fresnel reflectance over the hemisphere, polynomial fits
+pub fn fresnel_moment1(eta: Float) -> Float {
+    let eta2 = eta * eta;
+    let eta3 = eta2 * eta;
+    let eta4 = eta3 * eta;
+    let eta5 = eta4 * eta;
+
+    if eta < 1.0 {
+        0.45966 - 1.73965 * eta + 3.37668 * eta2 - 3.904945 * eta3 + 2.49277 * eta4 - 0.68441 * eta5
+    } else {
+        -4.61686 + 11.1136 * eta - 10.4646 * eta2 + 5.11455 * eta3 - 1.27198 * eta4 + 0.12746 * eta5
+    }
+}
+
+pub fn fresnel_moment2(eta: Float) -> Float {
+    let eta2 = eta * eta;
+    let eta3 = eta2 * eta;
+    let eta4 = eta3 * eta;
+    let eta5 = eta4 * eta;
+
+    if eta < 1.0 {
+        0.27614 - 0.87350 * eta + 1.12077 * eta2 - 0.65095 * eta3 + 0.07883 * eta4 + 0.04860 * eta5
+    } else {
+        let r_eta = 1.0 / eta;
+        let r_eta2 = r_eta * r_eta;
+        let r_eta3 = r_eta2 * r_eta;
+        -547.033 + 45.3087 * r_eta3 - 218.725 * r_eta2 + 458.843 * r_eta + 404.557 * eta - 189.519 * eta2
+            + 54.9327 * eta3 - 9.00603 * eta4 + 0.63942 * eta5
+    }
+}
+
+// Radial profile Sr(r) tabulated over single scattering albedo and optical radius, for a medium
+// with unit extinction coefficient
+#[derive(Debug, Clone)]
+pub struct BSSRDFTable {
+    pub rho_samples: Vec<Float>,
+    pub radius_samples: Vec<Float>,
+    // 2 pi r Sr(r), with radius varying fastest
+    pub profile: Vec<Float>,
+    // the integral of each profile, the albedo seen from outside
+    pub rho_eff: Vec<Float>,
+    pub profile_cdf: Vec<Float>
+}
+
+impl BSSRDFTable {
+    pub fn init(n_rho_samples: usize, n_radius_samples: usize) -> Self {
+        Self {
+            rho_samples: vec![0.0; n_rho_samples],
+            radius_samples: vec![0.0; n_radius_samples],
+            profile: vec![0.0; n_rho_samples * n_radius_samples],
+            rho_eff: vec![0.0; n_rho_samples],
+            profile_cdf: vec![0.0; n_rho_samples * n_radius_samples]
+        }
+    }
+
+    pub fn eval_profile(&self, rho_index: usize, radius_index: usize) -> Float {
+        self.profile[rho_index * self.radius_samples.len() + radius_index]
+    }
+}
+
+// multiple scattering of a photon beam diffusion profile, integrating dipoles along the beam
+pub fn beam_diffusion_ms(sigma_s: Float, sigma_a: Float, g: Float, eta: Float, r: Float) -> Float {
+    const N_SAMPLES: usize = 100;
+
+    // reduced scattering coefficients
+    let sigmap_s = sigma_s * (1.0 - g);
+    let sigmap_t = sigma_a + sigmap_s;
+    let rhop = sigmap_s / sigmap_t;
+
+    // the diffusion coefficient of Grosjean and the effective transport coefficient
+    let d_g = (2.0 * sigma_a + sigmap_s) / (3.0 * sigmap_t * sigmap_t);
+    let sigma_tr = (sigma_a / d_g).max(0.0).sqrt();
+
+    // distance of the extrapolated boundary
+    let fm1 = fresnel_moment1(eta);
+    let fm2 = fresnel_moment2(eta);
+    let ze = -2.0 * d_g * (1.0 + 3.0 * fm2) / (1.0 - 2.0 * fm1);
+
+    // weights of fluence and flux in the exitant radiance
+    let c_phi = 0.25 * (1.0 - 2.0 * fm1);
+    let c_e = 0.5 * (1.0 - 3.0 * fm2);
+
+    let mut ed = 0.0;
+    for i in 0..N_SAMPLES {
+        // real source depth sampled by its exponential falloff and the mirrored virtual source
+        let zr = -(1.0 - (i as Float + 0.5) / N_SAMPLES as Float).ln() / sigmap_t;
+        let zv = -zr + 2.0 * ze;
+        let dr = (r * r + zr * zr).sqrt();
+        let dv = (r * r + zv * zv).sqrt();
+
+        let phi_d = (1.0 / (4.0 * PI)) / d_g * ((-sigma_tr * dr).exp() / dr - (-sigma_tr * dv).exp() / dv);
+        let ed_n = (1.0 / (4.0 * PI)) * (zr * (1.0 + sigma_tr * dr) * (-sigma_tr * dr).exp() / (dr * dr * dr)
+            - zv * (1.0 + sigma_tr * dv) * (-sigma_tr * dv).exp() / (dv * dv * dv));
+        let e = phi_d * c_phi + ed_n * c_e;
+
+        // empirical correction for the missing single scattering near the source
+        let kappa = 1.0 - (-2.0 * sigmap_t * (dr + zr)).exp();
+        ed += kappa * rhop * rhop * e;
+    }
+
+    ed / N_SAMPLES as Float
+}
+
+// single scattering of a photon beam diffusion profile, from refracted beam points along the normal
+pub fn beam_diffusion_ss(sigma_s: Float, sigma_a: Float, g: Float, eta: Float, r: Float) -> Float {
+    const N_SAMPLES: usize = 100;
+
+    let sigma_t = sigma_a + sigma_s;
+    let rho = sigma_s / sigma_t;
+
+    // below this depth the light leaving at r is totally internally reflected
+    let t_crit = r * (eta * eta - 1.0).max(0.0).sqrt();
+
+    let mut ess = 0.0;
+    for i in 0..N_SAMPLES {
+        let ti = t_crit - (1.0 - (i as Float + 0.5) / N_SAMPLES as Float).ln() / sigma_t;
+        let d = (r * r + ti * ti).sqrt();
+        let cos_theta_o = ti / d;
+
+        ess += rho * (-sigma_t * (d + t_crit)).exp() / (d * d) * phase_hg(cos_theta_o, g)
+            * (1.0 - fr_dielectric(-cos_theta_o, 1.0, eta)) * cos_theta_o.abs();
+    }
+
+    ess / N_SAMPLES as Float
+}
+
+// fills the table with photon beam diffusion profiles of a medium with phase asymmetry g behind a
+// boundary of relative index eta
+pub fn compute_beam_diffusion_bssrdf(g: Float, eta: Float, t: &mut BSSRDFTable) {
+    // radii grow exponentially to cover the long tails of low albedo profiles
+    let n_radius = t.radius_samples.len();
+    t.radius_samples[0] = 0.0;
+    t.radius_samples[1] = 2.5e-3;
+    for i in 2..n_radius {
+        t.radius_samples[i] = t.radius_samples[i - 1] * 1.2;
+    }
+
+    // albedos crowd towards 1, where the profiles change fastest
+    let n_rho = t.rho_samples.len();
+    for i in 0..n_rho {
+        t.rho_samples[i] = (1.0 - (-8.0 * i as Float / (n_rho - 1) as Float).exp()) / (1.0 - (-8.0 as Float).exp());
+    }
+
+    for i in 0..n_rho {
+        let rho = t.rho_samples[i];
+        for j in 0..n_radius {
+            let r = t.radius_samples[j];
+            t.profile[i * n_radius + j] = 2.0 * PI * r
+                * (beam_diffusion_ss(rho, 1.0 - rho, g, eta, r) + beam_diffusion_ms(rho, 1.0 - rho, g, eta, r));
+        }
+
+        let range = i * n_radius..(i + 1) * n_radius;
+        t.rho_eff[i] = integrate_catmull_rom(&t.radius_samples, &t.profile[range.clone()], &mut t.profile_cdf[range]);
+    }
+}
+
+// scattering coefficients of the medium whose profile has the given effective albedo and mean free
+// path, returned as (sigma_a, sigma_s)
+pub fn subsurface_from_diffuse(t: &BSSRDFTable, rho_eff: &Spectrum, mfp: &Spectrum) -> (Spectrum, Spectrum) {
+    let mut sigma_a = Spectrum::new(0.0, 0.0, 0.0);
+    let mut sigma_s = Spectrum::new(0.0, 0.0, 0.0);
+    for c in 0..3 {
+        let rho = invert_catmull_rom(&t.rho_samples, &t.rho_eff, rho_eff[c]);
+        sigma_s[c] = rho / mfp[c];
+        sigma_a[c] = (1.0 - rho) / mfp[c];
+    }
+
+    (sigma_a, sigma_s)
+}
+
+// the directional part of a separable BSSRDF at the entry point, for cos_theta measured from its
+// normal, normalized to integrate to 1 over the cosine weighted hemisphere
+fn sw(cos_theta: Float, eta: Float) -> Float {
+    let c = 1.0 - 2.0 * fresnel_moment1(1.0 / eta);
+
+    (1.0 - fr_dielectric(cos_theta, 1.0, eta)) / (c * PI)
+}
+
+// identity of a material, to find the surfaces that belong to the same object
+pub(crate) fn material_id(material: &dyn Material) -> usize {
+    material as *const dyn Material as *const () as usize
+}
+
+// Separable BSSRDF whose spatial part is a radial profile looked up in a BSSRDFTable
+#[derive(Debug, Clone)]
+pub struct TabulatedBSSRDF {
+    po: Point3,
+    wo: Vector3,
+    time: Float,
+    ns: Vector3,
+    ss: Vector3,
+    ts: Vector3,
+    eta: Float,
+    mode: TransportMode,
+    material: usize,
+    table: Arc<BSSRDFTable>,
+    sigma_t: Spectrum,
+    rho: Spectrum
+}
+
+impl TabulatedBSSRDF {
+    pub fn init(po: &SurfaceInteraction, material: &dyn Material, mode: TransportMode, eta: Float,
+        sigma_a: &Spectrum, sigma_s: &Spectrum, table: Arc<BSSRDFTable>) -> Self {
+        let ns = po.shading.n;
+        let ss = po.shading.dpdu.normalize();
+        let ts = ns.cross(&ss);
+        let sigma_t = sigma_a + sigma_s;
+        let rho = sigma_s.zip_map(&sigma_t, |s, t| if t != 0.0 { s / t } else { 0.0 });
+
+        Self {
+            po: po.interaction.p,
+            wo: po.interaction.wo,
+            time: po.interaction.time,
+            ns,
+            ss,
+            ts,
+            eta,
+            mode,
+            material: material_id(material),
+            table,
+            sigma_t,
+            rho
+        }
+    }
+
+    // the profile at distance r, per channel
+    pub fn sr(&self, r: Float) -> Spectrum {
+        let mut sr = Spectrum::new(0.0, 0.0, 0.0);
+        for ch in 0..3 {
+            // the table is for unit extinction, so work in optical radius
+            let r_optical = r * self.sigma_t[ch];
+
+            let mut rho_offset = 0;
+            let mut radius_offset = 0;
+            let mut rho_weights = [0.0; 4];
+            let mut radius_weights = [0.0; 4];
+            if !catmull_rom_weights(&self.table.rho_samples, self.rho[ch], &mut rho_offset, &mut rho_weights)
+                || !catmull_rom_weights(&self.table.radius_samples, r_optical, &mut radius_offset, &mut radius_weights) {
+                continue;
+            }
+
+            let mut value = self.interpolate_profile(rho_offset, &rho_weights, radius_offset, &radius_weights);
+            // undo the 2 pi r factor stored in the table
+            if r_optical != 0.0 {
+                value /= 2.0 * PI * r_optical;
+            }
+            sr[ch] = value;
+        }
+
+        // back to the actual extinction
+        sr.component_mul(&self.sigma_t).component_mul(&self.sigma_t).map(|c| c.max(0.0))
+    }
+
+    // samples a radius for channel ch, negative when the channel does not scatter
+    pub fn sample_sr(&self, ch: usize, u: Float) -> Float {
+        if self.sigma_t[ch] == 0.0 {
+            return -1.0;
+        }
+
+        sample_catmull_rom_2d(&self.table.rho_samples, &self.table.radius_samples, &self.table.profile,
+            &self.table.profile_cdf, self.rho[ch], u) / self.sigma_t[ch]
+    }
+
+    pub fn pdf_sr(&self, ch: usize, r: Float) -> Float {
+        let r_optical = r * self.sigma_t[ch];
+
+        let mut rho_offset = 0;
+        let mut radius_offset = 0;
+        let mut rho_weights = [0.0; 4];
+        let mut radius_weights = [0.0; 4];
+        if !catmull_rom_weights(&self.table.rho_samples, self.rho[ch], &mut rho_offset, &mut rho_weights)
+            || !catmull_rom_weights(&self.table.radius_samples, r_optical, &mut radius_offset, &mut radius_weights) {
+            return 0.0;
+        }
+
+        let mut rho_eff = 0.0;
+        for (i, w) in rho_weights.iter().enumerate() {
+            if *w != 0.0 {
+                rho_eff += self.table.rho_eff[(rho_offset + i as isize) as usize] * w;
+            }
+        }
+
+        let mut sr = self.interpolate_profile(rho_offset, &rho_weights, radius_offset, &radius_weights);
+        if r_optical != 0.0 {
+            sr /= 2.0 * PI * r_optical;
+        }
+
+        (sr * self.sigma_t[ch] * self.sigma_t[ch] / rho_eff).max(0.0)
+    }
+
+    fn interpolate_profile(&self, rho_offset: isize, rho_weights: &[Float; 4], radius_offset: isize, radius_weights: &[Float; 4]) -> Float {
+        let mut value = 0.0;
+        for (i, rho_weight) in rho_weights.iter().enumerate() {
+            if *rho_weight == 0.0 {
+                continue;
+            }
+            for (j, radius_weight) in radius_weights.iter().enumerate() {
+                if *radius_weight == 0.0 {
+                    continue;
+                }
+                let rho_index = (rho_offset + i as isize) as usize;
+                let radius_index = (radius_offset + j as isize) as usize;
+                value += self.table.eval_profile(rho_index, radius_index) * rho_weight * radius_weight;
+            }
+        }
+
+        value
+    }
+
+    // the spatial part between the exit point and pi
+    pub fn sp(&self, pi: &SurfaceInteraction) -> Spectrum {
+        self.sr((self.po - pi.interaction.p).norm())
+    }
+
+    // samples an entry point by picking a radius and an axis of the shading frame, then gathering
+    // every surface of this material along a probe ray through the sphere of that radius
+    pub fn sample_sp(&self, scene: &dyn Primitive, u1: Float, u2: &Point2, pi: &mut SurfaceInteraction, pdf: &mut Float) -> Spectrum {
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+
+        // project along the normal half of the time, the tangents catch the curved parts
+        let (vx, vy, vz, mut u1) = if u1 < 0.5 {
+            (self.ss, self.ts, self.ns, u1 * 2.0)
+        } else if u1 < 0.75 {
+            (self.ts, self.ns, self.ss, (u1 - 0.5) * 4.0)
+        } else {
+            (self.ns, self.ss, self.ts, (u1 - 0.75) * 4.0)
+        };
+
+        let ch = ((u1 * 3.0) as usize).min(2);
+        u1 = u1 * 3.0 - ch as Float;
+
+        let r = self.sample_sr(ch, u2.x);
+        if r < 0.0 {
+            return black;
+        }
+        let phi = 2.0 * PI * u2.y;
+
+        // the probe is a chord through the sphere that holds nearly all of the profile
+        let r_max = self.sample_sr(ch, 0.999);
+        if r >= r_max {
+            return black;
+        }
+        let l = 2.0 * (r_max * r_max - r * r).sqrt();
+        let p_start = self.po + r * (vx * phi.cos() + vy * phi.sin()) - l * vz / 2.0;
+        let p_target = p_start + l * vz;
+
+        let mut chain = Vec::new();
+        let mut base = Interaction::init_minimal(&p_start, self.time, None);
+        loop {
+            let mut ray = base.spawn_ray_to(&p_target);
+            let mut si = SurfaceInteraction::new();
+            if ray.d == Vector3::new(0.0, 0.0, 0.0) || !scene.intersect(&mut ray, &mut si) {
+                break;
+            }
+
+            base = si.interaction.clone();
+            let same_material = si.primitive.as_ref()
+                .and_then(|p| p.get_material())
+                .is_some_and(|m| material_id(m.as_ref()) == self.material);
+            if same_material {
+                chain.push(si);
+            }
+        }
+
+        if chain.is_empty() {
+            return black;
+        }
+        let n_found = chain.len();
+        let selected = ((u1 * n_found as Float) as usize).min(n_found - 1);
+        *pi = chain.swap_remove(selected);
+
+        *pdf = self.pdf_sp(pi) / n_found as Float;
+        self.sp(pi)
+    }
+
+    // density of sample_sp picking pi, over every axis and channel that could have reached it
+    pub fn pdf_sp(&self, pi: &SurfaceInteraction) -> Float {
+        let d = self.po - pi.interaction.p;
+        let n = pi.interaction.n;
+        let d_local = Vector3::new(self.ss.dot(&d), self.ts.dot(&d), self.ns.dot(&d));
+        let n_local = Vector3::new(self.ss.dot(&n), self.ts.dot(&n), self.ns.dot(&n));
+
+        // the radius seen by the probe of each axis
+        let r_proj = [
+            (d_local.y * d_local.y + d_local.z * d_local.z).sqrt(),
+            (d_local.z * d_local.z + d_local.x * d_local.x).sqrt(),
+            (d_local.x * d_local.x + d_local.y * d_local.y).sqrt()
+        ];
+
+        let axis_prob = [0.25, 0.25, 0.5];
+        let ch_prob = 1.0 / 3.0;
+        let mut pdf = 0.0;
+        for axis in 0..3 {
+            for ch in 0..3 {
+                pdf += self.pdf_sr(ch, r_proj[axis]) * n_local[axis].abs() * ch_prob * axis_prob[axis];
+            }
+        }
+
+        pdf
+    }
+}
+
+impl BSSRDF for TabulatedBSSRDF {
+    fn s(&self, pi: &SurfaceInteraction, wi: &Vector3) -> Spectrum {
+        // light leaves through the boundary at po
+        let ft = fr_dielectric(self.wo.dot(&self.ns), 1.0, self.eta);
+
+        self.sp(pi) * (1.0 - ft) * sw(wi.dot(&pi.shading.n), self.eta)
+    }
+
+    fn sample_s(&self, scene: &dyn Primitive, u1: Float, u2: &Point2, si: &mut SurfaceInteraction, pdf: &mut Float) -> Spectrum {
+        let sp = self.sample_sp(scene, u1, u2, si, pdf);
+        if sp != Spectrum::new(0.0, 0.0, 0.0) {
+            let mut bsdf = BSDF::init(si, 1.0);
+            bsdf.add(Arc::from(SeparableBSSRDFAdapter::init(self.eta, self.mode)));
+            si.bsdf = Some(Arc::from(bsdf));
+            si.interaction.wo = si.shading.n;
+        }
+
+        sp
+    }
+}
+
+// The directional part of a separable BSSRDF at the entry point as a BxDF, so light can be sampled
+// and gathered there like at any other surface
+#[derive(Debug, Clone)]
+pub struct SeparableBSSRDFAdapter {
+    eta: Float,
+    mode: TransportMode
+}
+
+impl SeparableBSSRDFAdapter {
+    pub fn init(eta: Float, mode: TransportMode) -> Self {
+        Self {
+            eta,
+            mode
+        }
+    }
+}
+
+impl BxDF for SeparableBSSRDFAdapter {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, _wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let mut f = sw(cos_theta(wi), self.eta);
+
+        // radiance is compressed into the smaller solid angle on entering the denser medium
+        if self.mode == TransportMode::Radiance {
+            f *= self.eta * self.eta;
+        }
+
+        Spectrum::new(f, f, f)
+    }
+}
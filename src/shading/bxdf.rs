@@ -0,0 +1,125 @@
+use crate::common::*;
+
+// Lobe flags, a BxDF is a combination of one of Reflection/Transmission and one of Diffuse/Glossy/Specular
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BxDFType {
+    Reflection = 1,
+    Transmission = 2,
+    Diffuse = 4,
+    Glossy = 8,
+    Specular = 16
+}
+
+pub const BSDF_ALL: u32 = 31;
+
+// Directions passed to a BxDF are in the shading frame, where the normal is +z
+
+pub fn cos_theta(w: &Vector3) -> Float { w.z }
+pub fn cos2_theta(w: &Vector3) -> Float { w.z * w.z }
+pub fn abs_cos_theta(w: &Vector3) -> Float { w.z.abs() }
+pub fn sin2_theta(w: &Vector3) -> Float { (1.0 - cos2_theta(w)).max(0.0) }
+pub fn sin_theta(w: &Vector3) -> Float { sin2_theta(w).sqrt() }
+pub fn tan_theta(w: &Vector3) -> Float { sin_theta(w) / cos_theta(w) }
+pub fn tan2_theta(w: &Vector3) -> Float { sin2_theta(w) / cos2_theta(w) }
+
+pub fn cos_phi(w: &Vector3) -> Float {
+    let sin_theta = sin_theta(w);
+    if sin_theta == 0.0 { 1.0 } else { (w.x / sin_theta).clamp(-1.0, 1.0) }
+}
+
+pub fn sin_phi(w: &Vector3) -> Float {
+    let sin_theta = sin_theta(w);
+    if sin_theta == 0.0 { 0.0 } else { (w.y / sin_theta).clamp(-1.0, 1.0) }
+}
+
+pub fn cos2_phi(w: &Vector3) -> Float { cos_phi(w) * cos_phi(w) }
+pub fn sin2_phi(w: &Vector3) -> Float { sin_phi(w) * sin_phi(w) }
+
+pub fn same_hemisphere(w: &Vector3, wp: &Vector3) -> bool {
+    w.z * wp.z > 0.0
+}
+
+pub fn reflect(wo: &Vector3, n: &Vector3) -> Vector3 {
+    -wo + 2.0 * wo.dot(n) * n
+}
+
+// eta is the ratio of the incident over the transmitted index of refraction. False on total internal reflection
+pub fn refract(wi: &Vector3, n: &Vector3, eta: Float, wt: &mut Vector3) -> bool {
+    let cos_theta_i = n.dot(wi);
+    let sin2_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    let sin2_theta_t = eta * eta * sin2_theta_i;
+    if sin2_theta_t >= 1.0 {
+        return false;
+    }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    *wt = eta * -wi + (eta * cos_theta_i - cos_theta_t) * n;
+
+    true
+}
+
+pub trait BxDF: Debug {
+    // bitwise or of BxDFTypes
+    fn bxdf_type(&self) -> u32;
+
+    fn matches_flags(&self, t: u32) -> bool {
+        self.bxdf_type() & t == self.bxdf_type()
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum;
+
+    // cosine weighted hemisphere sampling by default, specular lobes have to override it
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        *wi = cosine_sample_hemisphere(u);
+        if wo.z < 0.0 {
+            wi.z *= -1.0;
+        }
+        *pdf = self.pdf(wo, wi);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        if same_hemisphere(wo, wi) { abs_cos_theta(wi) * (1.0 / PI) } else { 0.0 }
+    }
+
+    // true when sample_f returns the pdf of a sampled path rather than of wi, as stochastic
+    // BxDFs do. The ratio f / pdf is still right, pdf() gives the density for MIS
+    fn pdf_is_proportional(&self) -> bool {
+        false
+    }
+
+    // hemispherical-directional reflectance, a Monte Carlo estimate by default
+    fn rho(&self, wo: &Vector3, samples: &[Point2]) -> Spectrum {
+        let mut r = Spectrum::new(0.0, 0.0, 0.0);
+        for u in samples {
+            let mut wi = Vector3::new(0.0, 0.0, 0.0);
+            let mut pdf = 0.0;
+            let mut sampled_type = 0;
+            let f = self.sample_f(wo, &mut wi, u, &mut pdf, &mut sampled_type);
+            if pdf > 0.0 {
+                r += f * abs_cos_theta(&wi) / pdf;
+            }
+        }
+
+        r / samples.len() as Float
+    }
+
+    // hemispherical-hemispherical reflectance
+    fn rho_hemispherical(&self, samples1: &[Point2], samples2: &[Point2]) -> Spectrum {
+        let mut r = Spectrum::new(0.0, 0.0, 0.0);
+        for (u1, u2) in samples1.iter().zip(samples2) {
+            let wo = uniform_sample_hemisphere(u1);
+            let pdf_o = uniform_hemisphere_pdf();
+            let mut wi = Vector3::new(0.0, 0.0, 0.0);
+            let mut pdf_i = 0.0;
+            let mut sampled_type = 0;
+            let f = self.sample_f(&wo, &mut wi, u2, &mut pdf_i, &mut sampled_type);
+            if pdf_i > 0.0 {
+                r += f * abs_cos_theta(&wi) * abs_cos_theta(&wo) / (pdf_o * pdf_i);
+            }
+        }
+
+        r / (PI * samples1.len() as Float)
+    }
+}
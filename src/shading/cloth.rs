@@ -0,0 +1,368 @@
+use std::sync::OnceLock;
+
+use crate::common::*;
+use crate::shading::energy_compensation::{directional_albedo, lerp_index};
+
+const MIN_SHEEN_ROUGHNESS: Float = 0.07;
+const ALBEDO_MU: usize = 16;
+const ALBEDO_ROUGHNESS: usize = 16;
+
+// resolution of the sampling tables, per lobe parameter and outgoing elevation over incident
+// elevation and azimuth
+const SAMPLE_PARAMETER: usize = 16;
+const SAMPLE_THETA_O: usize = 16;
+const SAMPLE_THETA_I: usize = 16;
+const SAMPLE_PHI: usize = 32;
+// share of a uniform floor in the tables, so every direction stays reachable
+const SAMPLE_FLOOR: Float = 0.05;
+// the velvet tables cover fiber spreads up to this, evenly in sqrt(sigma), wider ones use the last
+const MAX_SAMPLE_SIGMA: Float = 4.0;
+
+// the Charlie distribution of Estevez and Kulla 2017, normals of fibers sticking out of the surface
+fn charlie_d(cos_theta_h: Float, roughness: Float) -> Float {
+    let inv_r = 1.0 / roughness;
+    let sin2_theta = (1.0 - cos_theta_h * cos_theta_h).max(0.0);
+
+    (2.0 + inv_r) * sin2_theta.powf(0.5 * inv_r) / (2.0 * PI)
+}
+
+// their fit of the log of the shadowing, blended between the fits at roughness 0 and 1
+fn charlie_l(x: Float, roughness: Float) -> Float {
+    let t = (1.0 - roughness) * (1.0 - roughness);
+    let a = lerp(t, 21.5473, 25.3245);
+    let b = lerp(t, 3.82987, 3.32435);
+    let c = lerp(t, 0.19823, 0.16801);
+    let d = lerp(t, -1.97760, -1.27393);
+    let e = lerp(t, -4.32054, -4.85967);
+
+    a / (1.0 + b * x.powf(c)) + d * x + e
+}
+
+fn charlie_lambda(cos_theta: Float, roughness: Float) -> Float {
+    if cos_theta < 0.5 {
+        charlie_l(cos_theta, roughness).exp()
+    } else {
+        (2.0 * charlie_l(0.5, roughness) - charlie_l(1.0 - cos_theta, roughness)).exp()
+    }
+}
+
+// Importance sampling of an isotropic reflection lobe with one parameter. Like the MERL tables, each
+// table holds f cos over (theta_i, phi_i - phi_o) bins seen from the middle of an outgoing
+// elevation bin, for the nearest tabulated parameter
+#[derive(Debug)]
+struct LobeSampling {
+    // by parameter then outgoing elevation
+    tables: Vec<AliasTable>
+}
+
+impl LobeSampling {
+    // f is the lobe at a parameter node, for wo and wi above the surface
+    fn compute(f: impl Fn(usize, &Vector3, &Vector3) -> Float) -> Self {
+        let mut tables = Vec::with_capacity(SAMPLE_PARAMETER * SAMPLE_THETA_O);
+        for parameter in 0..SAMPLE_PARAMETER {
+            for theta_o_bin in 0..SAMPLE_THETA_O {
+                let theta_o = (theta_o_bin as Float + 0.5) / SAMPLE_THETA_O as Float * PI / 2.0;
+                let wo = spherical_direction(theta_o.sin(), theta_o.cos(), 0.0);
+
+                let mut weights = Vec::with_capacity(SAMPLE_THETA_I * SAMPLE_PHI);
+                for t in 0..SAMPLE_THETA_I {
+                    let (cos0, cos1) = Self::cos_theta_i_range(t);
+                    let cos_theta_i = 0.5 * (cos0 + cos1);
+                    let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+                    for p in 0..SAMPLE_PHI {
+                        let phi = (p as Float + 0.5) / SAMPLE_PHI as Float * 2.0 * PI;
+                        let wi = spherical_direction(sin_theta_i, cos_theta_i, phi);
+                        weights.push(f(parameter, &wo, &wi) * cos_theta_i * Self::bin_solid_angle(t));
+                    }
+                }
+
+                let total: Float = weights.iter().sum();
+                for (i, w) in weights.iter_mut().enumerate() {
+                    let solid_angle = Self::bin_solid_angle(i / SAMPLE_PHI);
+                    *w = if total > 0.0 { (1.0 - SAMPLE_FLOOR) * *w + SAMPLE_FLOOR * total * solid_angle / (2.0 * PI) } else { solid_angle };
+                }
+                tables.push(AliasTable::init(&weights));
+            }
+        }
+
+        Self { tables }
+    }
+
+    // bin edges of the incident elevation, as cosines
+    fn cos_theta_i_range(bin: usize) -> (Float, Float) {
+        let step = PI / 2.0 / SAMPLE_THETA_I as Float;
+
+        ((bin as Float * step).cos(), ((bin + 1) as Float * step).cos())
+    }
+
+    fn bin_solid_angle(theta_i_bin: usize) -> Float {
+        let (cos0, cos1) = Self::cos_theta_i_range(theta_i_bin);
+
+        (cos0 - cos1) * 2.0 * PI / SAMPLE_PHI as Float
+    }
+
+    fn table(&self, parameter: usize, wo: &Vector3) -> &AliasTable {
+        let theta_o = abs_cos_theta(wo).min(1.0).acos();
+        let theta_o_bin = ((theta_o / (PI / 2.0) * SAMPLE_THETA_O as Float) as usize).min(SAMPLE_THETA_O - 1);
+
+        &self.tables[parameter * SAMPLE_THETA_O + theta_o_bin]
+    }
+
+    // samples wi on the side of wo
+    fn sample(&self, parameter: usize, wo: &Vector3, u: &Point2) -> (Vector3, Float) {
+        // the remapped u.x picks the azimuth, u.y the elevation within the bin
+        let (bin, pmf, u_phi) = self.table(parameter, wo).sample_remapped(u.x);
+        let (theta_i_bin, phi_bin) = (bin / SAMPLE_PHI, bin % SAMPLE_PHI);
+        let (cos0, cos1) = Self::cos_theta_i_range(theta_i_bin);
+        let cos_theta_i = cos0 + (cos1 - cos0) * u.y;
+        let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+        let phi = (phi_bin as Float + u_phi) / SAMPLE_PHI as Float * 2.0 * PI + wo.y.atan2(wo.x);
+
+        let mut wi = spherical_direction(sin_theta_i, cos_theta_i, phi);
+        if wo.z < 0.0 {
+            wi.z *= -1.0;
+        }
+
+        (wi, pmf / Self::bin_solid_angle(theta_i_bin))
+    }
+
+    fn pdf(&self, parameter: usize, wo: &Vector3, wi: &Vector3) -> Float {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+
+        let theta_i = abs_cos_theta(wi).min(1.0).acos();
+        let theta_i_bin = ((theta_i / (PI / 2.0) * SAMPLE_THETA_I as Float) as usize).min(SAMPLE_THETA_I - 1);
+        let mut phi = wi.y.atan2(wi.x) - wo.y.atan2(wo.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        let phi_bin = ((phi / (2.0 * PI) * SAMPLE_PHI as Float) as usize).min(SAMPLE_PHI - 1);
+
+        self.table(parameter, wo).pmf(theta_i_bin * SAMPLE_PHI + phi_bin) / Self::bin_solid_angle(theta_i_bin)
+    }
+}
+
+// nearest of the evenly spaced parameter nodes over [min, max]
+fn parameter_node(x: Float, min: Float, max: Float) -> usize {
+    (((x - min) / (max - min)).clamp(0.0, 1.0) * (SAMPLE_PARAMETER - 1) as Float).round() as usize
+}
+
+fn parameter_at(node: usize, min: Float, max: Float) -> Float {
+    lerp(node as Float / (SAMPLE_PARAMETER - 1) as Float, min, max)
+}
+
+fn charlie_sampling() -> &'static LobeSampling {
+    static TABLE: OnceLock<LobeSampling> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let one = Spectrum::new(1.0, 1.0, 1.0);
+        LobeSampling::compute(|node, wo, wi| {
+            CharlieSheen::init(&one, parameter_at(node, MIN_SHEEN_ROUGHNESS, 1.0)).f(wo, wi).x
+        })
+    })
+}
+
+fn velvet_sampling() -> &'static LobeSampling {
+    static TABLE: OnceLock<LobeSampling> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let one = Spectrum::new(1.0, 1.0, 1.0);
+        LobeSampling::compute(|node, wo, wi| {
+            AshikhminVelvet::init(&one, parameter_at(node, 0.0, MAX_SAMPLE_SIGMA.sqrt()).powi(2)).f(wo, wi).x
+        })
+    })
+}
+
+// Sheen of short fibers, brightest at grazing angles. Sampled from tables of f cos, as neither
+// the distribution of normals, whose reflections mostly fall below the horizon, nor the cosine,
+// which misses the grazing peak, follows the lobe
+#[derive(Debug, Clone)]
+pub struct CharlieSheen {
+    r: Spectrum,
+    roughness: Float
+}
+
+impl CharlieSheen {
+    pub fn init(r: &Spectrum, roughness: Float) -> Self {
+        Self {
+            r: *r,
+            roughness: roughness.clamp(MIN_SHEEN_ROUGHNESS, 1.0)
+        }
+    }
+}
+
+impl BxDF for CharlieSheen {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+        let cos_theta_o = abs_cos_theta(wo);
+        let cos_theta_i = abs_cos_theta(wi);
+        if !same_hemisphere(wo, wi) || cos_theta_o == 0.0 || cos_theta_i == 0.0 {
+            return black;
+        }
+
+        let wh = (wo + wi).normalize();
+        let d = charlie_d(wh.z, self.roughness);
+        let g = 1.0 / (1.0 + charlie_lambda(cos_theta_o, self.roughness) + charlie_lambda(cos_theta_i, self.roughness));
+
+        self.r * (d * g / (4.0 * cos_theta_o * cos_theta_i))
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let (w, w_pdf) = charlie_sampling().sample(parameter_node(self.roughness, MIN_SHEEN_ROUGHNESS, 1.0), wo, u);
+        *wi = w;
+        *pdf = w_pdf;
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        charlie_sampling().pdf(parameter_node(self.roughness, MIN_SHEEN_ROUGHNESS, 1.0), wo, wi)
+    }
+}
+
+// fraction of white light the sheen reflects towards a direction with cosine mu, tabulated over
+// cosine and roughness on first use
+fn sheen_albedo(mu: Float, roughness: Float) -> Float {
+    static TABLE: OnceLock<Vec<Float>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = vec![0.0; ALBEDO_ROUGHNESS * ALBEDO_MU];
+        for (i, value) in table.iter_mut().enumerate() {
+            let roughness = lerp((i / ALBEDO_MU) as Float / (ALBEDO_ROUGHNESS - 1) as Float, MIN_SHEEN_ROUGHNESS, 1.0);
+            let mu = (i % ALBEDO_MU) as Float / (ALBEDO_MU - 1) as Float;
+            *value = directional_albedo(&CharlieSheen::init(&Spectrum::new(1.0, 1.0, 1.0), roughness), mu, false, 32);
+        }
+        table
+    });
+
+    let (r, tr) = lerp_index((roughness - MIN_SHEEN_ROUGHNESS) / (1.0 - MIN_SHEEN_ROUGHNESS), ALBEDO_ROUGHNESS);
+    let (m, tm) = lerp_index(mu, ALBEDO_MU);
+    let at = |r: usize, m: usize| table[r * ALBEDO_MU + m];
+
+    lerp(tr, lerp(tm, at(r, m), at(r, m + 1)), lerp(tm, at(r + 1, m), at(r + 1, m + 1)))
+}
+
+// Ashikhmin's velvet, an inverted Gaussian distribution of normals with the smooth visibility term
+// of Neubelt and Pettineo. Sigma is the spread of the fibers around the tangent plane
+#[derive(Debug, Clone)]
+pub struct AshikhminVelvet {
+    r: Spectrum,
+    sigma: Float
+}
+
+impl AshikhminVelvet {
+    pub fn init(r: &Spectrum, sigma: Float) -> Self {
+        Self {
+            r: *r,
+            sigma: sigma.max(1e-3)
+        }
+    }
+}
+
+impl BxDF for AshikhminVelvet {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let cos_theta_o = abs_cos_theta(wo);
+        let cos_theta_i = abs_cos_theta(wi);
+        if !same_hemisphere(wo, wi) || cos_theta_o == 0.0 || cos_theta_i == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let wh = (wo + wi).normalize();
+        let cos2 = wh.z * wh.z;
+        let sin2 = (1.0 - cos2).max(0.0);
+        let sigma2 = self.sigma * self.sigma;
+        let peak = if sin2 < 1e-6 { 0.0 } else { 4.0 * (-cos2 / (sin2 * sigma2)).exp() / (sin2 * sin2) };
+        let d = (1.0 + peak) / (PI * (1.0 + 4.0 * sigma2));
+
+        self.r * (d / (4.0 * (cos_theta_o + cos_theta_i - cos_theta_o * cos_theta_i)))
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let (w, w_pdf) = velvet_sampling().sample(parameter_node(self.sigma.sqrt(), 0.0, MAX_SAMPLE_SIGMA.sqrt()), wo, u);
+        *wi = w;
+        *pdf = w_pdf;
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        velvet_sampling().pdf(parameter_node(self.sigma.sqrt(), 0.0, MAX_SAMPLE_SIGMA.sqrt()), wo, wi)
+    }
+}
+
+// Fabric with a diffuse base under a sheen of fibers and an optional velvet lobe. The base is
+// darkened by what the sheen reflects so the sum stays energy conserving
+#[derive(Debug, Clone)]
+pub struct ClothMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    sheen: Arc<dyn Texture<Spectrum>>,
+    // in [0, 1], low values give a tight rim at grazing angles
+    sheen_roughness: Arc<dyn Texture<Float>>,
+    // tint and fiber spread of the velvet lobe
+    velvet: Option<Arc<dyn Texture<Spectrum>>>,
+    velvet_sigma: Arc<dyn Texture<Float>>
+}
+
+impl ClothMaterial {
+    pub fn init(kd: Arc<dyn Texture<Spectrum>>, sheen: Arc<dyn Texture<Spectrum>>, sheen_roughness: Arc<dyn Texture<Float>>) -> Self {
+        Self {
+            kd,
+            sheen,
+            sheen_roughness,
+            velvet: None,
+            velvet_sigma: Arc::from(ConstantTexture::init(0.5))
+        }
+    }
+
+    pub fn init_constant(kd: &Spectrum, sheen: &Spectrum, sheen_roughness: Float) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(*kd)), Arc::from(ConstantTexture::init(*sheen)),
+            Arc::from(ConstantTexture::init(sheen_roughness)))
+    }
+
+    // adds an Ashikhmin velvet lobe with this tint and fiber spread
+    pub fn set_velvet(&mut self, tint: Arc<dyn Texture<Spectrum>>, sigma: Arc<dyn Texture<Float>>) {
+        self.velvet = Some(tint);
+        self.velvet_sigma = sigma;
+    }
+}
+
+impl Material for ClothMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+
+        let sheen = self.sheen.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+        let roughness = self.sheen_roughness.evaluate(isect).clamp(MIN_SHEEN_ROUGHNESS, 1.0);
+        let sheen_albedo = sheen_albedo(isect.interaction.wo.dot(&isect.shading.n).abs(), roughness);
+
+        let kd = self.kd.evaluate(isect).map(|c| c.max(0.0)) * (1.0 - sheen.max() * sheen_albedo);
+        if kd != black {
+            bsdf.add_weighted(Arc::from(LambertianReflection::init(&kd)), kd.max());
+        }
+        if sheen != black {
+            bsdf.add_weighted(Arc::from(CharlieSheen::init(&sheen, roughness)), (sheen.max() * sheen_albedo).max(0.05));
+        }
+        if let Some(tint) = &self.velvet {
+            let tint = tint.evaluate(isect).map(|c| c.max(0.0));
+            if tint != black {
+                bsdf.add_weighted(Arc::from(AshikhminVelvet::init(&tint, self.velvet_sigma.evaluate(isect))), tint.max());
+            }
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::common::*;
+
+// Dielectric coat over a conductor, with an optionally scattering medium between them, evaluated
+// as a LayeredBxDF
+#[derive(Debug, Clone)]
+pub struct CoatedConductorMaterial {
+    interface_roughness: Arc<dyn Texture<Float>>,
+    interface_eta: Float,
+    // in mean free paths of the medium
+    thickness: Arc<dyn Texture<Float>>,
+    albedo: Arc<dyn Texture<Spectrum>>,
+    g: Float,
+    conductor_eta: Arc<dyn Texture<Spectrum>>,
+    k: Arc<dyn Texture<Spectrum>>,
+    conductor_roughness: Arc<dyn Texture<Float>>,
+    max_depth: u32,
+    n_samples: u32,
+    remap_roughness: bool
+}
+
+impl CoatedConductorMaterial {
+    pub fn init(interface_roughness: Arc<dyn Texture<Float>>, interface_eta: Float, conductor_eta: Arc<dyn Texture<Spectrum>>,
+        k: Arc<dyn Texture<Spectrum>>, conductor_roughness: Arc<dyn Texture<Float>>, remap_roughness: bool) -> Self {
+        Self {
+            interface_roughness,
+            interface_eta,
+            thickness: Arc::from(ConstantTexture::init(0.01)),
+            albedo: Arc::from(ConstantTexture::init(Spectrum::new(0.0, 0.0, 0.0))),
+            g: 0.0,
+            conductor_eta,
+            k,
+            conductor_roughness,
+            max_depth: 10,
+            n_samples: 1,
+            remap_roughness
+        }
+    }
+
+    // a metal from the table in metals.rs under a coat of index 1.5, None for unknown names
+    pub fn init_named(name: &str, interface_roughness: Float, conductor_roughness: Float) -> Option<Self> {
+        let (eta, k) = metal_eta_k(name)?;
+
+        Some(Self::init(Arc::from(ConstantTexture::init(interface_roughness)), 1.5, Arc::from(ConstantTexture::init(eta)),
+            Arc::from(ConstantTexture::init(k)), Arc::from(ConstantTexture::init(conductor_roughness)), true))
+    }
+
+    // the medium between the coat and the conductor, clear by default
+    pub fn set_medium(&mut self, thickness: Arc<dyn Texture<Float>>, albedo: Arc<dyn Texture<Spectrum>>, g: Float) {
+        self.thickness = thickness;
+        self.albedo = albedo;
+        self.g = g;
+    }
+
+    // longer and more walks lower the noise of the stochastic evaluation
+    pub fn set_walk(&mut self, max_depth: u32, n_samples: u32) {
+        self.max_depth = max_depth;
+        self.n_samples = n_samples;
+    }
+
+    fn alpha(&self, roughness: &Arc<dyn Texture<Float>>, isect: &SurfaceInteraction) -> Float {
+        let rough = roughness.evaluate(isect).max(0.0);
+        if self.remap_roughness && rough > 0.0 { roughness_to_alpha(rough) } else { rough }
+    }
+}
+
+impl Material for CoatedConductorMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+
+        let interface_alpha = self.alpha(&self.interface_roughness, isect);
+        let top = LayerInterface::init_dielectric(self.interface_eta, interface_alpha, interface_alpha, mode);
+
+        // the conductor sits in the coat, so its indices are relative to it
+        let eta = self.conductor_eta.evaluate(isect) / self.interface_eta;
+        let k = self.k.evaluate(isect) / self.interface_eta;
+        let fresnel = Arc::from(FresnelConductor::init(&Spectrum::new(1.0, 1.0, 1.0), &eta, &k));
+        let conductor_alpha = self.alpha(&self.conductor_roughness, isect);
+        let conductor: Arc<dyn BxDF> = if conductor_alpha == 0.0 {
+            Arc::from(SpecularReflection::init(&Spectrum::new(1.0, 1.0, 1.0), fresnel))
+        } else {
+            let distribution = TrowbridgeReitzDistribution::init(conductor_alpha, conductor_alpha, true);
+            Arc::from(MicrofacetReflection::init(&Spectrum::new(1.0, 1.0, 1.0), Arc::from(distribution), fresnel))
+        };
+        let bottom = LayerInterface::init_reciprocal(conductor);
+
+        let albedo = self.albedo.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+        let thickness = self.thickness.evaluate(isect);
+        bsdf.add(Arc::from(LayeredBxDF::init(top, bottom, thickness, &albedo, self.g, self.max_depth, self.n_samples, true)));
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
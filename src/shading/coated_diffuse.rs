@@ -0,0 +1,141 @@
+use crate::common::*;
+
+// average Fresnel reflectance of diffuse light arriving from outside a boundary with relative
+// index eta, 2 * integral of F(cos) cos over cos in [0, 1]
+fn diffuse_fresnel_reflectance(eta: Float) -> Float {
+    const N_STEPS: usize = 64;
+
+    let mut sum = 0.0;
+    for i in 0..N_STEPS {
+        let cos_theta = (i as Float + 0.5) / N_STEPS as Float;
+        sum += fr_dielectric(cos_theta, 1.0, eta) * cos_theta;
+    }
+
+    2.0 * sum / N_STEPS as Float
+}
+
+// Diffuse base seen through a clear coat. Light entering the coat is absorbed on its way to the
+// base and back, and light reflected inside the coat bounces back onto the base. The specular
+// reflection off the coat itself is a separate BxDF
+#[derive(Debug, Clone)]
+pub struct CoatedDiffuseReflection {
+    r: Spectrum,
+    eta: Float,
+    // transmittance of the coat for a path of unit length
+    coat_color: Spectrum,
+    thickness: Float,
+    // diffuse reflectance of the inside of the coat
+    fdr_internal: Float
+}
+
+impl CoatedDiffuseReflection {
+    pub fn init(r: &Spectrum, eta: Float, coat_color: &Spectrum, thickness: Float) -> Self {
+        // what is not transmitted out is reflected back, diffuse light fills the larger inside cone
+        let fdr_external = diffuse_fresnel_reflectance(eta);
+        let fdr_internal = 1.0 - (1.0 - fdr_external) / (eta * eta);
+
+        Self {
+            r: *r,
+            eta,
+            coat_color: *coat_color,
+            thickness,
+            fdr_internal
+        }
+    }
+
+    // attenuation along a path through the coat, in units of its thickness
+    fn coat_transmittance(&self, path_length: Float) -> Spectrum {
+        self.coat_color.map(|c| c.max(0.0).powf(self.thickness * path_length))
+    }
+
+    // 1 / cos of the refracted angle inside the coat
+    fn inv_cos_refracted(&self, cos_theta: Float) -> Float {
+        let sin2_theta_t = (1.0 - cos_theta * cos_theta).max(0.0) / (self.eta * self.eta);
+
+        1.0 / (1.0 - sin2_theta_t).max(1e-6).sqrt()
+    }
+}
+
+impl BxDF for CoatedDiffuseReflection {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        if !same_hemisphere(wo, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let cos_o = abs_cos_theta(wo);
+        let cos_i = abs_cos_theta(wi);
+        let t_o = 1.0 - fr_dielectric(cos_o, 1.0, self.eta);
+        let t_i = 1.0 - fr_dielectric(cos_i, 1.0, self.eta);
+
+        let a = self.coat_transmittance(self.inv_cos_refracted(cos_o) + self.inv_cos_refracted(cos_i));
+        // diffuse light crosses the coat along 1 / cos, which averages 2, once down and once up
+        let a_internal = self.coat_transmittance(4.0);
+
+        let inner = self.r.component_mul(&a_internal) * self.fdr_internal;
+        let multiple_bounces = Spectrum::new(1.0 / (1.0 - inner.x), 1.0 / (1.0 - inner.y), 1.0 / (1.0 - inner.z));
+
+        self.r.component_mul(&a).component_mul(&multiple_bounces) * (t_o * t_i / (self.eta * self.eta * PI))
+    }
+}
+
+// Clear coat over a diffuse base, like lacquered wood or car paint without flakes
+#[derive(Debug, Clone)]
+pub struct CoatedDiffuseMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    // absorption of the coat, as transmittance through a unit of thickness
+    coat_color: Arc<dyn Texture<Spectrum>>,
+    thickness: Arc<dyn Texture<Float>>,
+    eta: Float,
+    remap_roughness: bool
+}
+
+impl CoatedDiffuseMaterial {
+    pub fn init(kd: Arc<dyn Texture<Spectrum>>, roughness: Arc<dyn Texture<Float>>, coat_color: Arc<dyn Texture<Spectrum>>,
+        thickness: Arc<dyn Texture<Float>>, eta: Float, remap_roughness: bool) -> Self {
+        Self {
+            kd,
+            roughness,
+            coat_color,
+            thickness,
+            eta,
+            remap_roughness
+        }
+    }
+
+    pub fn init_constant(kd: &Spectrum, roughness: Float, coat_color: &Spectrum, thickness: Float, eta: Float) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(*kd)), Arc::from(ConstantTexture::init(roughness)),
+            Arc::from(ConstantTexture::init(*coat_color)), Arc::from(ConstantTexture::init(thickness)), eta, true)
+    }
+}
+
+impl Material for CoatedDiffuseMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+
+        let kd = self.kd.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+        let coat_color = self.coat_color.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+        let thickness = self.thickness.evaluate(isect).max(0.0);
+        if kd != Spectrum::new(0.0, 0.0, 0.0) {
+            bsdf.add(Arc::from(CoatedDiffuseReflection::init(&kd, self.eta, &coat_color, thickness)));
+        }
+
+        let fresnel = Arc::from(FresnelDielectric::init(1.0, self.eta));
+        let mut rough = self.roughness.evaluate(isect);
+        if rough == 0.0 {
+            bsdf.add(Arc::from(SpecularReflection::init(&Spectrum::new(1.0, 1.0, 1.0), fresnel)));
+        } else {
+            if self.remap_roughness {
+                rough = roughness_to_alpha(rough);
+            }
+            let distribution = TrowbridgeReitzDistribution::init(rough, rough, true);
+            bsdf.add(Arc::from(MicrofacetReflection::init(&Spectrum::new(1.0, 1.0, 1.0), Arc::from(distribution), fresnel)));
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::common::*;
+
+// A dielectric boundary as a single BxDF covering both reflection and transmission, smooth when
+// there is no distribution. Layered BxDFs need each interface to be one BxDF
+#[derive(Debug, Clone)]
+pub struct DielectricBxDF {
+    // index of the inside (-z) over the outside (+z)
+    eta: Float,
+    distribution: Option<Arc<dyn MicrofacetDistribution>>,
+    mode: TransportMode
+}
+
+impl DielectricBxDF {
+    pub fn init(eta: Float, distribution: Option<Arc<dyn MicrofacetDistribution>>, mode: TransportMode) -> Self {
+        Self {
+            eta,
+            distribution,
+            mode
+        }
+    }
+
+    // probability of sampling reflection. The rough case can't know the microfacet Fresnel term
+    // before sampling wh, so it uses the macro surface one and keeps both lobes reachable
+    fn reflect_probability(&self, wo: &Vector3) -> Float {
+        let f = fr_dielectric(cos_theta(wo), 1.0, self.eta);
+        if self.distribution.is_some() { f.clamp(0.05, 0.95) } else { f }
+    }
+
+    // relative index of refraction for light leaving along wo
+    fn eta_prime(&self, wo: &Vector3) -> Float {
+        if cos_theta(wo) > 0.0 { self.eta } else { 1.0 / self.eta }
+    }
+
+    // generalized half vector, facing +z. None for configurations no microfacet can produce
+    fn half_vector(&self, wo: &Vector3, wi: &Vector3) -> Option<Vector3> {
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        if cos_theta_o == 0.0 || cos_theta_i == 0.0 {
+            return None;
+        }
+
+        let etap = if cos_theta_o * cos_theta_i > 0.0 { 1.0 } else { self.eta_prime(wo) };
+        let wm = wi * etap + wo;
+        if wm.norm_squared() == 0.0 {
+            return None;
+        }
+        let wm = face_forward(&wm.normalize(), &Vector3::new(0.0, 0.0, 1.0));
+
+        // backfacing microfacets
+        if wm.dot(wi) * cos_theta_i < 0.0 || wm.dot(wo) * cos_theta_o < 0.0 {
+            return None;
+        }
+
+        Some(wm)
+    }
+}
+
+impl BxDF for DielectricBxDF {
+    fn bxdf_type(&self) -> u32 {
+        let lobe = if self.distribution.is_some() { BxDFType::Glossy } else { BxDFType::Specular };
+
+        BxDFType::Reflection as u32 | BxDFType::Transmission as u32 | lobe as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+        let wm = match self.half_vector(wo, wi) {
+            Some(wm) => wm,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        let f = fr_dielectric(wo.dot(&wm), 1.0, self.eta);
+        if same_hemisphere(wo, wi) {
+            let r = distribution.d(&wm) * distribution.g(wo, wi) * f / (4.0 * cos_theta_i * cos_theta_o).abs();
+            return Spectrum::new(r, r, r);
+        }
+
+        let etap = self.eta_prime(wo);
+        let denom = (wi.dot(&wm) + wo.dot(&wm) / etap).powi(2) * cos_theta_i * cos_theta_o;
+        let mut t = distribution.d(&wm) * (1.0 - f) * distribution.g(wo, wi) * (wi.dot(&wm) * wo.dot(&wm) / denom).abs();
+        if self.mode == TransportMode::Radiance {
+            t /= etap * etap;
+        }
+
+        Spectrum::new(t, t, t)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let pr = self.reflect_probability(wo);
+        let sample_reflection = u.x < pr;
+        let u_remapped = if sample_reflection {
+            Point2::new((u.x / pr).min(ONE_MINUS_EPSILON), u.y)
+        } else {
+            Point2::new(((u.x - pr) / (1.0 - pr)).min(ONE_MINUS_EPSILON), u.y)
+        };
+
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => {
+                // the same as FresnelSpecular, with the choice made by the Fresnel term itself
+                let etap = self.eta_prime(wo);
+                if sample_reflection {
+                    *wi = Vector3::new(-wo.x, -wo.y, wo.z);
+                    *sampled_type = BxDFType::Reflection as u32 | BxDFType::Specular as u32;
+                    *pdf = pr;
+                    return Spectrum::new(pr, pr, pr) / abs_cos_theta(wi);
+                }
+
+                if !refract(wo, &face_forward(&Vector3::new(0.0, 0.0, 1.0), wo), 1.0 / etap, wi) {
+                    return Spectrum::new(0.0, 0.0, 0.0);
+                }
+                let mut t = 1.0 - pr;
+                if self.mode == TransportMode::Radiance {
+                    t /= etap * etap;
+                }
+                *sampled_type = BxDFType::Transmission as u32 | BxDFType::Specular as u32;
+                *pdf = 1.0 - pr;
+                return Spectrum::new(t, t, t) / abs_cos_theta(wi);
+            }
+        };
+
+        let wh = distribution.sample_wh(wo, &u_remapped);
+        if wo.dot(&wh) <= 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        if sample_reflection {
+            *wi = reflect(wo, &wh);
+            if !same_hemisphere(wo, wi) {
+                return Spectrum::new(0.0, 0.0, 0.0);
+            }
+            *sampled_type = BxDFType::Reflection as u32 | BxDFType::Glossy as u32;
+        } else {
+            if !refract(wo, &wh, 1.0 / self.eta_prime(wo), wi) || same_hemisphere(wo, wi) || wi.z == 0.0 {
+                return Spectrum::new(0.0, 0.0, 0.0);
+            }
+            *sampled_type = BxDFType::Transmission as u32 | BxDFType::Glossy as u32;
+        }
+        *pdf = self.pdf(wo, wi);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        let distribution = match &self.distribution {
+            Some(distribution) => distribution,
+            None => return 0.0
+        };
+        let wm = match self.half_vector(wo, wi) {
+            Some(wm) => wm,
+            None => return 0.0
+        };
+
+        let pr = self.reflect_probability(wo);
+        if same_hemisphere(wo, wi) {
+            return distribution.pdf(wo, &wm) / (4.0 * wo.dot(&wm).abs()) * pr;
+        }
+
+        let etap = self.eta_prime(wo);
+        let denom = (wi.dot(&wm) + wo.dot(&wm) / etap).powi(2);
+        let dwm_dwi = wi.dot(&wm).abs() / denom;
+
+        distribution.pdf(wo, &wm) * dwm_dwi * (1.0 - pr)
+    }
+}
@@ -0,0 +1,691 @@
+use std::sync::OnceLock;
+
+use crate::common::*;
+use crate::shading::energy_compensation::lerp_index;
+
+const ALBEDO_ROUGHNESS: usize = 16;
+const ALBEDO_MU: usize = 32;
+const ALBEDO_ETA: usize = 8;
+// the largest index the specular parameter gives, r0 = 0.08
+const ALBEDO_ETA_MAX: Float = 1.8;
+
+fn schlick_weight(cos_theta: Float) -> Float {
+    let m = (1.0 - cos_theta).clamp(0.0, 1.0);
+
+    (m * m) * (m * m) * m
+}
+
+fn fr_schlick(r0: &Spectrum, cos_theta: Float) -> Spectrum {
+    let w = schlick_weight(cos_theta);
+
+    r0 * (1.0 - w) + Spectrum::new(w, w, w)
+}
+
+fn schlick_r0_from_eta(eta: Float) -> Float {
+    ((eta - 1.0) * (eta - 1.0)) / ((eta + 1.0) * (eta + 1.0))
+}
+
+fn lerp_spectrum(t: Float, a: &Spectrum, b: &Spectrum) -> Spectrum {
+    a * (1.0 - t) + b * t
+}
+
+// half vector of a reflection, None when wo and wi cancel
+fn half_vector(wo: &Vector3, wi: &Vector3) -> Option<Vector3> {
+    let wh = wi + wo;
+    if wh == Vector3::new(0.0, 0.0, 0.0) {
+        return None;
+    }
+
+    Some(wh.normalize())
+}
+
+// Burley's diffuse, with retro-reflection split out into DisneyRetro
+#[derive(Debug, Clone)]
+pub struct DisneyDiffuse {
+    r: Spectrum
+}
+
+impl DisneyDiffuse {
+    pub fn init(r: &Spectrum) -> Self {
+        Self {
+            r: *r
+        }
+    }
+}
+
+impl BxDF for DisneyDiffuse {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let fo = schlick_weight(abs_cos_theta(wo));
+        let fi = schlick_weight(abs_cos_theta(wi));
+
+        self.r * (1.0 / PI) * (1.0 - fo / 2.0) * (1.0 - fi / 2.0)
+    }
+}
+
+// Hanrahan-Krueger like approximation of subsurface scattering for thin surfaces
+#[derive(Debug, Clone)]
+pub struct DisneyFakeSS {
+    r: Spectrum,
+    roughness: Float
+}
+
+impl DisneyFakeSS {
+    pub fn init(r: &Spectrum, roughness: Float) -> Self {
+        Self {
+            r: *r,
+            roughness
+        }
+    }
+}
+
+impl BxDF for DisneyFakeSS {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let wh = match half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+        let cos_theta_d = wi.dot(&wh);
+
+        let fss90 = cos_theta_d * cos_theta_d * self.roughness;
+        let fo = schlick_weight(abs_cos_theta(wo));
+        let fi = schlick_weight(abs_cos_theta(wi));
+        let fss = lerp(fo, 1.0, fss90) * lerp(fi, 1.0, fss90);
+        let ss = 1.25 * (fss * (1.0 / (abs_cos_theta(wo) + abs_cos_theta(wi)) - 0.5) + 0.5);
+
+        self.r * (1.0 / PI) * ss
+    }
+}
+
+// retro-reflection of rough diffuse surfaces
+#[derive(Debug, Clone)]
+pub struct DisneyRetro {
+    r: Spectrum,
+    roughness: Float
+}
+
+impl DisneyRetro {
+    pub fn init(r: &Spectrum, roughness: Float) -> Self {
+        Self {
+            r: *r,
+            roughness
+        }
+    }
+}
+
+impl BxDF for DisneyRetro {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let wh = match half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+        let cos_theta_d = wi.dot(&wh);
+
+        let fo = schlick_weight(abs_cos_theta(wo));
+        let fi = schlick_weight(abs_cos_theta(wi));
+        let rr = 2.0 * self.roughness * cos_theta_d * cos_theta_d;
+
+        self.r * (1.0 / PI) * rr * (fo + fi + fo * fi * (rr - 1.0))
+    }
+}
+
+// grazing angle reflection of cloth like surfaces
+#[derive(Debug, Clone)]
+pub struct DisneySheen {
+    r: Spectrum
+}
+
+impl DisneySheen {
+    pub fn init(r: &Spectrum) -> Self {
+        Self {
+            r: *r
+        }
+    }
+}
+
+impl BxDF for DisneySheen {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let wh = match half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+
+        self.r * schlick_weight(wi.dot(&wh))
+    }
+}
+
+// Generalized Trowbridge-Reitz with gamma 1, the long tailed clearcoat distribution
+fn gtr1(cos_theta: Float, alpha: Float) -> Float {
+    let alpha2 = alpha * alpha;
+
+    (alpha2 - 1.0) / (PI * alpha2.ln() * (1.0 + (alpha2 - 1.0) * cos_theta * cos_theta))
+}
+
+fn smith_g_ggx(cos_theta: Float, alpha: Float) -> Float {
+    let alpha2 = alpha * alpha;
+    let cos_theta2 = cos_theta * cos_theta;
+
+    1.0 / (cos_theta + (alpha2 + cos_theta2 - alpha2 * cos_theta2).sqrt())
+}
+
+// a second, colourless specular lobe with a fixed index of refraction of 1.5
+#[derive(Debug, Clone)]
+pub struct DisneyClearcoat {
+    weight: Float,
+    gloss: Float
+}
+
+impl DisneyClearcoat {
+    pub fn init(weight: Float, gloss: Float) -> Self {
+        Self {
+            weight,
+            gloss
+        }
+    }
+}
+
+impl BxDF for DisneyClearcoat {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let wh = match half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return Spectrum::new(0.0, 0.0, 0.0)
+        };
+
+        let dr = gtr1(abs_cos_theta(&wh), self.gloss);
+        let fr = fr_schlick(&Spectrum::new(0.04, 0.04, 0.04), wo.dot(&wh)).x;
+        let gr = smith_g_ggx(abs_cos_theta(wo), 0.25) * smith_g_ggx(abs_cos_theta(wi), 0.25);
+
+        let v = self.weight * gr * fr * dr / 4.0;
+
+        Spectrum::new(v, v, v)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let alpha2 = self.gloss * self.gloss;
+        let cos_theta = ((1.0 - alpha2.powf(1.0 - u.x)) / (1.0 - alpha2)).max(0.0).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u.y;
+        let mut wh = spherical_direction(sin_theta, cos_theta, phi);
+        if !same_hemisphere(wo, &wh) {
+            wh = -wh;
+        }
+
+        *wi = reflect(wo, &wh);
+        if !same_hemisphere(wo, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        *pdf = self.pdf(wo, wi);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+
+        let wh = match half_vector(wo, wi) {
+            Some(wh) => wh,
+            None => return 0.0
+        };
+
+        gtr1(abs_cos_theta(&wh), self.gloss) * abs_cos_theta(&wh) / (4.0 * wo.dot(&wh))
+    }
+}
+
+// Blends the dielectric Fresnel term with a Schlick term for metals
+#[derive(Debug, Clone)]
+pub struct DisneyFresnel {
+    r0: Spectrum,
+    metallic: Float,
+    eta: Float
+}
+
+impl DisneyFresnel {
+    pub fn init(r0: &Spectrum, metallic: Float, eta: Float) -> Self {
+        Self {
+            r0: *r0,
+            metallic,
+            eta
+        }
+    }
+}
+
+impl Fresnel for DisneyFresnel {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        let dielectric = fr_dielectric(cos_i, 1.0, self.eta);
+
+        lerp_spectrum(self.metallic, &Spectrum::new(dielectric, dielectric, dielectric), &fr_schlick(&self.r0, cos_i))
+    }
+}
+
+// Trowbridge-Reitz with separable masking-shadowing, as the Disney model is fitted with
+#[derive(Debug, Clone)]
+pub struct DisneyMicrofacetDistribution {
+    distribution: TrowbridgeReitzDistribution
+}
+
+impl DisneyMicrofacetDistribution {
+    pub fn init(alpha_x: Float, alpha_y: Float) -> Self {
+        Self {
+            distribution: TrowbridgeReitzDistribution::init(alpha_x, alpha_y, true)
+        }
+    }
+}
+
+impl MicrofacetDistribution for DisneyMicrofacetDistribution {
+    fn d(&self, wh: &Vector3) -> Float { self.distribution.d(wh) }
+    fn lambda(&self, w: &Vector3) -> Float { self.distribution.lambda(w) }
+    fn sample_wh(&self, wo: &Vector3, u: &Point2) -> Vector3 { self.distribution.sample_wh(wo, u) }
+    fn sample_visible_area(&self) -> bool { self.distribution.sample_visible_area() }
+
+    fn g(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        self.g1(wo) * self.g1(wi)
+    }
+}
+
+// Directional albedo of the white lobes, tabulated on first use, so the diffuse lobes can be
+// scaled into what the others leave. By roughness then cosine: fake subsurface, retro-reflection,
+// and the specular lobe with a Fresnel term of 1 and of the Schlick weight alone, which a metal's
+// reflectance at normal incidence blends. The dielectric specular lobe is by roughness, index and
+// cosine, the clearcoat by gloss and cosine and the sheen by cosine. DisneyDiffuse has a closed
+// form, (1 - fo / 2) (1 - 1 / 42) as the cosine weighted mean of fi is 1 / 21
+#[derive(Debug)]
+struct DisneyAlbedo {
+    fake_ss: Vec<Float>,
+    retro: Vec<Float>,
+    specular_one: Vec<Float>,
+    specular_schlick: Vec<Float>,
+    specular_dielectric: Vec<Float>,
+    clearcoat: Vec<Float>,
+    sheen: Vec<Float>
+}
+
+impl DisneyAlbedo {
+    fn compute() -> Self {
+        let white = Spectrum::new(1.0, 1.0, 1.0);
+        let n = 16;
+        let samples: Vec<Point2> = (0..n * n).map(|i| Point2::new(((i / n) as Float + 0.5) / n as Float, ((i % n) as Float + 0.5) / n as Float)).collect();
+        let wo = |m: usize| {
+            let mu = (m as Float / (ALBEDO_MU - 1) as Float).powi(2).max(1e-3);
+            Vector3::new((1.0 - mu * mu).sqrt(), 0.0, mu)
+        };
+        // by each lobe's own sampling, unlike directional_albedo not clamped to 1 as some exceed it
+        let albedo = |bxdf: &dyn BxDF, m: usize| bxdf.rho(&wo(m), &samples).x;
+        // the diffuse lobes peak where wi grazes, which cosine sampling rarely visits. A midpoint
+        // rule in t = sqrt(cos_i) and phi over the half the lobes are symmetric in converges fast
+        let quadrature = |bxdf: &dyn BxDF, m: usize| {
+            let wo = wo(m);
+            let mut sum = 0.0;
+            for t in 0..n {
+                let t = (t as Float + 0.5) / n as Float;
+                let cos_i = t * t;
+                let sin_i = (1.0 - cos_i * cos_i).sqrt();
+                for p in 0..n {
+                    let phi = (p as Float + 0.5) / n as Float * PI;
+                    let wi = Vector3::new(sin_i * phi.cos(), sin_i * phi.sin(), cos_i);
+                    sum += bxdf.f(&wo, &wi).x * cos_i * 2.0 * t;
+                }
+            }
+            2.0 * PI * sum / (n * n) as Float
+        };
+        let specular = |roughness: Float, fresnel: DisneyFresnel| {
+            let alpha = (roughness * roughness).max(0.001);
+            MicrofacetReflection::init(&white, Arc::from(DisneyMicrofacetDistribution::init(alpha, alpha)), Arc::from(fresnel))
+        };
+
+        let table_size = ALBEDO_ROUGHNESS * ALBEDO_MU;
+        let (mut fake_ss, mut retro) = (vec![0.0; table_size], vec![0.0; table_size]);
+        let (mut specular_one, mut specular_schlick) = (vec![0.0; table_size], vec![0.0; table_size]);
+        let mut specular_dielectric = vec![0.0; ALBEDO_ETA * table_size];
+        let mut clearcoat = vec![0.0; table_size];
+        for r in 0..ALBEDO_ROUGHNESS {
+            let roughness = r as Float / (ALBEDO_ROUGHNESS - 1) as Float;
+            let one = specular(roughness, DisneyFresnel::init(&white, 1.0, 1.0));
+            let schlick = specular(roughness, DisneyFresnel::init(&Spectrum::new(0.0, 0.0, 0.0), 1.0, 1.0));
+            let coat = DisneyClearcoat::init(1.0, lerp(roughness, 0.1, 0.001));
+            for m in 0..ALBEDO_MU {
+                let i = r * ALBEDO_MU + m;
+                fake_ss[i] = quadrature(&DisneyFakeSS::init(&white, roughness), m);
+                retro[i] = quadrature(&DisneyRetro::init(&white, roughness), m);
+                specular_one[i] = albedo(&one, m);
+                specular_schlick[i] = albedo(&schlick, m);
+                clearcoat[i] = albedo(&coat, m);
+            }
+            for k in 0..ALBEDO_ETA {
+                let dielectric = specular(roughness, DisneyFresnel::init(&white, 0.0, Self::eta_at(k)));
+                for m in 0..ALBEDO_MU {
+                    specular_dielectric[(k * ALBEDO_ROUGHNESS + r) * ALBEDO_MU + m] = albedo(&dielectric, m);
+                }
+            }
+        }
+        let sheen = (0..ALBEDO_MU).map(|m| quadrature(&DisneySheen::init(&white), m)).collect();
+
+        Self { fake_ss, retro, specular_one, specular_schlick, specular_dielectric, clearcoat, sheen }
+    }
+
+    fn eta_at(k: usize) -> Float {
+        1.0 + (ALBEDO_ETA_MAX - 1.0) * k as Float / (ALBEDO_ETA - 1) as Float
+    }
+
+    // nodes are evenly spaced in sqrt(mu), Fresnel and masking change fastest at grazing angles
+    fn lookup(table: &[Float], x: Float, mu: Float) -> Float {
+        let (r, tr) = lerp_index(x, ALBEDO_ROUGHNESS);
+        let (m, tm) = lerp_index(mu.sqrt(), ALBEDO_MU);
+        let at = |r: usize, m: usize| table[r * ALBEDO_MU + m];
+
+        lerp(tr, lerp(tm, at(r, m), at(r, m + 1)), lerp(tm, at(r + 1, m), at(r + 1, m + 1)))
+    }
+
+    fn fake_ss(&self, roughness: Float, mu: Float) -> Float {
+        Self::lookup(&self.fake_ss, roughness, mu)
+    }
+
+    fn retro(&self, roughness: Float, mu: Float) -> Float {
+        Self::lookup(&self.retro, roughness, mu)
+    }
+
+    // the metal's Schlick Fresnel is linear in its reflectance at normal incidence r0
+    fn specular_metal(&self, roughness: Float, r0: Float, mu: Float) -> Float {
+        lerp(r0, Self::lookup(&self.specular_schlick, roughness, mu), Self::lookup(&self.specular_one, roughness, mu))
+    }
+
+    fn specular_dielectric(&self, roughness: Float, eta: Float, mu: Float) -> Float {
+        let (k, tk) = lerp_index((eta - 1.0) / (ALBEDO_ETA_MAX - 1.0), ALBEDO_ETA);
+        let table_size = ALBEDO_ROUGHNESS * ALBEDO_MU;
+        let slice = |k: usize| Self::lookup(&self.specular_dielectric[k * table_size..(k + 1) * table_size], roughness, mu);
+
+        lerp(tk, slice(k), slice(k + 1))
+    }
+
+    // gloss in [0, 1] as the clearcoat_gloss parameter
+    fn clearcoat(&self, gloss: Float, mu: Float) -> Float {
+        Self::lookup(&self.clearcoat, gloss, mu)
+    }
+
+    fn sheen(&self, mu: Float) -> Float {
+        let (m, tm) = lerp_index(mu.sqrt(), ALBEDO_MU);
+
+        lerp(tm, self.sheen[m], self.sheen[m + 1])
+    }
+}
+
+fn disney_albedo() -> &'static DisneyAlbedo {
+    static TABLE: OnceLock<DisneyAlbedo> = OnceLock::new();
+    TABLE.get_or_init(DisneyAlbedo::compute)
+}
+
+// The principled parameter set. All parameters are in [0, 1] except eta. Thin surfaces have no
+// inside, which enables flatness and diffuse transmission
+#[derive(Debug, Clone)]
+pub struct DisneyMaterial {
+    color: Arc<dyn Texture<Spectrum>>,
+    metallic: Arc<dyn Texture<Float>>,
+    roughness: Arc<dyn Texture<Float>>,
+    anisotropic: Arc<dyn Texture<Float>>,
+    // dielectric reflectance at normal incidence, 0.5 is an eta of 1.5
+    specular: Arc<dyn Texture<Float>>,
+    specular_tint: Arc<dyn Texture<Float>>,
+    sheen: Arc<dyn Texture<Float>>,
+    sheen_tint: Arc<dyn Texture<Float>>,
+    clearcoat: Arc<dyn Texture<Float>>,
+    clearcoat_gloss: Arc<dyn Texture<Float>>,
+    spec_trans: Arc<dyn Texture<Float>>,
+    flatness: Arc<dyn Texture<Float>>,
+    diff_trans: Arc<dyn Texture<Float>>,
+    thin: bool
+}
+
+impl Default for DisneyMaterial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DisneyMaterial {
+    // grey dielectric, set the rest with the setters
+    pub fn new() -> Self {
+        let zero: Arc<dyn Texture<Float>> = Arc::from(ConstantTexture::init(0.0));
+
+        Self {
+            color: Arc::from(ConstantTexture::init(Spectrum::new(0.5, 0.5, 0.5))),
+            metallic: zero.clone(),
+            roughness: Arc::from(ConstantTexture::init(0.5)),
+            anisotropic: zero.clone(),
+            specular: Arc::from(ConstantTexture::init(0.5)),
+            specular_tint: zero.clone(),
+            sheen: zero.clone(),
+            sheen_tint: Arc::from(ConstantTexture::init(0.5)),
+            clearcoat: zero.clone(),
+            clearcoat_gloss: Arc::from(ConstantTexture::init(1.0)),
+            spec_trans: zero.clone(),
+            flatness: zero.clone(),
+            diff_trans: zero,
+            thin: false
+        }
+    }
+
+    pub fn set_color(&mut self, t: Arc<dyn Texture<Spectrum>>) { self.color = t; }
+    pub fn set_metallic(&mut self, t: Arc<dyn Texture<Float>>) { self.metallic = t; }
+    pub fn set_roughness(&mut self, t: Arc<dyn Texture<Float>>) { self.roughness = t; }
+    pub fn set_anisotropic(&mut self, t: Arc<dyn Texture<Float>>) { self.anisotropic = t; }
+    pub fn set_specular(&mut self, t: Arc<dyn Texture<Float>>) { self.specular = t; }
+    pub fn set_specular_tint(&mut self, t: Arc<dyn Texture<Float>>) { self.specular_tint = t; }
+    pub fn set_sheen(&mut self, t: Arc<dyn Texture<Float>>) { self.sheen = t; }
+    pub fn set_sheen_tint(&mut self, t: Arc<dyn Texture<Float>>) { self.sheen_tint = t; }
+    pub fn set_clearcoat(&mut self, t: Arc<dyn Texture<Float>>) { self.clearcoat = t; }
+    pub fn set_clearcoat_gloss(&mut self, t: Arc<dyn Texture<Float>>) { self.clearcoat_gloss = t; }
+    pub fn set_spec_trans(&mut self, t: Arc<dyn Texture<Float>>) { self.spec_trans = t; }
+    pub fn set_flatness(&mut self, t: Arc<dyn Texture<Float>>) { self.flatness = t; }
+    pub fn set_diff_trans(&mut self, t: Arc<dyn Texture<Float>>) { self.diff_trans = t; }
+    pub fn set_thin(&mut self, thin: bool) { self.thin = thin; }
+}
+
+impl Material for DisneyMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, _allow_multiple_lobes: bool) {
+        let c = self.color.evaluate(isect).map(|v| v.clamp(0.0, 1.0));
+        let metallic = self.metallic.evaluate(isect).clamp(0.0, 1.0);
+        let rough = self.roughness.evaluate(isect).clamp(0.0, 1.0);
+        let spec_trans = self.spec_trans.evaluate(isect).clamp(0.0, 1.0);
+        // 0 is no diffuse transmission, 1 is all of it
+        let diff_trans = self.diff_trans.evaluate(isect).clamp(0.0, 1.0) / 2.0;
+
+        let r0 = (0.08 * self.specular.evaluate(isect).clamp(0.0, 1.0)).min(0.99);
+        let eta = (1.0 + r0.sqrt()) / (1.0 - r0.sqrt());
+        let mut bsdf = BSDF::init(isect, eta);
+
+        // hue and saturation of the base colour
+        let lum = rgb_y(&c);
+        let c_tint = if lum > 0.0 { c / lum } else { Spectrum::new(1.0, 1.0, 1.0) };
+        let white = Spectrum::new(1.0, 1.0, 1.0);
+
+        let sheen_weight = self.sheen.evaluate(isect).clamp(0.0, 1.0);
+        let c_sheen = if sheen_weight > 0.0 {
+            lerp_spectrum(self.sheen_tint.evaluate(isect).clamp(0.0, 1.0), &white, &c_tint)
+        } else {
+            Spectrum::new(0.0, 0.0, 0.0)
+        };
+
+        // whatever the specular lobes reflect at normal incidence is not available to the
+        // diffuse lobes
+        let spec_tint = self.specular_tint.evaluate(isect).clamp(0.0, 1.0);
+        let c_spec0 = lerp_spectrum(metallic, &(lerp_spectrum(spec_tint, &white, &c_tint) * schlick_r0_from_eta(eta)), &c);
+        let diffuse_weight = (1.0 - metallic) * (1.0 - spec_trans) * (1.0 - rgb_y(&c_spec0));
+        let clearcoat = self.clearcoat.evaluate(isect).clamp(0.0, 1.0);
+        let flat = if self.thin { self.flatness.evaluate(isect).clamp(0.0, 1.0) } else { 0.0 };
+
+        // Burley's retro-reflection and the thin surface approximation reflect more than they
+        // receive at grazing angles. The albedo of every lobe for this wo is looked up and the
+        // diffuse ones are scaled into whatever the others leave, so the BSDF never gains energy.
+        // The transmission gets at most what the dielectric specular lobe doesn't reflect
+        let cos_o = isect.shading.n.dot(&isect.interaction.wo).abs();
+        let fo = schlick_weight(cos_o);
+        let albedo = disney_albedo();
+        let clearcoat_gloss = self.clearcoat_gloss.evaluate(isect).clamp(0.0, 1.0);
+        let specular_dielectric = albedo.specular_dielectric(rough, eta, cos_o);
+        let other_albedo = lerp(metallic, specular_dielectric, albedo.specular_metal(rough, c_spec0.max(), cos_o)) +
+            clearcoat * albedo.clearcoat(clearcoat_gloss, cos_o) +
+            (1.0 - metallic) * spec_trans * (1.0 - specular_dielectric) +
+            sheen_weight * diffuse_weight * c_sheen.max() * albedo.sheen(cos_o);
+        let diffuse = (1.0 - fo / 2.0) * (1.0 - 1.0 / 42.0);
+        let fake_ss = albedo.fake_ss(rough, cos_o);
+        let retro = if rough > 0.0 { albedo.retro(rough, cos_o) } else { 0.0 };
+        let diffuse_albedo = diffuse_weight * ((1.0 - diff_trans) * ((1.0 - flat) * diffuse + flat * fake_ss) + retro + diff_trans);
+        let energy_scale = if diffuse_albedo > 0.0 { ((1.0 - other_albedo).max(0.0) / diffuse_albedo).min(1.0) } else { 1.0 };
+
+        if diffuse_weight > 0.0 && lum > 0.0 {
+            let diffuse_sample_weight = diffuse_weight * lum;
+            let c_diffuse = c * diffuse_weight * energy_scale;
+
+            if self.thin {
+                // flatness blends towards a subsurface look
+                if flat < 1.0 {
+                    bsdf.add_weighted(Arc::from(DisneyDiffuse::init(&(c_diffuse * (1.0 - flat) * (1.0 - diff_trans)))), diffuse_sample_weight);
+                }
+                if flat > 0.0 {
+                    bsdf.add_weighted(Arc::from(DisneyFakeSS::init(&(c_diffuse * flat * (1.0 - diff_trans)), rough)), diffuse_sample_weight);
+                }
+            } else {
+                bsdf.add_weighted(Arc::from(DisneyDiffuse::init(&c_diffuse)), diffuse_sample_weight);
+            }
+
+            if rough > 0.0 {
+                bsdf.add_weighted(Arc::from(DisneyRetro::init(&c_diffuse, rough)), diffuse_sample_weight * rough);
+            }
+            if sheen_weight > 0.0 {
+                bsdf.add_weighted(Arc::from(DisneySheen::init(&(c_sheen * diffuse_weight * sheen_weight))), 0.25 * diffuse_weight * sheen_weight);
+            }
+        }
+
+        // anisotropy stretches the highlight along dpdu
+        let aspect = (1.0 - self.anisotropic.evaluate(isect).clamp(0.0, 1.0) * 0.9).sqrt();
+        let ax = (rough * rough / aspect).max(0.001);
+        let ay = (rough * rough * aspect).max(0.001);
+        let distribution = DisneyMicrofacetDistribution::init(ax, ay);
+
+        let fresnel = DisneyFresnel::init(&c_spec0, metallic, eta);
+        bsdf.add_weighted(Arc::from(MicrofacetReflection::init(&white, Arc::from(distribution), Arc::from(fresnel))), rgb_y(&c_spec0).max(0.1));
+
+        if clearcoat > 0.0 {
+            let gloss = lerp(clearcoat_gloss, 0.1, 0.001);
+            bsdf.add_weighted(Arc::from(DisneyClearcoat::init(clearcoat, gloss)), 0.25 * clearcoat);
+        }
+
+        if spec_trans > 0.0 && (1.0 - metallic) > 0.0 {
+            let t = c.map(|v| v.sqrt()) * spec_trans * (1.0 - metallic);
+            let t_weight = rgb_y(&t);
+            if t_weight > 0.0 {
+                if self.thin {
+                    // a thin slab blurs more than a single rough interface
+                    let rscaled = (0.65 * eta - 0.35) * rough;
+                    let ax = (rscaled * rscaled / aspect).max(0.001);
+                    let ay = (rscaled * rscaled * aspect).max(0.001);
+                    let distribution = TrowbridgeReitzDistribution::init(ax, ay, true);
+                    bsdf.add_weighted(Arc::from(MicrofacetTransmission::init(&t, Arc::from(distribution), 1.0, eta, mode)), t_weight);
+                } else {
+                    let distribution = DisneyMicrofacetDistribution::init(ax, ay);
+                    bsdf.add_weighted(Arc::from(MicrofacetTransmission::init(&t, Arc::from(distribution), 1.0, eta, mode)), t_weight);
+                }
+            }
+        }
+
+        if self.thin && diff_trans > 0.0 && lum > 0.0 {
+            bsdf.add_weighted(Arc::from(LambertianTransmission::init(&(c * diff_trans * diffuse_weight * energy_scale))), diff_trans * diffuse_weight * lum);
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(v: Float) -> Arc<dyn Texture<Float>> {
+        Arc::from(ConstantTexture::init(v))
+    }
+
+    // reflected and transmitted fraction of the light arriving along wo, by each lobe's sampling
+    fn albedo(material: &DisneyMaterial, cos_o: Float) -> Float {
+        let wo = Vector3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        let mut isect = SurfaceInteraction::init(&Point3::new(0.0, 0.0, 0.0), &zero, &Point2::new(0.0, 0.0), &wo,
+            &Vector3::new(1.0, 0.0, 0.0), &Vector3::new(0.0, 1.0, 0.0), &zero, &zero, 0.0, None);
+        material.compute_scattering_function(&mut isect, TransportMode::Radiance, true);
+
+        let n = 32;
+        let samples: Vec<Point2> = (0..n * n).map(|i| Point2::new(((i / n) as Float + 0.5) / n as Float, ((i % n) as Float + 0.5) / n as Float)).collect();
+        isect.bsdf.unwrap().rho(&wo, &samples, BSDF_ALL).max()
+    }
+
+    #[test]
+    fn white_furnace() {
+        for thin in [false, true] {
+            for rough in [0.0, 0.5, 1.0] {
+                for flat in [0.0, 1.0] {
+                    for sheen in [0.0, 1.0] {
+                        for diff_trans in [0.0, 1.0] {
+                            for clearcoat in [0.0, 1.0] {
+                                let mut material = DisneyMaterial::new();
+                                material.set_color(Arc::from(ConstantTexture::init(Spectrum::new(1.0, 1.0, 1.0))));
+                                material.set_roughness(constant(rough));
+                                material.set_flatness(constant(flat));
+                                material.set_sheen(constant(sheen));
+                                material.set_diff_trans(constant(diff_trans));
+                                material.set_clearcoat(constant(clearcoat));
+                                material.set_thin(thin);
+                                for cos_o in [0.02, 0.3, 1.0] {
+                                    let a = albedo(&material, cos_o);
+                                    assert!(a <= 1.01, "albedo {a} with thin {thin} roughness {rough} flatness {flat} sheen {sheen} diff_trans {diff_trans} clearcoat {clearcoat} at cos {cos_o}");
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // a white metal reflects everything, and below alpha 0.01 the f32 ratio of the near delta
+    // lobe to its pdf is off by about 1%
+    #[test]
+    fn white_furnace_metal_and_glass() {
+        for rough in [0.1, 0.5, 1.0] {
+            for (metallic, spec_trans) in [(1.0, 0.0), (0.5, 0.0), (0.0, 1.0), (0.0, 0.5)] {
+                let mut material = DisneyMaterial::new();
+                material.set_color(Arc::from(ConstantTexture::init(Spectrum::new(1.0, 1.0, 1.0))));
+                material.set_roughness(constant(rough));
+                material.set_metallic(constant(metallic));
+                material.set_spec_trans(constant(spec_trans));
+                for cos_o in [0.02, 0.3, 1.0] {
+                    let a = albedo(&material, cos_o);
+                    assert!(a <= 1.01, "albedo {a} with roughness {rough} metallic {metallic} spec_trans {spec_trans} at cos {cos_o}");
+                }
+            }
+        }
+    }
+}
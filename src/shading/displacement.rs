@@ -0,0 +1,151 @@
+use crate::common::*;
+use read_image::{read_exr_image, read_png_image};
+
+// Perturbs the shading geometry at a point before a material builds its BSDF
+#[derive(Debug, Clone)]
+pub enum Displacement {
+    // height along the shading normal
+    Bump(Arc<dyn Texture<Float>>),
+    // normals in the tangent frame of dpdu and the shading normal
+    NormalMap(Arc<NormalMap>)
+}
+
+impl Displacement {
+    pub fn apply(&self, si: &mut SurfaceInteraction) {
+        match self {
+            Displacement::Bump(d) => bump(d.as_ref(), si),
+            Displacement::NormalMap(map) => normal_map(map, si)
+        }
+    }
+}
+
+// Any material with a bump or normal map, the shading geometry is perturbed before the wrapped
+// material builds its BSDF
+#[derive(Debug, Clone)]
+pub struct DisplacedMaterial {
+    material: Arc<dyn Material>,
+    displacement: Displacement
+}
+
+impl DisplacedMaterial {
+    pub fn init(material: Arc<dyn Material>, displacement: Displacement) -> Self {
+        Self {
+            material,
+            displacement
+        }
+    }
+}
+
+impl Material for DisplacedMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, allow_multiple_lobes: bool) {
+        self.displacement.apply(isect);
+
+        self.material.compute_scattering_function(isect, mode, allow_multiple_lobes);
+    }
+}
+
+// Moves the shading frame as if the surface were offset by d, with the derivatives of d taken by
+// finite differences over about a pixel footprint
+pub fn bump(d: &dyn Texture<Float>, si: &mut SurfaceInteraction) {
+    let mut si_eval = si.clone();
+
+    // shift in u
+    let mut du = 0.5 * (si.dudx.abs() + si.dudy.abs());
+    if du == 0.0 {
+        du = 0.0005;
+    }
+    si_eval.interaction.p = si.interaction.p + du * si.shading.dpdu;
+    si_eval.uv = si.uv + Vector2::new(du, 0.0);
+    si_eval.interaction.n = (si.shading.dpdu.cross(&si.shading.dpdv) + du * si.dndu).normalize();
+    let u_displace = d.evaluate(&si_eval);
+
+    // shift in v
+    let mut dv = 0.5 * (si.dvdx.abs() + si.dvdy.abs());
+    if dv == 0.0 {
+        dv = 0.0005;
+    }
+    si_eval.interaction.p = si.interaction.p + dv * si.shading.dpdv;
+    si_eval.uv = si.uv + Vector2::new(0.0, dv);
+    si_eval.interaction.n = (si.shading.dpdu.cross(&si.shading.dpdv) + dv * si.dndv).normalize();
+    let v_displace = d.evaluate(&si_eval);
+
+    let displace = d.evaluate(si);
+
+    let n = si.shading.n;
+    let dpdu = si.shading.dpdu + (u_displace - displace) / du * n + displace * si.shading.dndu;
+    let dpdv = si.shading.dpdv + (v_displace - displace) / dv * n + displace * si.shading.dndv;
+    let (dndu, dndv) = (si.shading.dndu, si.shading.dndv);
+
+    si.set_shading_geometry(&dpdu, &dpdv, &dndu, &dndv, false);
+}
+
+// Tangent space normals stored as RGB in [0, 1], read without any color decoding
+#[derive(Debug, Clone)]
+pub struct NormalMap {
+    // top row first
+    pixels: Vec<Spectrum>,
+    width: usize,
+    height: usize
+}
+
+impl NormalMap {
+    pub fn init(pixels: Vec<Spectrum>, width: usize, height: usize) -> Self {
+        assert!(pixels.len() == width * height && width > 0, "Normal map size does not match its pixels");
+
+        Self {
+            pixels,
+            width,
+            height
+        }
+    }
+
+    pub fn from_file(file_path_str: &str) -> Result<Self, String> {
+        let is_exr = file_path_str.to_lowercase().ends_with(".exr");
+        let (pixels, width, height) = if is_exr { read_exr_image(file_path_str)? } else { read_png_image(file_path_str, false)? };
+        if width == 0 || height == 0 {
+            return Err(format!("Normal map {} is empty", file_path_str));
+        }
+
+        Ok(Self::init(pixels, width, height))
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Spectrum {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+
+        self.pixels[y * self.width + x]
+    }
+
+    // the tangent space normal at uv, bilinearly filtered and repeating, with v = 0 the bottom row
+    pub fn lookup(&self, uv: &Point2) -> Vector3 {
+        let x = uv.x * self.width as Float - 0.5;
+        let y = (1.0 - uv.y) * self.height as Float - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let rgb = (1.0 - dx) * (1.0 - dy) * self.texel(x0, y0) + dx * (1.0 - dy) * self.texel(x0 + 1, y0)
+            + (1.0 - dx) * dy * self.texel(x0, y0 + 1) + dx * dy * self.texel(x0 + 1, y0 + 1);
+
+        rgb.map(|c| 2.0 * c - 1.0).normalize()
+    }
+}
+
+// Replaces the shading normal with the one in the map, keeping the lengths of dpdu and dpdv
+pub fn normal_map(map: &NormalMap, si: &mut SurfaceInteraction) {
+    let local = map.lookup(&si.uv);
+
+    // tangent frame with x along dpdu and z along the shading normal
+    let x = si.shading.dpdu.normalize();
+    let z = si.shading.n;
+    let y = z.cross(&x);
+    let ns = (x * local.x + y * local.y + z * local.z).normalize();
+
+    let u_len = si.shading.dpdu.norm();
+    let v_len = si.shading.dpdv.norm();
+    let dpdu = (si.shading.dpdu - ns * ns.dot(&si.shading.dpdu)).normalize() * u_len;
+    let dpdv = ns.cross(&dpdu).normalize() * v_len;
+    let (dndu, dndv) = (si.shading.dndu, si.shading.dndv);
+
+    si.set_shading_geometry(&dpdu, &dpdv, &dndu, &dndv, false);
+}
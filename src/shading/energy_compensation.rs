@@ -0,0 +1,431 @@
+use std::sync::OnceLock;
+
+use crate::common::*;
+
+// Kulla-Conty energy compensation. Single scattering microfacet models miss the light that
+// bounces between microfacets, 1 - E(mu) of it for a direction with cosine mu, where E is the
+// directional albedo. A diffuse-like lobe proportional to (1 - E(mu_o)) (1 - E(mu_i)) adds it
+// back, with E tabulated over cosine, alpha and for dielectrics the index of refraction
+
+const CONDUCTOR_MU: usize = 32;
+const CONDUCTOR_ALPHA: usize = 32;
+const DIELECTRIC_MU: usize = 16;
+const DIELECTRIC_ALPHA: usize = 16;
+const DIELECTRIC_ETA: usize = 16;
+const ETA_MIN: Float = 1.01;
+const ETA_MAX: Float = 3.0;
+
+// albedo of a lobe seen from a direction with cosine mu, by stratified importance sampling
+pub(crate) fn directional_albedo(bxdf: &dyn BxDF, mu: Float, inside: bool, n: usize) -> Float {
+    let mu = mu.max(1e-3);
+    let wo = Vector3::new((1.0 - mu * mu).max(0.0).sqrt(), 0.0, if inside { -mu } else { mu });
+
+    let mut sum = 0.0;
+    for i in 0..n {
+        for j in 0..n {
+            let u = Point2::new((i as Float + 0.5) / n as Float, (j as Float + 0.5) / n as Float);
+            let mut wi = Vector3::new(0.0, 0.0, 0.0);
+            let mut pdf = 0.0;
+            let mut sampled_type = bxdf.bxdf_type();
+            let f = bxdf.sample_f(&wo, &mut wi, &u, &mut pdf, &mut sampled_type);
+            if pdf > 0.0 && f.x.is_finite() {
+                sum += f.x * abs_cos_theta(&wi) / pdf;
+            }
+        }
+    }
+
+    (sum / (n * n) as Float).min(1.0)
+}
+
+// the cosine weighted average 2 int E(mu) mu dmu of values at evenly spaced cosines from 0 to 1
+fn hemispherical_average(e: &[Float]) -> Float {
+    let n = e.len();
+    let step = 1.0 / (n - 1) as Float;
+    let mut sum = 0.0;
+    for i in 0..n - 1 {
+        let (mu0, mu1) = (i as Float * step, (i + 1) as Float * step);
+        sum += 0.5 * (e[i] * mu0 + e[i + 1] * mu1) * step;
+    }
+
+    2.0 * sum
+}
+
+// linear interpolation into values at evenly spaced points from 0 to 1, x is clamped
+pub(crate) fn lerp_index(x: Float, n: usize) -> (usize, Float) {
+    let x = x.clamp(0.0, 1.0) * (n - 1) as Float;
+    let i = (x as usize).min(n - 2);
+
+    (i, x - i as Float)
+}
+
+// table position of an alpha, E is 1 at alpha 0 so the first node is exact
+fn alpha_coordinate(alpha: Float) -> Float {
+    alpha.clamp(0.0, 1.0)
+}
+
+#[derive(Debug)]
+struct ConductorAlbedo {
+    // by alpha then mu, for a perfect reflector
+    e: Vec<Float>,
+    e_avg: Vec<Float>
+}
+
+impl ConductorAlbedo {
+    fn compute() -> Self {
+        let mut e = vec![1.0; CONDUCTOR_ALPHA * CONDUCTOR_MU];
+        let mut e_avg = vec![1.0; CONDUCTOR_ALPHA];
+        for a in 1..CONDUCTOR_ALPHA {
+            let alpha = a as Float / (CONDUCTOR_ALPHA - 1) as Float;
+            let distribution = Arc::from(TrowbridgeReitzDistribution::init(alpha, alpha, true));
+            let bxdf = MicrofacetReflection::init(&Spectrum::new(1.0, 1.0, 1.0), distribution, Arc::from(FresnelNoOp));
+
+            let row = &mut e[a * CONDUCTOR_MU..(a + 1) * CONDUCTOR_MU];
+            for (m, value) in row.iter_mut().enumerate() {
+                *value = directional_albedo(&bxdf, m as Float / (CONDUCTOR_MU - 1) as Float, false, 16);
+            }
+            e_avg[a] = hemispherical_average(row);
+        }
+
+        Self { e, e_avg }
+    }
+
+    fn e(&self, mu: Float, alpha: Float) -> Float {
+        let (a, ta) = lerp_index(alpha_coordinate(alpha), CONDUCTOR_ALPHA);
+        let (m, tm) = lerp_index(mu, CONDUCTOR_MU);
+        let at = |a: usize, m: usize| self.e[a * CONDUCTOR_MU + m];
+
+        lerp(ta, lerp(tm, at(a, m), at(a, m + 1)), lerp(tm, at(a + 1, m), at(a + 1, m + 1)))
+    }
+
+    fn e_avg(&self, alpha: Float) -> Float {
+        let (a, ta) = lerp_index(alpha_coordinate(alpha), CONDUCTOR_ALPHA);
+
+        lerp(ta, self.e_avg[a], self.e_avg[a + 1])
+    }
+}
+
+fn conductor_albedo() -> &'static ConductorAlbedo {
+    static TABLE: OnceLock<ConductorAlbedo> = OnceLock::new();
+    TABLE.get_or_init(ConductorAlbedo::compute)
+}
+
+#[derive(Debug)]
+struct DielectricAlbedo {
+    // reflection plus transmission by side, eta, alpha then mu. Side 0 is outside, where light
+    // enters the denser medium
+    e: Vec<Float>,
+    e_avg: Vec<Float>,
+    // average Fresnel reflectance by side and eta
+    f_avg: Vec<Float>
+}
+
+impl DielectricAlbedo {
+    fn compute() -> Self {
+        let mut e = vec![1.0; 2 * DIELECTRIC_ETA * DIELECTRIC_ALPHA * DIELECTRIC_MU];
+        let mut e_avg = vec![1.0; 2 * DIELECTRIC_ETA * DIELECTRIC_ALPHA];
+        let mut f_avg = vec![0.0; 2 * DIELECTRIC_ETA];
+
+        for side in 0..2 {
+            let inside = side == 1;
+            for k in 0..DIELECTRIC_ETA {
+                let eta = Self::eta_at(k);
+
+                let fresnel: Vec<Float> = (0..DIELECTRIC_MU).map(|m| {
+                    let mu = m as Float / (DIELECTRIC_MU - 1) as Float;
+                    fr_dielectric(if inside { -mu } else { mu }, 1.0, eta)
+                }).collect();
+                f_avg[side * DIELECTRIC_ETA + k] = hemispherical_average(&fresnel);
+
+                for a in 1..DIELECTRIC_ALPHA {
+                    let alpha = a as Float / (DIELECTRIC_ALPHA - 1) as Float;
+                    let distribution: Arc<dyn MicrofacetDistribution> = Arc::from(TrowbridgeReitzDistribution::init(alpha, alpha, true));
+                    let one = Spectrum::new(1.0, 1.0, 1.0);
+                    let reflection = MicrofacetReflection::init(&one, distribution.clone(), Arc::from(FresnelDielectric::init(1.0, eta)));
+                    let transmission = MicrofacetTransmission::init(&one, distribution, 1.0, eta, TransportMode::Importance);
+
+                    let offset = ((side * DIELECTRIC_ETA + k) * DIELECTRIC_ALPHA + a) * DIELECTRIC_MU;
+                    let row = &mut e[offset..offset + DIELECTRIC_MU];
+                    for (m, value) in row.iter_mut().enumerate() {
+                        let mu = m as Float / (DIELECTRIC_MU - 1) as Float;
+                        *value = (directional_albedo(&reflection, mu, inside, 12) + directional_albedo(&transmission, mu, inside, 12)).min(1.0);
+                    }
+                    e_avg[(side * DIELECTRIC_ETA + k) * DIELECTRIC_ALPHA + a] = hemispherical_average(row);
+                }
+            }
+        }
+
+        Self { e, e_avg, f_avg }
+    }
+
+    fn eta_at(k: usize) -> Float {
+        ETA_MIN + (ETA_MAX - ETA_MIN) * k as Float / (DIELECTRIC_ETA - 1) as Float
+    }
+
+    fn eta_coordinate(eta: Float) -> Float {
+        (eta - ETA_MIN) / (ETA_MAX - ETA_MIN)
+    }
+
+    fn e(&self, mu: Float, alpha: Float, eta: Float, inside: bool) -> Float {
+        let side = inside as usize;
+        let (k, tk) = lerp_index(Self::eta_coordinate(eta), DIELECTRIC_ETA);
+        let (a, ta) = lerp_index(alpha_coordinate(alpha), DIELECTRIC_ALPHA);
+        let (m, tm) = lerp_index(mu, DIELECTRIC_MU);
+        let at = |k: usize, a: usize, m: usize| self.e[((side * DIELECTRIC_ETA + k) * DIELECTRIC_ALPHA + a) * DIELECTRIC_MU + m];
+        let slice = |k: usize| lerp(ta, lerp(tm, at(k, a, m), at(k, a, m + 1)), lerp(tm, at(k, a + 1, m), at(k, a + 1, m + 1)));
+
+        lerp(tk, slice(k), slice(k + 1))
+    }
+
+    fn e_avg(&self, alpha: Float, eta: Float, inside: bool) -> Float {
+        let side = inside as usize;
+        let (k, tk) = lerp_index(Self::eta_coordinate(eta), DIELECTRIC_ETA);
+        let (a, ta) = lerp_index(alpha_coordinate(alpha), DIELECTRIC_ALPHA);
+        let at = |k: usize, a: usize| self.e_avg[(side * DIELECTRIC_ETA + k) * DIELECTRIC_ALPHA + a];
+
+        lerp(tk, lerp(ta, at(k, a), at(k, a + 1)), lerp(ta, at(k + 1, a), at(k + 1, a + 1)))
+    }
+
+    fn f_avg(&self, eta: Float, inside: bool) -> Float {
+        let side = inside as usize;
+        let (k, tk) = lerp_index(Self::eta_coordinate(eta), DIELECTRIC_ETA);
+
+        lerp(tk, self.f_avg[side * DIELECTRIC_ETA + k], self.f_avg[side * DIELECTRIC_ETA + k + 1])
+    }
+}
+
+fn dielectric_albedo() -> &'static DielectricAlbedo {
+    static TABLE: OnceLock<DielectricAlbedo> = OnceLock::new();
+    TABLE.get_or_init(DielectricAlbedo::compute)
+}
+
+// the energy a Trowbridge-Reitz conductor of this alpha loses to multiple scattering, averaged
+// over the hemisphere, useful as the sampling weight of the compensation lobe
+pub fn conductor_missing_energy(alpha: Float) -> Float {
+    1.0 - conductor_albedo().e_avg(alpha)
+}
+
+// the tables hold indices above one, a smaller one swaps the sides
+fn dielectric_side(eta: Float, inside: bool) -> (Float, bool) {
+    if eta < 1.0 { (1.0 / eta, !inside) } else { (eta, inside) }
+}
+
+// the same for a dielectric of relative index eta, seen from outside or inside
+pub fn dielectric_missing_energy(alpha: Float, eta: Float, inside: bool) -> Float {
+    let (eta, inside) = dielectric_side(eta, inside);
+
+    1.0 - dielectric_albedo().e_avg(alpha, eta, inside)
+}
+
+// The multiple scattering missing from MicrofacetReflection with a Trowbridge-Reitz distribution
+// and a conductor Fresnel term
+#[derive(Debug, Clone)]
+pub struct ConductorCompensation {
+    alpha: Float,
+    e_avg: Float,
+    // the color of the light that survives the bounces
+    f_ms: Spectrum
+}
+
+impl ConductorCompensation {
+    // alpha is sqrt(alpha_x alpha_y) for anisotropic distributions
+    pub fn init(r: &Spectrum, alpha: Float, fresnel: &dyn Fresnel) -> Self {
+        let e_avg = conductor_albedo().e_avg(alpha);
+
+        // average Fresnel reflectance over the cosine weighted hemisphere
+        let n = 32;
+        let mut f_avg = Spectrum::new(0.0, 0.0, 0.0);
+        for i in 0..n {
+            let mu = (i as Float + 0.5) / n as Float;
+            f_avg += fresnel.evaluate(mu) * (2.0 * mu / n as Float);
+        }
+
+        // the series of bounces, each reflecting f_avg and escaping with e_avg
+        let f_ms = (f_avg.component_mul(&f_avg) * e_avg)
+            .component_div(&f_avg.map(|f| 1.0 - f * (1.0 - e_avg)))
+            .component_mul(r);
+
+        Self {
+            alpha,
+            e_avg,
+            f_ms
+        }
+    }
+}
+
+impl BxDF for ConductorCompensation {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        if !same_hemisphere(wo, wi) || self.e_avg >= 1.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let table = conductor_albedo();
+        let e_o = table.e(abs_cos_theta(wo), self.alpha);
+        let e_i = table.e(abs_cos_theta(wi), self.alpha);
+
+        self.f_ms * ((1.0 - e_o) * (1.0 - e_i) / (PI * (1.0 - self.e_avg)))
+    }
+}
+
+// The multiple scattering missing from a rough dielectric made of MicrofacetReflection and
+// MicrofacetTransmission with a Trowbridge-Reitz distribution. The lost energy is split between
+// reflection and transmission by the average Fresnel reflectance of the side light arrives from
+#[derive(Debug, Clone)]
+pub struct DielectricCompensation {
+    r: Spectrum,
+    t: Spectrum,
+    alpha: Float,
+    // relative index of the inside over the outside
+    eta: Float,
+    mode: TransportMode
+}
+
+impl DielectricCompensation {
+    pub fn init(r: &Spectrum, t: &Spectrum, alpha: Float, eta: Float, mode: TransportMode) -> Self {
+        Self {
+            r: *r,
+            t: *t,
+            alpha,
+            eta,
+            mode
+        }
+    }
+
+    fn side(&self, w: &Vector3) -> (Float, bool) {
+        dielectric_side(self.eta, cos_theta(w) < 0.0)
+    }
+
+    fn reflect_probability(&self, wo: &Vector3) -> Float {
+        let (eta, inside) = self.side(wo);
+
+        dielectric_albedo().f_avg(eta, inside)
+    }
+}
+
+impl BxDF for DielectricCompensation {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Transmission as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let table = dielectric_albedo();
+        let (eta, inside_o) = self.side(wo);
+        let (_, inside_i) = self.side(wi);
+
+        let e_o = table.e(abs_cos_theta(wo), self.alpha, eta, inside_o);
+        let e_i = table.e(abs_cos_theta(wi), self.alpha, eta, inside_i);
+        let e_avg_i = table.e_avg(self.alpha, eta, inside_i);
+        if e_avg_i >= 1.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        // normalized so the lobe on the side of wi integrates to the energy lost from wo
+        let lobe = (1.0 - e_o) * (1.0 - e_i) / (PI * (1.0 - e_avg_i));
+        let fr = self.reflect_probability(wo);
+        if same_hemisphere(wo, wi) {
+            return self.r * (fr * lobe);
+        }
+
+        // radiance is compressed or spread by the change of index, as in MicrofacetTransmission
+        let mut ft = self.t * ((1.0 - fr) * lobe);
+        if self.mode == TransportMode::Radiance {
+            let (eta_i, eta_t) = if cos_theta(wo) > 0.0 { (1.0, self.eta) } else { (self.eta, 1.0) };
+            ft *= (eta_i * eta_i) / (eta_t * eta_t);
+        }
+
+        ft
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, sampled_type: &mut u32) -> Spectrum {
+        let fr = self.reflect_probability(wo);
+
+        // cosine distributed on the side picked by the reflectance
+        let (reflect, u0) = if u.x < fr { (true, u.x / fr) } else { (false, (u.x - fr) / (1.0 - fr)) };
+        *wi = cosine_sample_hemisphere(&Point2::new(u0.min(ONE_MINUS_EPSILON), u.y));
+        if (wo.z < 0.0) == reflect {
+            wi.z *= -1.0;
+        }
+
+        *sampled_type = if reflect { BxDFType::Reflection as u32 } else { BxDFType::Transmission as u32 } | BxDFType::Glossy as u32;
+        *pdf = self.pdf(wo, wi);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        let fr = self.reflect_probability(wo);
+        let side = if same_hemisphere(wo, wi) { fr } else { 1.0 - fr };
+
+        side * abs_cos_theta(wi) / PI
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // integral of f cos over the sphere, keeping each lobe on the side its type allows as BSDF does
+    fn furnace(lobes: &[&dyn BxDF], wo: &Vector3) -> Float {
+        let n = 192;
+        let mut sum = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let wi = uniform_sample_sphere(&Point2::new((i as Float + 0.5) / n as Float, (j as Float + 0.5) / n as Float));
+                let side = if wo.z * wi.z > 0.0 { BxDFType::Reflection as u32 } else { BxDFType::Transmission as u32 };
+                for lobe in lobes.iter().filter(|lobe| lobe.bxdf_type() & side != 0) {
+                    sum += lobe.f(wo, &wi).x * abs_cos_theta(&wi) / uniform_sphere_pdf();
+                }
+            }
+        }
+
+        sum / (n * n) as Float
+    }
+
+    #[test]
+    fn white_furnace() {
+        let one = Spectrum::new(1.0, 1.0, 1.0);
+        for alpha in [0.2, 0.5, 0.8, 1.0] {
+            let distribution: Arc<dyn MicrofacetDistribution> = Arc::from(TrowbridgeReitzDistribution::init(alpha, alpha, true));
+            for mu in [0.1 as Float, 0.5, 0.9] {
+                let sin = (1.0 - mu * mu).sqrt();
+
+                let reflection = MicrofacetReflection::init(&one, distribution.clone(), Arc::from(FresnelNoOp));
+                let compensation = ConductorCompensation::init(&one, alpha, &FresnelNoOp);
+                let metal = furnace(&[&reflection, &compensation], &Vector3::new(sin, 0.0, mu));
+                assert!((metal - 1.0).abs() < 0.03, "metal alpha {} mu {}: {}", alpha, mu, metal);
+
+                for wo in [Vector3::new(sin, 0.0, mu), Vector3::new(sin, 0.0, -mu)] {
+                    let reflection = MicrofacetReflection::init(&one, distribution.clone(), Arc::from(FresnelDielectric::init(1.0, 1.5)));
+                    let transmission = MicrofacetTransmission::init(&one, distribution.clone(), 1.0, 1.5, TransportMode::Importance);
+                    let compensation = DielectricCompensation::init(&one, &one, alpha, 1.5, TransportMode::Importance);
+                    let glass = furnace(&[&reflection, &transmission, &compensation], &wo);
+                    assert!((glass - 1.0).abs() < 0.03, "glass alpha {} cos {}: {}", alpha, wo.z, glass);
+                }
+            }
+        }
+    }
+
+    // the sampling weight of the lobe is the energy it adds back, averaged over cosine weighted wo
+    #[test]
+    fn compensation_weight() {
+        let one = Spectrum::new(1.0, 1.0, 1.0);
+        let n = 16;
+        for alpha in [0.3 as Float, 0.7, 1.0] {
+            for (eta, inside) in [(1.5, false), (1.5, true), (1.0 / 1.5, false)] {
+                let compensation = DielectricCompensation::init(&one, &one, alpha, eta, TransportMode::Importance);
+                let mut average = 0.0;
+                for i in 0..n {
+                    let mu = (i as Float + 0.5) / n as Float;
+                    let wo = Vector3::new((1.0 - mu * mu).sqrt(), 0.0, if inside { -mu } else { mu });
+                    average += 2.0 * mu * furnace(&[&compensation], &wo) / n as Float;
+                }
+
+                let missing = dielectric_missing_energy(alpha, eta, inside);
+                assert!((average - missing).abs() < 0.01 + 0.1 * missing, "alpha {} eta {} inside {}: {} vs {}", alpha, eta, inside, average, missing);
+            }
+        }
+    }
+}
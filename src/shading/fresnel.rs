@@ -0,0 +1,117 @@
+use crate::common::*;
+
+// Fresnel reflectance of a dielectric interface for unpolarized light. cos_theta_i is measured
+// from the side of eta_i, a negative value means the ray arrives from the eta_t side
+pub fn fr_dielectric(cos_theta_i: Float, eta_i: Float, eta_t: Float) -> Float {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (mut eta_i, mut eta_t) = (eta_i, eta_t);
+    if cos_theta_i <= 0.0 {
+        std::mem::swap(&mut eta_i, &mut eta_t);
+        cos_theta_i = cos_theta_i.abs();
+    }
+
+    let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+    let sin_theta_t = eta_i / eta_t * sin_theta_i;
+    // total internal reflection
+    if sin_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(0.0).sqrt();
+
+    let r_parl = ((eta_t * cos_theta_i) - (eta_i * cos_theta_t)) / ((eta_t * cos_theta_i) + (eta_i * cos_theta_t));
+    let r_perp = ((eta_i * cos_theta_i) - (eta_t * cos_theta_t)) / ((eta_i * cos_theta_i) + (eta_t * cos_theta_t));
+
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+// one channel of the conductor reflectance, eta and k relative to the outside medium
+fn fr_conductor_channel(cos_theta_i: Float, eta: Float, k: Float) -> Float {
+    let cos2_theta_i = cos_theta_i * cos_theta_i;
+    let sin2_theta_i = 1.0 - cos2_theta_i;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2_theta_i;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2_theta_i;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2_theta_i * a2_plus_b2 + sin2_theta_i * sin2_theta_i;
+    let t4 = t2 * sin2_theta_i;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// Fresnel reflectance of a conductor with complex index of refraction eta_t + i k
+pub fn fr_conductor(cos_theta_i: Float, eta_i: &Spectrum, eta_t: &Spectrum, k: &Spectrum) -> Spectrum {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+
+    Spectrum::new(
+        fr_conductor_channel(cos_theta_i, eta_t.x / eta_i.x, k.x / eta_i.x),
+        fr_conductor_channel(cos_theta_i, eta_t.y / eta_i.y, k.y / eta_i.y),
+        fr_conductor_channel(cos_theta_i, eta_t.z / eta_i.z, k.z / eta_i.z)
+    )
+}
+
+pub trait Fresnel: Debug {
+    fn evaluate(&self, cos_i: Float) -> Spectrum;
+}
+
+#[derive(Debug, Clone)]
+pub struct FresnelConductor {
+    eta_i: Spectrum,
+    eta_t: Spectrum,
+    k: Spectrum
+}
+
+impl FresnelConductor {
+    pub fn init(eta_i: &Spectrum, eta_t: &Spectrum, k: &Spectrum) -> Self {
+        Self {
+            eta_i: *eta_i,
+            eta_t: *eta_t,
+            k: *k
+        }
+    }
+}
+
+impl Fresnel for FresnelConductor {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        fr_conductor(cos_i.abs(), &self.eta_i, &self.eta_t, &self.k)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FresnelDielectric {
+    eta_i: Float,
+    eta_t: Float
+}
+
+impl FresnelDielectric {
+    pub fn init(eta_i: Float, eta_t: Float) -> Self {
+        Self {
+            eta_i,
+            eta_t
+        }
+    }
+}
+
+impl Fresnel for FresnelDielectric {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        let f = fr_dielectric(cos_i, self.eta_i, self.eta_t);
+
+        Spectrum::new(f, f, f)
+    }
+}
+
+// reflects everything
+#[derive(Debug, Clone)]
+pub struct FresnelNoOp;
+
+impl Fresnel for FresnelNoOp {
+    fn evaluate(&self, _cos_i: Float) -> Spectrum {
+        Spectrum::new(1.0, 1.0, 1.0)
+    }
+}
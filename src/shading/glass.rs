@@ -0,0 +1,121 @@
+use crate::common::*;
+
+// Dielectric with specular or rough reflection and transmission
+#[derive(Debug, Clone)]
+pub struct GlassMaterial {
+    kr: Arc<dyn Texture<Spectrum>>,
+    kt: Arc<dyn Texture<Spectrum>>,
+    u_roughness: Arc<dyn Texture<Float>>,
+    v_roughness: Arc<dyn Texture<Float>>,
+    index: Arc<dyn Texture<Float>>,
+    remap_roughness: bool,
+    // add back the energy lost to multiple scattering between microfacets
+    energy_compensation: bool,
+    // nm, an interference film on the surface when set
+    film_thickness: Option<Arc<dyn Texture<Float>>>,
+    film_eta: Arc<dyn Texture<Float>>
+}
+
+impl GlassMaterial {
+    pub fn init(kr: Arc<dyn Texture<Spectrum>>, kt: Arc<dyn Texture<Spectrum>>, u_roughness: Arc<dyn Texture<Float>>,
+        v_roughness: Arc<dyn Texture<Float>>, index: Arc<dyn Texture<Float>>, remap_roughness: bool) -> Self {
+        Self {
+            kr,
+            kt,
+            u_roughness,
+            v_roughness,
+            index,
+            remap_roughness,
+            energy_compensation: true,
+            film_thickness: None,
+            film_eta: Arc::from(ConstantTexture::init(1.33))
+        }
+    }
+
+    pub fn init_constant(kr: &Spectrum, kt: &Spectrum, roughness: Float, index: Float) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(*kr)), Arc::from(ConstantTexture::init(*kt)),
+            Arc::from(ConstantTexture::init(roughness)), Arc::from(ConstantTexture::init(roughness)),
+            Arc::from(ConstantTexture::init(index)), true)
+    }
+
+    pub fn set_energy_compensation(&mut self, energy_compensation: bool) {
+        self.energy_compensation = energy_compensation;
+    }
+
+    // a thin film of this thickness in nm and index of refraction over the surface
+    pub fn set_thin_film(&mut self, thickness: Arc<dyn Texture<Float>>, eta: Arc<dyn Texture<Float>>) {
+        self.film_thickness = Some(thickness);
+        self.film_eta = eta;
+    }
+}
+
+impl Material for GlassMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, allow_multiple_lobes: bool) {
+        let eta = self.index.evaluate(isect);
+        let mut u_rough = self.u_roughness.evaluate(isect);
+        let mut v_rough = self.v_roughness.evaluate(isect);
+        let r = self.kr.evaluate(isect).map(|c| c.max(0.0));
+        let t = self.kt.evaluate(isect).map(|c| c.max(0.0));
+
+        let mut bsdf = BSDF::init(isect, eta);
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+        if r == black && t == black {
+            isect.bsdf = Some(Arc::from(bsdf));
+            return;
+        }
+
+        let film = self.film_thickness.as_ref().map(|thickness| ThinFilm::init(thickness.evaluate(isect), self.film_eta.evaluate(isect)));
+        let fresnel: Arc<dyn Fresnel> = match film {
+            Some(film) => Arc::from(FresnelThinFilmDielectric::init(1.0, eta, film)),
+            None => Arc::from(FresnelDielectric::init(1.0, eta))
+        };
+
+        // FresnelSpecular has no film, the lobes are split instead
+        let is_specular = u_rough == 0.0 && v_rough == 0.0;
+        if is_specular && allow_multiple_lobes && film.is_none() {
+            bsdf.add(Arc::from(FresnelSpecular::init(&r, &t, 1.0, eta, mode)));
+        } else {
+            if self.remap_roughness {
+                u_rough = roughness_to_alpha(u_rough);
+                v_rough = roughness_to_alpha(v_rough);
+            }
+            let distribution: Option<Arc<dyn MicrofacetDistribution>> = if is_specular {
+                None
+            } else {
+                Some(Arc::from(TrowbridgeReitzDistribution::init(u_rough, v_rough, true)))
+            };
+
+            if r != black {
+                match &distribution {
+                    Some(distribution) => bsdf.add(Arc::from(MicrofacetReflection::init(&r, distribution.clone(), fresnel.clone()))),
+                    None => bsdf.add(Arc::from(SpecularReflection::init(&r, fresnel.clone())))
+                }
+            }
+            if t != black {
+                match &distribution {
+                    Some(distribution) => {
+                        let mut transmission = MicrofacetTransmission::init(&t, distribution.clone(), 1.0, eta, mode);
+                        transmission.set_fresnel(fresnel);
+                        bsdf.add(Arc::from(transmission));
+                    }
+                    None => {
+                        let mut transmission = SpecularTransmission::init(&t, 1.0, eta, mode);
+                        transmission.set_fresnel(fresnel);
+                        bsdf.add(Arc::from(transmission));
+                    }
+                }
+            }
+            if self.energy_compensation && distribution.is_some() {
+                // the side is the one wo leaves from, as the BSDF frame sees it
+                let alpha = (u_rough * v_rough).sqrt();
+                let inside = isect.interaction.wo.dot(&isect.shading.n) < 0.0;
+                let missing = dielectric_missing_energy(alpha, eta, inside);
+                if missing > 1e-3 {
+                    bsdf.add_weighted(Arc::from(DielectricCompensation::init(&r, &t, alpha, eta, mode)), missing);
+                }
+            }
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
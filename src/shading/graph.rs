@@ -0,0 +1,845 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::common::*;
+
+// The kinds of values flowing between the nodes of a shading graph. A Float is accepted anywhere
+// a Color is and is used for all three channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphType {
+    Float,
+    Color,
+    Uv,
+    Bsdf
+}
+
+// an input of a node, the output of an earlier node or a literal
+#[derive(Debug, Clone)]
+pub enum GraphInput {
+    Node(usize),
+    Float(Float),
+    Color(Spectrum)
+}
+
+#[derive(Debug, Clone)]
+pub enum GraphNode {
+    // a literal as a node of its own
+    Constant(GraphInput),
+
+    // the surface parameterization
+    Uv,
+    // scales, then rotates by degrees about the origin, then offsets
+    UvTransform { uv: GraphInput, scale_u: GraphInput, scale_v: GraphInput, offset_u: GraphInput, offset_v: GraphInput, rotation: GraphInput },
+
+    // textures looked up at the surface uv, or at the given one
+    FloatTexture { texture: Arc<dyn Texture<Float>>, uv: Option<GraphInput> },
+    ColorTexture { texture: Arc<dyn Texture<Spectrum>>, uv: Option<GraphInput> },
+    // a on squares where floor(u) + floor(v) is even, b on the others
+    Checker { uv: GraphInput, a: GraphInput, b: GraphInput },
+
+    Add(GraphInput, GraphInput),
+    Sub(GraphInput, GraphInput),
+    Mul(GraphInput, GraphInput),
+    // a at t = 0, b at t = 1
+    Mix { a: GraphInput, b: GraphInput, t: GraphInput },
+    Clamp { x: GraphInput, min: GraphInput, max: GraphInput },
+    // maps [from_min, from_max] linearly to [to_min, to_max]
+    Remap { x: GraphInput, from_min: GraphInput, from_max: GraphInput, to_min: GraphInput, to_max: GraphInput },
+
+    Rgb { r: GraphInput, g: GraphInput, b: GraphInput },
+    Channel { c: GraphInput, index: usize },
+    Luminance(GraphInput),
+    // 1 - x
+    Invert(GraphInput),
+    Gamma { x: GraphInput, gamma: GraphInput },
+    // 0 is grey, 1 leaves the colour as it is
+    Saturation { c: GraphInput, s: GraphInput },
+
+    Lambertian { color: GraphInput },
+    // sigma in degrees
+    OrenNayar { color: GraphInput, sigma: GraphInput },
+    // roughness in [0, 1], 0 is a mirror
+    Conductor { eta: GraphInput, k: GraphInput, roughness: GraphInput },
+    Dielectric { eta: GraphInput, roughness: GraphInput },
+    Sheen { color: GraphInput, roughness: GraphInput },
+    // the lobes of both
+    AddBsdf(GraphInput, GraphInput)
+}
+
+// texture coordinates with their screen space derivatives, so transformed lookups still filter
+#[derive(Debug, Clone, Copy)]
+struct UvValue {
+    st: Point2,
+    dstdx: Vector2,
+    dstdy: Vector2
+}
+
+#[derive(Debug, Clone)]
+struct Lobe {
+    bxdf: Arc<dyn BxDF>,
+    weight: Float
+}
+
+#[derive(Debug, Clone)]
+enum GraphValue {
+    Float(Float),
+    Color(Spectrum),
+    Uv(UvValue),
+    // eta is the index of refraction the BSDF reports, 1 without a dielectric lobe
+    Bsdf { lobes: Vec<Lobe>, eta: Float }
+}
+
+impl GraphValue {
+    fn float(&self) -> Float {
+        match self {
+            GraphValue::Float(f) => *f,
+            GraphValue::Color(c) => rgb_y(c),
+            _ => 0.0
+        }
+    }
+
+    fn color(&self) -> Spectrum {
+        match self {
+            GraphValue::Float(f) => Spectrum::new(*f, *f, *f),
+            GraphValue::Color(c) => *c,
+            _ => Spectrum::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn uv(&self) -> UvValue {
+        match self {
+            GraphValue::Uv(uv) => *uv,
+            _ => UvValue { st: Point2::new(0.0, 0.0), dstdx: Vector2::new(0.0, 0.0), dstdy: Vector2::new(0.0, 0.0) }
+        }
+    }
+
+    // a per channel operation on Floats and Colors, Float only when every value is one
+    fn lift(values: &[GraphValue], f: impl Fn(&[Float]) -> Float) -> GraphValue {
+        if values.iter().all(|v| matches!(v, GraphValue::Float(_))) {
+            let x: Vec<Float> = values.iter().map(|v| v.float()).collect();
+            return GraphValue::Float(f(&x));
+        }
+
+        let colors: Vec<Spectrum> = values.iter().map(|v| v.color()).collect();
+        let channel = |i: usize| f(&colors.iter().map(|c| c[i]).collect::<Vec<Float>>());
+
+        GraphValue::Color(Spectrum::new(channel(0), channel(1), channel(2)))
+    }
+}
+
+fn type_name(t: GraphType) -> &'static str {
+    match t {
+        GraphType::Float => "Float",
+        GraphType::Color => "Color",
+        GraphType::Uv => "Uv",
+        GraphType::Bsdf => "Bsdf"
+    }
+}
+
+// A graph of texture, math, colour and BxDF nodes evaluated at every shading point. Nodes can
+// only use nodes defined before them so the graph has no cycles, and each node is type checked as
+// it is added. In the text form each line defines a node or names the output:
+//
+//     # a scaled checker between two colours under a sheen
+//     tiled = uv_transform uv 8 8 0 0 0
+//     pattern = checker tiled [0.8 0.1 0.1] [0.9 0.9 0.9]
+//     base = lambertian pattern
+//     fuzz = sheen 0.5 0.3
+//     cloth = add_bsdf base fuzz
+//     output cloth
+//
+// Arguments are node names, numbers or colours in brackets, and uv is the surface uv. Textures
+// added with add_float_texture or add_color_texture are used with texture <name> [uv]
+#[derive(Debug, Clone)]
+pub struct ShadingGraph {
+    nodes: Vec<GraphNode>,
+    types: Vec<GraphType>,
+    names: HashMap<String, usize>,
+    float_textures: HashMap<String, Arc<dyn Texture<Float>>>,
+    color_textures: HashMap<String, Arc<dyn Texture<Spectrum>>>,
+    output: Option<usize>
+}
+
+impl ShadingGraph {
+    // starts with the surface uv as a node named uv
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![GraphNode::Uv],
+            types: vec![GraphType::Uv],
+            names: HashMap::from([("uv".to_string(), 0)]),
+            float_textures: HashMap::new(),
+            color_textures: HashMap::new(),
+            output: None
+        }
+    }
+
+    // a graph file using no external textures
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let mut graph = Self::new();
+        graph.parse_file(path)?;
+
+        Ok(graph)
+    }
+
+    pub fn add_float_texture(&mut self, name: &str, texture: Arc<dyn Texture<Float>>) {
+        self.float_textures.insert(name.to_string(), texture);
+    }
+
+    pub fn add_color_texture(&mut self, name: &str, texture: Arc<dyn Texture<Spectrum>>) {
+        self.color_textures.insert(name.to_string(), texture);
+    }
+
+    pub fn node(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    // adds a node after checking the types of its inputs, returns its index
+    pub fn add_node(&mut self, name: &str, node: GraphNode) -> Result<usize, String> {
+        if self.names.contains_key(name) {
+            return Err(format!("Node {} is defined twice", name));
+        }
+
+        let t = self.check(&node).map_err(|e| format!("Node {}: {}", name, e))?;
+        self.nodes.push(node);
+        self.types.push(t);
+        self.names.insert(name.to_string(), self.nodes.len() - 1);
+
+        Ok(self.nodes.len() - 1)
+    }
+
+    pub fn set_output(&mut self, name: &str) -> Result<(), String> {
+        let index = self.node(name).ok_or_else(|| format!("Unknown output node {}", name))?;
+        self.output = Some(index);
+
+        Ok(())
+    }
+
+    pub fn parse_file(&mut self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read shading graph {}: {}", path, e))?;
+
+        self.parse(&text)
+    }
+
+    // adds the nodes of a graph in text form
+    pub fn parse(&mut self, text: &str) -> Result<(), String> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            self.parse_line(line).map_err(|e| format!("Shading graph line {}: {}", number + 1, e))?;
+        }
+
+        Ok(())
+    }
+
+    // true when the graph has an output, it is a BSDF and its lobes fit in one
+    pub fn validate(&self) -> Result<(), String> {
+        let output = match self.output {
+            Some(output) if self.types[output] == GraphType::Bsdf => output,
+            Some(output) => return Err(format!("The shading graph output is a {}, not a Bsdf", type_name(self.types[output]))),
+            None => return Err("The shading graph has no output".to_string())
+        };
+
+        if self.max_lobes()[output] > MAX_BXDFS {
+            return Err(format!("The shading graph output can have more lobes than the {} a BSDF holds", MAX_BXDFS));
+        }
+
+        Ok(())
+    }
+
+    // the most lobes each node can give, in one pass as nodes only use earlier ones
+    fn max_lobes(&self) -> Vec<usize> {
+        let mut lobes: Vec<usize> = Vec::with_capacity(self.nodes.len());
+        for (node, t) in self.nodes.iter().zip(&self.types) {
+            let count = match node {
+                GraphNode::AddBsdf(a, b) => [a, b].iter().map(|input| match input {
+                    GraphInput::Node(i) => lobes[*i],
+                    _ => 0
+                }).fold(0, usize::saturating_add),
+                _ if *t == GraphType::Bsdf => 1,
+                _ => 0
+            };
+            lobes.push(count);
+        }
+
+        lobes
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let tokens = tokenize(line)?;
+        if tokens.first().map(String::as_str) == Some("output") {
+            if tokens.len() != 2 {
+                return Err("Expected output <node>".to_string());
+            }
+            return self.set_output(&tokens[1]);
+        }
+
+        if tokens.len() < 3 || tokens[1] != "=" {
+            return Err("Expected <name> = <op> <arguments>".to_string());
+        }
+        let name = &tokens[0];
+        let op = tokens[2].as_str();
+        let args = &tokens[3..];
+
+        let node = self.node_from_op(op, args)?;
+        self.add_node(name, node)?;
+
+        Ok(())
+    }
+
+    fn parse_input(&self, token: &str) -> Result<GraphInput, String> {
+        if let Ok(f) = token.parse::<Float>() {
+            return Ok(GraphInput::Float(f));
+        }
+
+        if let Some(inner) = token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            let c: Result<Vec<Float>, _> = inner.split_whitespace().map(|t| t.parse::<Float>()).collect();
+            return match c {
+                Ok(c) if c.len() == 3 => Ok(GraphInput::Color(Spectrum::new(c[0], c[1], c[2]))),
+                _ => Err(format!("Invalid colour {}, expected [r g b]", token))
+            };
+        }
+
+        self.node(token).map(GraphInput::Node).ok_or_else(|| format!("Unknown node {}", token))
+    }
+
+    fn node_from_op(&self, op: &str, args: &[String]) -> Result<GraphNode, String> {
+        let arity = |n: usize, signature: &str| {
+            if args.len() == n { Ok(()) } else { Err(format!("Expected {} {}", op, signature)) }
+        };
+        let input = |i: usize| self.parse_input(&args[i]);
+
+        let node = match op {
+            "constant" => { arity(1, "<value>")?; GraphNode::Constant(input(0)?) }
+            "uv" => { arity(0, "")?; GraphNode::Uv }
+            "uv_transform" => {
+                arity(6, "<uv> <scale u> <scale v> <offset u> <offset v> <rotation>")?;
+                GraphNode::UvTransform { uv: input(0)?, scale_u: input(1)?, scale_v: input(2)?, offset_u: input(3)?, offset_v: input(4)?, rotation: input(5)? }
+            }
+            "texture" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err("Expected texture <name> [uv]".to_string());
+                }
+                let uv = if args.len() == 2 { Some(input(1)?) } else { None };
+                if let Some(texture) = self.float_textures.get(&args[0]) {
+                    GraphNode::FloatTexture { texture: texture.clone(), uv }
+                } else if let Some(texture) = self.color_textures.get(&args[0]) {
+                    GraphNode::ColorTexture { texture: texture.clone(), uv }
+                } else {
+                    return Err(format!("Unknown texture {}", args[0]));
+                }
+            }
+            "checker" => { arity(3, "<uv> <a> <b>")?; GraphNode::Checker { uv: input(0)?, a: input(1)?, b: input(2)? } }
+            "add" => { arity(2, "<a> <b>")?; GraphNode::Add(input(0)?, input(1)?) }
+            "sub" => { arity(2, "<a> <b>")?; GraphNode::Sub(input(0)?, input(1)?) }
+            "mul" => { arity(2, "<a> <b>")?; GraphNode::Mul(input(0)?, input(1)?) }
+            "mix" => { arity(3, "<a> <b> <t>")?; GraphNode::Mix { a: input(0)?, b: input(1)?, t: input(2)? } }
+            "clamp" => { arity(3, "<x> <min> <max>")?; GraphNode::Clamp { x: input(0)?, min: input(1)?, max: input(2)? } }
+            "remap" => {
+                arity(5, "<x> <from min> <from max> <to min> <to max>")?;
+                GraphNode::Remap { x: input(0)?, from_min: input(1)?, from_max: input(2)?, to_min: input(3)?, to_max: input(4)? }
+            }
+            "rgb" => { arity(3, "<r> <g> <b>")?; GraphNode::Rgb { r: input(0)?, g: input(1)?, b: input(2)? } }
+            "channel" => {
+                arity(2, "<color> <0, 1 or 2>")?;
+                let index = match args[1].as_str() { "0" => 0, "1" => 1, "2" => 2, _ => return Err("The channel index is 0, 1 or 2".to_string()) };
+                GraphNode::Channel { c: input(0)?, index }
+            }
+            "luminance" => { arity(1, "<color>")?; GraphNode::Luminance(input(0)?) }
+            "invert" => { arity(1, "<x>")?; GraphNode::Invert(input(0)?) }
+            "gamma" => { arity(2, "<x> <gamma>")?; GraphNode::Gamma { x: input(0)?, gamma: input(1)? } }
+            "saturation" => { arity(2, "<color> <saturation>")?; GraphNode::Saturation { c: input(0)?, s: input(1)? } }
+            "lambertian" => { arity(1, "<color>")?; GraphNode::Lambertian { color: input(0)? } }
+            "oren_nayar" => { arity(2, "<color> <sigma>")?; GraphNode::OrenNayar { color: input(0)?, sigma: input(1)? } }
+            "conductor" => { arity(3, "<eta> <k> <roughness>")?; GraphNode::Conductor { eta: input(0)?, k: input(1)?, roughness: input(2)? } }
+            "dielectric" => { arity(2, "<eta> <roughness>")?; GraphNode::Dielectric { eta: input(0)?, roughness: input(1)? } }
+            "sheen" => { arity(2, "<color> <roughness>")?; GraphNode::Sheen { color: input(0)?, roughness: input(1)? } }
+            "add_bsdf" => { arity(2, "<a> <b>")?; GraphNode::AddBsdf(input(0)?, input(1)?) }
+            _ => return Err(format!("Unknown node type {}", op))
+        };
+
+        Ok(node)
+    }
+
+    fn input_type(&self, input: &GraphInput) -> Result<GraphType, String> {
+        match input {
+            GraphInput::Node(i) => self.types.get(*i).copied().ok_or_else(|| format!("Input node {} does not exist", i)),
+            GraphInput::Float(_) => Ok(GraphType::Float),
+            GraphInput::Color(_) => Ok(GraphType::Color)
+        }
+    }
+
+    // the type of an input that has to be one of the allowed ones
+    fn expect(&self, what: &str, input: &GraphInput, allowed: &[GraphType]) -> Result<GraphType, String> {
+        let t = self.input_type(input)?;
+        if allowed.contains(&t) {
+            return Ok(t);
+        }
+
+        let names: Vec<&str> = allowed.iter().map(|t| type_name(*t)).collect();
+        Err(format!("{} has to be a {}, not a {}", what, names.join(" or "), type_name(t)))
+    }
+
+    // the output type of a node, or why its inputs don't fit
+    fn check(&self, node: &GraphNode) -> Result<GraphType, String> {
+        use GraphType::*;
+        let numeric = [Float, Color];
+        let scalar = [Float];
+        // Color when any of the numeric inputs is one
+        let widest = |types: &[GraphType]| if types.contains(&Color) { Color } else { Float };
+
+        let t = match node {
+            GraphNode::Constant(value) => self.expect("the value", value, &numeric)?,
+            GraphNode::Uv => Uv,
+            GraphNode::UvTransform { uv, scale_u, scale_v, offset_u, offset_v, rotation } => {
+                self.expect("uv", uv, &[Uv])?;
+                for (what, input) in [("scale u", scale_u), ("scale v", scale_v), ("offset u", offset_u), ("offset v", offset_v), ("rotation", rotation)] {
+                    self.expect(what, input, &scalar)?;
+                }
+                Uv
+            }
+            GraphNode::FloatTexture { uv, .. } | GraphNode::ColorTexture { uv, .. } => {
+                if let Some(uv) = uv {
+                    self.expect("uv", uv, &[Uv])?;
+                }
+                if matches!(node, GraphNode::FloatTexture { .. }) { Float } else { Color }
+            }
+            GraphNode::Checker { uv, a, b } => {
+                self.expect("uv", uv, &[Uv])?;
+                widest(&[self.expect("a", a, &numeric)?, self.expect("b", b, &numeric)?])
+            }
+            GraphNode::Add(a, b) | GraphNode::Sub(a, b) | GraphNode::Mul(a, b) => {
+                widest(&[self.expect("a", a, &numeric)?, self.expect("b", b, &numeric)?])
+            }
+            GraphNode::Mix { a, b, t } => {
+                widest(&[self.expect("a", a, &numeric)?, self.expect("b", b, &numeric)?, self.expect("t", t, &numeric)?])
+            }
+            GraphNode::Clamp { x, min, max } => {
+                self.expect("min", min, &scalar)?;
+                self.expect("max", max, &scalar)?;
+                self.expect("x", x, &numeric)?
+            }
+            GraphNode::Remap { x, from_min, from_max, to_min, to_max } => {
+                for (what, input) in [("from min", from_min), ("from max", from_max), ("to min", to_min), ("to max", to_max)] {
+                    self.expect(what, input, &scalar)?;
+                }
+                self.expect("x", x, &numeric)?
+            }
+            GraphNode::Rgb { r, g, b } => {
+                self.expect("r", r, &scalar)?;
+                self.expect("g", g, &scalar)?;
+                self.expect("b", b, &scalar)?;
+                Color
+            }
+            GraphNode::Channel { c, index } => {
+                if *index > 2 {
+                    return Err(format!("The channel index is 0, 1 or 2, not {}", index));
+                }
+                self.expect("the colour", c, &numeric)?;
+                Float
+            }
+            GraphNode::Luminance(c) => {
+                self.expect("the colour", c, &numeric)?;
+                Float
+            }
+            GraphNode::Invert(x) => self.expect("x", x, &numeric)?,
+            GraphNode::Gamma { x, gamma } => {
+                self.expect("gamma", gamma, &scalar)?;
+                self.expect("x", x, &numeric)?
+            }
+            GraphNode::Saturation { c, s } => {
+                self.expect("the saturation", s, &scalar)?;
+                self.expect("the colour", c, &numeric)?;
+                Color
+            }
+            GraphNode::Lambertian { color } => {
+                self.expect("the colour", color, &numeric)?;
+                Bsdf
+            }
+            GraphNode::OrenNayar { color, sigma } => {
+                self.expect("the colour", color, &numeric)?;
+                self.expect("sigma", sigma, &scalar)?;
+                Bsdf
+            }
+            GraphNode::Conductor { eta, k, roughness } => {
+                self.expect("eta", eta, &numeric)?;
+                self.expect("k", k, &numeric)?;
+                self.expect("the roughness", roughness, &scalar)?;
+                Bsdf
+            }
+            GraphNode::Dielectric { eta, roughness } => {
+                self.expect("eta", eta, &scalar)?;
+                self.expect("the roughness", roughness, &scalar)?;
+                Bsdf
+            }
+            GraphNode::Sheen { color, roughness } => {
+                self.expect("the colour", color, &numeric)?;
+                self.expect("the roughness", roughness, &scalar)?;
+                Bsdf
+            }
+            GraphNode::AddBsdf(a, b) => {
+                self.expect("a", a, &[Bsdf])?;
+                self.expect("b", b, &[Bsdf])?;
+                Bsdf
+            }
+        };
+
+        Ok(t)
+    }
+
+    fn inputs(node: &GraphNode) -> Vec<&GraphInput> {
+        match node {
+            GraphNode::Uv => Vec::new(),
+            GraphNode::Constant(x) | GraphNode::Luminance(x) | GraphNode::Invert(x) | GraphNode::Channel { c: x, .. } |
+            GraphNode::Lambertian { color: x } => vec![x],
+            GraphNode::FloatTexture { uv, .. } | GraphNode::ColorTexture { uv, .. } => uv.iter().collect(),
+            GraphNode::UvTransform { uv, scale_u, scale_v, offset_u, offset_v, rotation } => vec![uv, scale_u, scale_v, offset_u, offset_v, rotation],
+            GraphNode::Checker { uv: a, a: b, b: c } | GraphNode::Mix { a, b, t: c } | GraphNode::Clamp { x: a, min: b, max: c } |
+            GraphNode::Rgb { r: a, g: b, b: c } | GraphNode::Conductor { eta: a, k: b, roughness: c } => vec![a, b, c],
+            GraphNode::Remap { x, from_min, from_max, to_min, to_max } => vec![x, from_min, from_max, to_min, to_max],
+            GraphNode::Add(a, b) | GraphNode::Sub(a, b) | GraphNode::Mul(a, b) | GraphNode::AddBsdf(a, b) |
+            GraphNode::Gamma { x: a, gamma: b } | GraphNode::Saturation { c: a, s: b } | GraphNode::OrenNayar { color: a, sigma: b } |
+            GraphNode::Dielectric { eta: a, roughness: b } | GraphNode::Sheen { color: a, roughness: b } => vec![a, b]
+        }
+    }
+
+    // the nodes the output depends on
+    fn live_nodes(&self) -> Vec<bool> {
+        let mut live = vec![false; self.nodes.len()];
+        if let Some(output) = self.output {
+            live[output] = true;
+        }
+        for i in (0..self.nodes.len()).rev() {
+            if !live[i] {
+                continue;
+            }
+            for input in Self::inputs(&self.nodes[i]) {
+                if let GraphInput::Node(j) = input {
+                    live[*j] = true;
+                }
+            }
+        }
+
+        live
+    }
+
+    fn evaluate(&self, si: &SurfaceInteraction, mode: TransportMode, live: &[bool]) -> GraphValue {
+        let mut values: Vec<GraphValue> = Vec::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            let value = if live[i] { Self::evaluate_node(node, &values, si, mode) } else { GraphValue::Float(0.0) };
+            values.push(value);
+        }
+
+        match self.output {
+            Some(output) => values.swap_remove(output),
+            None => GraphValue::Bsdf { lobes: Vec::new(), eta: 1.0 }
+        }
+    }
+
+    fn evaluate_node(node: &GraphNode, values: &[GraphValue], si: &SurfaceInteraction, mode: TransportMode) -> GraphValue {
+        let get = |input: &GraphInput| match input {
+            GraphInput::Node(i) => values[*i].clone(),
+            GraphInput::Float(f) => GraphValue::Float(*f),
+            GraphInput::Color(c) => GraphValue::Color(*c)
+        };
+        let float = |input: &GraphInput| get(input).float();
+        let color = |input: &GraphInput| get(input).color();
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+
+        // the surface with its texture coordinates replaced
+        let at_uv = |uv: &Option<GraphInput>| -> Option<SurfaceInteraction> {
+            let uv = get(uv.as_ref()?).uv();
+            let mut si = si.clone();
+            si.uv = uv.st;
+            si.dudx = uv.dstdx.x;
+            si.dvdx = uv.dstdx.y;
+            si.dudy = uv.dstdy.x;
+            si.dvdy = uv.dstdy.y;
+            Some(si)
+        };
+
+        match node {
+            GraphNode::Constant(value) => get(value),
+            GraphNode::Uv => GraphValue::Uv(UvValue {
+                st: si.uv,
+                dstdx: Vector2::new(si.dudx, si.dvdx),
+                dstdy: Vector2::new(si.dudy, si.dvdy)
+            }),
+            GraphNode::UvTransform { uv, scale_u, scale_v, offset_u, offset_v, rotation } => {
+                let uv = get(uv).uv();
+                let (sin, cos) = float(rotation).to_radians().sin_cos();
+                let (su, sv) = (float(scale_u), float(scale_v));
+                let m = na::Matrix2::new(cos * su, -sin * sv, sin * su, cos * sv);
+                GraphValue::Uv(UvValue {
+                    st: Point2::from(m * uv.st.coords + Vector2::new(float(offset_u), float(offset_v))),
+                    dstdx: m * uv.dstdx,
+                    dstdy: m * uv.dstdy
+                })
+            }
+            GraphNode::FloatTexture { texture, uv } => GraphValue::Float(texture.evaluate(at_uv(uv).as_ref().unwrap_or(si))),
+            GraphNode::ColorTexture { texture, uv } => GraphValue::Color(texture.evaluate(at_uv(uv).as_ref().unwrap_or(si))),
+            GraphNode::Checker { uv, a, b } => {
+                let st = get(uv).uv().st;
+                let even = (st.x.floor() + st.y.floor()) as i64 % 2 == 0;
+                let (chosen, other) = if even { (get(a), get(b)) } else { (get(b), get(a)) };
+                // keeps the type the checker was given, a Color if either side is one
+                GraphValue::lift(&[chosen, other], |x| x[0])
+            }
+            GraphNode::Add(a, b) => GraphValue::lift(&[get(a), get(b)], |x| x[0] + x[1]),
+            GraphNode::Sub(a, b) => GraphValue::lift(&[get(a), get(b)], |x| x[0] - x[1]),
+            GraphNode::Mul(a, b) => GraphValue::lift(&[get(a), get(b)], |x| x[0] * x[1]),
+            GraphNode::Mix { a, b, t } => GraphValue::lift(&[get(a), get(b), get(t)], |x| lerp(x[2], x[0], x[1])),
+            GraphNode::Clamp { x, min, max } => {
+                let (min, max) = (float(min), float(max));
+                GraphValue::lift(&[get(x)], |x| x[0].max(min).min(max))
+            }
+            GraphNode::Remap { x, from_min, from_max, to_min, to_max } => {
+                let (from_min, from_max, to_min, to_max) = (float(from_min), float(from_max), float(to_min), float(to_max));
+                let range = if from_max != from_min { from_max - from_min } else { 1.0 };
+                GraphValue::lift(&[get(x)], |x| lerp((x[0] - from_min) / range, to_min, to_max))
+            }
+            GraphNode::Rgb { r, g, b } => GraphValue::Color(Spectrum::new(float(r), float(g), float(b))),
+            GraphNode::Channel { c, index } => GraphValue::Float(color(c)[*index]),
+            GraphNode::Luminance(c) => GraphValue::Float(rgb_y(&color(c))),
+            GraphNode::Invert(x) => GraphValue::lift(&[get(x)], |x| 1.0 - x[0]),
+            GraphNode::Gamma { x, gamma } => {
+                let gamma = float(gamma);
+                GraphValue::lift(&[get(x)], |x| x[0].max(0.0).powf(gamma))
+            }
+            GraphNode::Saturation { c, s } => {
+                let c = color(c);
+                let grey = rgb_y(&c);
+                let s = float(s);
+                GraphValue::Color(c.map(|v| lerp(s, grey, v)))
+            }
+            GraphNode::Lambertian { color: c } => {
+                let r = color(c).map(|v| v.clamp(0.0, 1.0));
+                let lobes = if r == black { Vec::new() } else { vec![Lobe { bxdf: Arc::from(LambertianReflection::init(&r)), weight: r.max() }] };
+                GraphValue::Bsdf { lobes, eta: 1.0 }
+            }
+            GraphNode::OrenNayar { color: c, sigma } => {
+                let r = color(c).map(|v| v.clamp(0.0, 1.0));
+                let lobes = if r == black { Vec::new() } else {
+                    vec![Lobe { bxdf: Arc::from(OrenNayar::init(&r, float(sigma).clamp(0.0, 90.0))), weight: r.max() }]
+                };
+                GraphValue::Bsdf { lobes, eta: 1.0 }
+            }
+            GraphNode::Conductor { eta, k, roughness } => {
+                let one = Spectrum::new(1.0, 1.0, 1.0);
+                let fresnel = Arc::from(FresnelConductor::init(&one, &color(eta), &color(k)));
+                let roughness = float(roughness).max(0.0);
+                let bxdf: Arc<dyn BxDF> = if roughness == 0.0 {
+                    Arc::from(SpecularReflection::init(&one, fresnel))
+                } else {
+                    let alpha = roughness_to_alpha(roughness);
+                    Arc::from(MicrofacetReflection::init(&one, Arc::from(TrowbridgeReitzDistribution::init(alpha, alpha, true)), fresnel))
+                };
+                GraphValue::Bsdf { lobes: vec![Lobe { bxdf, weight: 1.0 }], eta: 1.0 }
+            }
+            GraphNode::Dielectric { eta, roughness } => {
+                let eta = float(eta).max(1e-3);
+                let roughness = float(roughness).max(0.0);
+                let distribution: Option<Arc<dyn MicrofacetDistribution>> = if roughness == 0.0 { None } else {
+                    let alpha = roughness_to_alpha(roughness);
+                    Some(Arc::from(TrowbridgeReitzDistribution::init(alpha, alpha, true)))
+                };
+                GraphValue::Bsdf { lobes: vec![Lobe { bxdf: Arc::from(DielectricBxDF::init(eta, distribution, mode)), weight: 1.0 }], eta }
+            }
+            GraphNode::Sheen { color: c, roughness } => {
+                let r = color(c).map(|v| v.clamp(0.0, 1.0));
+                let lobes = if r == black { Vec::new() } else {
+                    vec![Lobe { bxdf: Arc::from(CharlieSheen::init(&r, float(roughness))), weight: r.max() }]
+                };
+                GraphValue::Bsdf { lobes, eta: 1.0 }
+            }
+            GraphNode::AddBsdf(a, b) => match (get(a), get(b)) {
+                (GraphValue::Bsdf { lobes: mut a, eta: eta_a }, GraphValue::Bsdf { lobes: b, eta: eta_b }) => {
+                    a.extend(b);
+                    GraphValue::Bsdf { lobes: a, eta: if eta_a != 1.0 { eta_a } else { eta_b } }
+                }
+                _ => GraphValue::Bsdf { lobes: Vec::new(), eta: 1.0 }
+            }
+        }
+    }
+}
+
+impl Default for ShadingGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// splits a line on whitespace, keeping bracketed colours together
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_brackets = false;
+    for c in line.chars() {
+        match c {
+            '[' if !in_brackets => {
+                in_brackets = true;
+                current.push(c);
+            }
+            ']' if in_brackets => {
+                in_brackets = false;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_brackets => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c)
+        }
+    }
+    if in_brackets {
+        return Err("Unclosed [".to_string());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+// A material built from a validated shading graph, its output gives the lobes of the BSDF
+#[derive(Debug, Clone)]
+pub struct GraphMaterial {
+    graph: ShadingGraph,
+    live: Vec<bool>
+}
+
+impl GraphMaterial {
+    pub fn init(graph: ShadingGraph) -> Result<Self, String> {
+        graph.validate()?;
+        let live = graph.live_nodes();
+
+        Ok(Self {
+            graph,
+            live
+        })
+    }
+}
+
+impl Material for GraphMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, _allow_multiple_lobes: bool) {
+        let (lobes, eta) = match self.graph.evaluate(isect, mode, &self.live) {
+            GraphValue::Bsdf { lobes, eta } => (lobes, eta),
+            _ => (Vec::new(), 1.0)
+        };
+
+        let mut bsdf = BSDF::init(isect, eta);
+        for lobe in lobes {
+            bsdf.add_weighted(lobe.bxdf, lobe.weight.max(1e-3));
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ShadingGraph, String> {
+        let mut graph = ShadingGraph::new();
+        graph.parse(text)?;
+
+        Ok(graph)
+    }
+
+    fn error(text: &str) -> String {
+        match parse(text).and_then(|graph| graph.validate()) {
+            Ok(()) => panic!("the graph should be rejected:\n{}", text),
+            Err(e) => e
+        }
+    }
+
+    #[test]
+    fn accepts_a_typed_graph() {
+        let graph = parse("
+            # a checkered plastic
+            tiles = uv_transform uv 4 4 0 0 45
+            base = checker tiles [0.8 0.1 0.1] 0.9
+            tint = mix base [1 1 1] 0.25
+            diffuse = lambertian tint
+            coat = dielectric 1.5 0.1
+            surface = add_bsdf diffuse coat
+            output surface
+        ").unwrap();
+        assert_eq!(graph.validate(), Ok(()));
+
+        let material = GraphMaterial::init(graph).unwrap();
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        let mut isect = SurfaceInteraction::init(&Point3::new(0.0, 0.0, 0.0), &zero, &Point2::new(0.3, 0.6), &Vector3::new(0.0, 0.0, 1.0),
+            &Vector3::new(1.0, 0.0, 0.0), &Vector3::new(0.0, 1.0, 0.0), &zero, &zero, 0.0, None);
+        material.compute_scattering_function(&mut isect, TransportMode::Radiance, true);
+        assert_eq!(isect.bsdf.unwrap().num_components(BSDF_ALL), 2);
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        assert!(error("d = lambertian uv").contains("the colour has to be a Float or Color, not a Uv"));
+        assert!(error("d = oren_nayar 0.5 [0.1 0.2 0.3]").contains("sigma has to be a Float, not a Color"));
+        assert!(error("t = uv_transform 1 1 1 0 0 0").contains("uv has to be a Uv, not a Float"));
+        assert!(error("d = lambertian 0.5\ns = add d 1").contains("a has to be a Float or Color, not a Bsdf"));
+        assert!(error("d = lambertian 0.5\ns = add_bsdf d 0.5").contains("b has to be a Bsdf, not a Float"));
+        assert!(error("c = rgb 1 [0 0 0] 1").contains("g has to be a Float, not a Color"));
+    }
+
+    #[test]
+    fn rejects_channel_indices_past_blue() {
+        assert!(error("c = channel [1 0 0] 3").contains("The channel index is 0, 1 or 2"));
+
+        let mut graph = ShadingGraph::new();
+        let channel = GraphNode::Channel { c: GraphInput::Color(Spectrum::new(1.0, 0.0, 0.0)), index: 5 };
+        assert_eq!(graph.add_node("c", channel), Err("Node c: The channel index is 0, 1 or 2, not 5".to_string()));
+        assert_eq!(graph.node("c"), None);
+    }
+
+    // a node can only use nodes defined before it, so a graph can't loop back on itself
+    #[test]
+    fn rejects_unknown_and_repeated_nodes() {
+        assert!(error("a = add b 1\nb = constant 1").contains("Unknown node b"));
+        assert!(error("a = add a 1").contains("Unknown node a"));
+        assert!(error("a = constant 1\na = constant 2").contains("Node a is defined twice"));
+        assert!(error("uv = constant 1").contains("Node uv is defined twice"));
+        assert!(error("d = lambertian 0.5\noutput e").contains("Unknown output node e"));
+
+        let mut graph = ShadingGraph::new();
+        assert_eq!(graph.add_node("n", GraphNode::Invert(GraphInput::Node(7))), Err("Node n: Input node 7 does not exist".to_string()));
+    }
+
+    #[test]
+    fn rejects_outputs_that_are_not_a_bsdf() {
+        assert_eq!(error("d = lambertian 0.5"), "The shading graph has no output");
+        assert_eq!(error("c = constant [1 1 1]\noutput c"), "The shading graph output is a Color, not a Bsdf");
+        assert!(GraphMaterial::init(parse("c = constant 1\noutput c").unwrap()).is_err());
+    }
+
+    #[test]
+    fn rejects_outputs_with_too_many_lobes() {
+        let mut text = "l1 = lambertian 0.1\n".to_string();
+        for i in 1..MAX_BXDFS {
+            text += &format!("d{} = lambertian 0.1\nl{} = add_bsdf l{} d{}\n", i, i + 1, i, i);
+        }
+        assert_eq!(parse(&format!("{}output l{}", text, MAX_BXDFS)).unwrap().validate(), Ok(()));
+
+        // sharing a node still counts its lobes on both sides
+        let doubled = "a = lambertian 0.1\nb = add_bsdf a a\nc = add_bsdf b b\nd = add_bsdf c c\ne = add_bsdf d d\noutput e";
+        assert!(error(doubled).contains("more lobes than the 8 a BSDF holds"));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(error("a = blend 1 2").contains("Unknown node type blend"));
+        assert!(error("a = mix 1 2").contains("Expected mix <a> <b> <t>"));
+        assert!(error("a = constant [1 2]").contains("Invalid colour [1 2], expected [r g b]"));
+        assert!(error("a = constant [1 2 3").contains("Unclosed ["));
+        assert!(error("a constant 1").contains("Expected <name> = <op> <arguments>"));
+        assert!(error("a = texture wood").contains("Unknown texture wood"));
+        assert!(error("\n\nd = lambertian uv").starts_with("Shading graph line 3: Node d:"));
+    }
+}
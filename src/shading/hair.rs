@@ -0,0 +1,447 @@
+use crate::common::*;
+
+// number of explicit lobes, R, TT and TRT. The rest is lumped into one residual lobe
+const P_MAX: usize = 3;
+const SQRT_PI_OVER_8: Float = 0.626_657;
+
+// absorption of the two melanin pigments per unit concentration
+const EUMELANIN_SIGMA_A: [Float; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN_SIGMA_A: [Float; 3] = [0.187, 0.4, 1.05];
+
+fn safe_sqrt(x: Float) -> Float {
+    x.max(0.0).sqrt()
+}
+
+fn safe_asin(x: Float) -> Float {
+    x.clamp(-1.0, 1.0).asin()
+}
+
+// modified Bessel function of the first kind, order 0
+fn i0(x: Float) -> Float {
+    let mut val = 0.0;
+    let mut x2i = 1.0;
+    let mut ifact = 1.0;
+    let mut i4 = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            ifact *= i as Float;
+        }
+        val += x2i / (i4 * ifact * ifact);
+        x2i *= x * x;
+        i4 *= 4.0;
+    }
+
+    val
+}
+
+fn log_i0(x: Float) -> Float {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+// longitudinal scattering, v is the variance
+fn mp(cos_theta_i: Float, cos_theta_o: Float, sin_theta_i: Float, sin_theta_o: Float, v: Float) -> Float {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+
+    // the plain form overflows for low roughness
+    if v <= 0.1 {
+        (log_i0(a) - b - 1.0 / v + std::f32::consts::LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        ((-b).exp() * i0(a)) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+// attenuation of each lobe, from the Fresnel term at the cuticle and absorption inside the fiber
+fn ap(cos_theta_o: Float, eta: Float, h: Float, t: &Spectrum) -> [Spectrum; P_MAX + 1] {
+    let cos_gamma_o = safe_sqrt(1.0 - h * h);
+    let cos_theta = cos_theta_o * cos_gamma_o;
+    let f = fr_dielectric(cos_theta, 1.0, eta);
+
+    let mut ap = [Spectrum::new(0.0, 0.0, 0.0); P_MAX + 1];
+    ap[0] = Spectrum::new(f, f, f);
+    ap[1] = t * (1.0 - f) * (1.0 - f);
+    for p in 2..P_MAX {
+        ap[p] = ap[p - 1].component_mul(t) * f;
+    }
+    // the geometric series of all longer paths
+    let tf = t * f;
+    ap[P_MAX] = ap[P_MAX - 1].component_mul(&tf).component_div(&Spectrum::new(1.0 - tf.x, 1.0 - tf.y, 1.0 - tf.z));
+
+    ap
+}
+
+// net azimuthal deflection of lobe p
+fn phi(p: usize, gamma_o: Float, gamma_t: Float) -> Float {
+    2.0 * p as Float * gamma_t - 2.0 * gamma_o + p as Float * PI
+}
+
+fn logistic(x: Float, s: Float) -> Float {
+    let x = x.abs();
+
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: Float, s: Float) -> Float {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: Float, s: Float, a: Float, b: Float) -> Float {
+    logistic(x, s) / (logistic_cdf(b, s) - logistic_cdf(a, s))
+}
+
+fn sample_trimmed_logistic(u: Float, s: Float, a: Float, b: Float) -> Float {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(a, s)) - 1.0).ln();
+
+    x.clamp(a, b)
+}
+
+// azimuthal scattering of lobe p
+fn np(phi_diff: Float, p: usize, s: Float, gamma_o: Float, gamma_t: Float) -> Float {
+    let mut dphi = phi_diff - phi(p, gamma_o, gamma_t);
+    while dphi > PI {
+        dphi -= 2.0 * PI;
+    }
+    while dphi < -PI {
+        dphi += 2.0 * PI;
+    }
+
+    trimmed_logistic(dphi, s, -PI, PI)
+}
+
+// the even bits of x packed into the low half
+fn compact_1_by_1(mut x: u32) -> u32 {
+    x &= 0x55555555;
+    x = (x ^ (x >> 1)) & 0x33333333;
+    x = (x ^ (x >> 2)) & 0x0f0f0f0f;
+    x = (x ^ (x >> 4)) & 0x00ff00ff;
+    x = (x ^ (x >> 8)) & 0x0000ffff;
+
+    x
+}
+
+// two sample values from the interleaved bits of one, sampling needs four dimensions
+fn demux_float(f: Float) -> Point2 {
+    let v = (f as f64 * (1u64 << 32) as f64) as u64;
+    let bits = (compact_1_by_1(v as u32), compact_1_by_1((v >> 1) as u32));
+
+    Point2::new(bits.0 as Float / (1 << 16) as Float, bits.1 as Float / (1 << 16) as Float)
+}
+
+// absorption coefficient from eumelanin and pheomelanin concentrations
+pub fn hair_sigma_a_from_concentration(ce: Float, cp: Float) -> Spectrum {
+    Spectrum::new(
+        ce * EUMELANIN_SIGMA_A[0] + cp * PHEOMELANIN_SIGMA_A[0],
+        ce * EUMELANIN_SIGMA_A[1] + cp * PHEOMELANIN_SIGMA_A[1],
+        ce * EUMELANIN_SIGMA_A[2] + cp * PHEOMELANIN_SIGMA_A[2]
+    )
+}
+
+// absorption coefficient that gives roughly the color c after multiple scattering
+pub fn hair_sigma_a_from_reflectance(c: &Spectrum, beta_n: Float) -> Spectrum {
+    let denom = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3) + 5.574 * beta_n.powi(4) +
+        0.245 * beta_n.powi(5);
+
+    c.map(|c| (c.ln() / denom).powi(2))
+}
+
+// Chiang et al. 2016 hair fiber scattering. The local frame has x along the fiber and the
+// normal in the yz plane, with h the offset across the fiber in [-1, 1]
+#[derive(Debug, Clone)]
+pub struct HairBSDF {
+    h: Float,
+    gamma_o: Float,
+    eta: Float,
+    sigma_a: Spectrum,
+    // longitudinal variance per lobe and azimuthal logistic scale
+    v: [Float; P_MAX + 1],
+    s: Float,
+    // sin and cos of 2^k times the cuticle tilt
+    sin_2k_alpha: [Float; 3],
+    cos_2k_alpha: [Float; 3]
+}
+
+impl HairBSDF {
+    // beta_m and beta_n are the longitudinal and azimuthal roughness in [0, 1], alpha the
+    // cuticle tilt in degrees
+    pub fn init(h: Float, eta: Float, sigma_a: &Spectrum, beta_m: Float, beta_n: Float, alpha: Float) -> Self {
+        let h = h.clamp(-1.0, 1.0);
+
+        let mut v = [0.0; P_MAX + 1];
+        v[0] = (0.726 * beta_m + 0.812 * beta_m * beta_m + 3.7 * beta_m.powi(20)).powi(2);
+        v[1] = 0.25 * v[0];
+        v[2] = 4.0 * v[0];
+        for p in 3..=P_MAX {
+            v[p] = v[2];
+        }
+
+        let s = SQRT_PI_OVER_8 * (0.265 * beta_n + 1.194 * beta_n * beta_n + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [0.0; 3];
+        let mut cos_2k_alpha = [0.0; 3];
+        sin_2k_alpha[0] = alpha.to_radians().sin();
+        cos_2k_alpha[0] = safe_sqrt(1.0 - sin_2k_alpha[0] * sin_2k_alpha[0]);
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+
+        Self {
+            h,
+            gamma_o: safe_asin(h),
+            eta,
+            sigma_a: *sigma_a,
+            v,
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha
+        }
+    }
+
+    // theta_o rotated by the cuticle tilt for lobe p, the residual lobe is not tilted
+    fn tilted(&self, p: usize, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Float) {
+        let (sin_theta_op, cos_theta_op) = match p {
+            0 => (sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1]),
+            1 => (sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0]),
+            2 => (sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2]),
+            _ => (sin_theta_o, cos_theta_o)
+        };
+
+        (sin_theta_op, cos_theta_op.abs())
+    }
+
+    // angles of the refracted ray inside the fiber and its transmittance across it
+    fn transmittance(&self, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Spectrum) {
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+
+        // modified index for the projection onto the normal plane
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = self.h / etap;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let gamma_t = safe_asin(sin_gamma_t);
+
+        let t = (-self.sigma_a * (2.0 * cos_gamma_t / cos_theta_t)).map(|x| x.exp());
+
+        (gamma_t, t)
+    }
+
+    // probability of picking each lobe, by its luminance
+    fn ap_pdf(&self, cos_theta_o: Float) -> [Float; P_MAX + 1] {
+        let sin_theta_o = safe_sqrt(1.0 - cos_theta_o * cos_theta_o);
+        let (_, t) = self.transmittance(sin_theta_o, cos_theta_o);
+        let ap = ap(cos_theta_o, self.eta, self.h, &t);
+
+        let mut ap_pdf = [0.0; P_MAX + 1];
+        let sum_y: Float = ap.iter().map(rgb_y).sum();
+        for p in 0..=P_MAX {
+            ap_pdf[p] = rgb_y(&ap[p]) / sum_y;
+        }
+
+        ap_pdf
+    }
+
+    fn pdf_angles(&self, sin_theta_o: Float, cos_theta_o: Float, sin_theta_i: Float, cos_theta_i: Float, dphi: Float, gamma_t: Float) -> Float {
+        let ap_pdf = self.ap_pdf(cos_theta_o);
+
+        let mut pdf = 0.0;
+        for (p, v) in self.v.iter().enumerate().take(P_MAX) {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            pdf += mp(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, *v) * ap_pdf[p] *
+                np(dphi, p, self.s, self.gamma_o, gamma_t);
+        }
+        pdf += mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) * ap_pdf[P_MAX] * (1.0 / (2.0 * PI));
+
+        pdf
+    }
+}
+
+impl BxDF for HairBSDF {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Transmission as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi_i = wi.z.atan2(wi.y);
+
+        let (gamma_t, t) = self.transmittance(sin_theta_o, cos_theta_o);
+        let ap = ap(cos_theta_o, self.eta, self.h, &t);
+
+        let dphi = phi_i - phi_o;
+        let mut f = Spectrum::new(0.0, 0.0, 0.0);
+        for (p, v) in self.v.iter().enumerate().take(P_MAX) {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            f += ap[p] * mp(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, *v) *
+                np(dphi, p, self.s, self.gamma_o, gamma_t);
+        }
+        f += ap[P_MAX] * mp(cos_theta_i, cos_theta_o, sin_theta_i, sin_theta_o, self.v[P_MAX]) / (2.0 * PI);
+
+        // the model is of the scattered radiance, the cosine is applied by the caller
+        if abs_cos_theta(wi) > 0.0 {
+            f /= abs_cos_theta(wi);
+        }
+
+        f
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+
+        // lobe, azimuth, and the two longitudinal dimensions
+        let mut u0 = demux_float(u.x);
+        let u1 = demux_float(u.y);
+
+        let ap_pdf = self.ap_pdf(cos_theta_o);
+        let mut p = 0;
+        while p < P_MAX {
+            if u0.x < ap_pdf[p] {
+                break;
+            }
+            u0.x -= ap_pdf[p];
+            p += 1;
+        }
+
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+
+        // longitudinal angle around the specular cone
+        let u_m = u1.x.max(1e-5);
+        let cos_theta = 1.0 + self.v[p] * (u_m + (1.0 - u_m) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * u1.y).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        let (gamma_t, _) = self.transmittance(sin_theta_o, cos_theta_o);
+        let dphi = if p < P_MAX {
+            phi(p, self.gamma_o, gamma_t) + sample_trimmed_logistic(u0.y, self.s, -PI, PI)
+        } else {
+            2.0 * PI * u0.y
+        };
+
+        let phi_i = phi_o + dphi;
+        *wi = Vector3::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin());
+        *pdf = self.pdf_angles(sin_theta_o, cos_theta_o, sin_theta_i, cos_theta_i, dphi, gamma_t);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi_i = wi.z.atan2(wi.y);
+
+        let (gamma_t, _) = self.transmittance(sin_theta_o, cos_theta_o);
+
+        self.pdf_angles(sin_theta_o, cos_theta_o, sin_theta_i, cos_theta_i, phi_i - phi_o, gamma_t)
+    }
+}
+
+// Hair fibers with absorption given directly, as a color, or by melanin concentrations
+#[derive(Debug, Clone)]
+pub struct HairMaterial {
+    sigma_a: Option<Arc<dyn Texture<Spectrum>>>,
+    color: Option<Arc<dyn Texture<Spectrum>>>,
+    eumelanin: Option<Arc<dyn Texture<Float>>>,
+    pheomelanin: Option<Arc<dyn Texture<Float>>>,
+    eta: Arc<dyn Texture<Float>>,
+    beta_m: Arc<dyn Texture<Float>>,
+    beta_n: Arc<dyn Texture<Float>>,
+    alpha: Arc<dyn Texture<Float>>
+}
+
+impl Default for HairMaterial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HairMaterial {
+    // brown hair with the default fiber parameters
+    pub fn new() -> Self {
+        Self {
+            sigma_a: None,
+            color: None,
+            eumelanin: None,
+            pheomelanin: None,
+            eta: Arc::from(ConstantTexture::init(1.55)),
+            beta_m: Arc::from(ConstantTexture::init(0.3)),
+            beta_n: Arc::from(ConstantTexture::init(0.3)),
+            alpha: Arc::from(ConstantTexture::init(2.0))
+        }
+    }
+
+    // the absorption setters replace each other, sigma_a wins over color and color over melanin
+    pub fn set_sigma_a(&mut self, sigma_a: Arc<dyn Texture<Spectrum>>) {
+        self.sigma_a = Some(sigma_a);
+    }
+
+    pub fn set_color(&mut self, color: Arc<dyn Texture<Spectrum>>) {
+        self.color = Some(color);
+    }
+
+    pub fn set_melanin(&mut self, eumelanin: Arc<dyn Texture<Float>>, pheomelanin: Arc<dyn Texture<Float>>) {
+        self.eumelanin = Some(eumelanin);
+        self.pheomelanin = Some(pheomelanin);
+    }
+
+    pub fn set_eta(&mut self, eta: Arc<dyn Texture<Float>>) {
+        self.eta = eta;
+    }
+
+    pub fn set_roughness(&mut self, beta_m: Arc<dyn Texture<Float>>, beta_n: Arc<dyn Texture<Float>>) {
+        self.beta_m = beta_m;
+        self.beta_n = beta_n;
+    }
+
+    // cuticle scale tilt in degrees
+    pub fn set_alpha(&mut self, alpha: Arc<dyn Texture<Float>>) {
+        self.alpha = alpha;
+    }
+}
+
+impl Material for HairMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let beta_m = self.beta_m.evaluate(isect).clamp(0.0, 1.0);
+        let beta_n = self.beta_n.evaluate(isect).clamp(0.0, 1.0);
+        let alpha = self.alpha.evaluate(isect);
+        let eta = self.eta.evaluate(isect);
+
+        let sigma_a = if let Some(sigma_a) = &self.sigma_a {
+            sigma_a.evaluate(isect).map(|s| s.max(0.0))
+        } else if let Some(color) = &self.color {
+            let c = color.evaluate(isect).map(|c| c.clamp(0.0, 0.99));
+            hair_sigma_a_from_reflectance(&c, beta_n)
+        } else {
+            let ce = self.eumelanin.as_ref().map_or(1.3, |e| e.evaluate(isect).max(0.0));
+            let cp = self.pheomelanin.as_ref().map_or(0.0, |p| p.evaluate(isect).max(0.0));
+            hair_sigma_a_from_concentration(ce, cp)
+        };
+
+        // v runs across the fiber
+        let h = -1.0 + 2.0 * isect.uv.y;
+
+        let mut bsdf = BSDF::init(isect, eta);
+        bsdf.add(Arc::from(HairBSDF::init(h, eta, &sigma_a, beta_m, beta_n, alpha)));
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
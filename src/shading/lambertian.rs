@@ -0,0 +1,79 @@
+use crate::common::*;
+
+// Scatters equally in all directions of the hemisphere
+#[derive(Debug, Clone)]
+pub struct LambertianReflection {
+    r: Spectrum
+}
+
+impl LambertianReflection {
+    pub fn init(r: &Spectrum) -> Self {
+        Self {
+            r: *r
+        }
+    }
+}
+
+impl BxDF for LambertianReflection {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, _wo: &Vector3, _wi: &Vector3) -> Spectrum {
+        self.r * (1.0 / PI)
+    }
+
+    fn rho(&self, _wo: &Vector3, _samples: &[Point2]) -> Spectrum {
+        self.r
+    }
+
+    fn rho_hemispherical(&self, _samples1: &[Point2], _samples2: &[Point2]) -> Spectrum {
+        self.r
+    }
+}
+
+// Scatters equally in all directions of the opposite hemisphere
+#[derive(Debug, Clone)]
+pub struct LambertianTransmission {
+    t: Spectrum
+}
+
+impl LambertianTransmission {
+    pub fn init(t: &Spectrum) -> Self {
+        Self {
+            t: *t
+        }
+    }
+}
+
+impl BxDF for LambertianTransmission {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Transmission as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, _wo: &Vector3, _wi: &Vector3) -> Spectrum {
+        self.t * (1.0 / PI)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        *wi = cosine_sample_hemisphere(u);
+        if wo.z > 0.0 {
+            wi.z *= -1.0;
+        }
+        *pdf = self.pdf(wo, wi);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        if !same_hemisphere(wo, wi) { abs_cos_theta(wi) * (1.0 / PI) } else { 0.0 }
+    }
+
+    fn rho(&self, _wo: &Vector3, _samples: &[Point2]) -> Spectrum {
+        self.t
+    }
+
+    fn rho_hemispherical(&self, _samples1: &[Point2], _samples2: &[Point2]) -> Spectrum {
+        self.t
+    }
+}
@@ -0,0 +1,509 @@
+use crate::common::*;
+
+// An interface of a layer stack. Non reciprocal BxDFs like dielectric transmission differ when
+// the walk is traced from the other end, so the adjoint one is kept alongside
+#[derive(Debug, Clone)]
+pub struct LayerInterface {
+    bxdf: Arc<dyn BxDF>,
+    adjoint: Arc<dyn BxDF>
+}
+
+impl LayerInterface {
+    pub fn init(bxdf: Arc<dyn BxDF>, adjoint: Arc<dyn BxDF>) -> Self {
+        Self {
+            bxdf,
+            adjoint
+        }
+    }
+
+    // conductors and diffuse surfaces are their own adjoint
+    pub fn init_reciprocal(bxdf: Arc<dyn BxDF>) -> Self {
+        Self {
+            adjoint: bxdf.clone(),
+            bxdf
+        }
+    }
+
+    // a dielectric boundary, smooth for a roughness of 0
+    pub fn init_dielectric(eta: Float, alpha_x: Float, alpha_y: Float, mode: TransportMode) -> Self {
+        let distribution: Option<Arc<dyn MicrofacetDistribution>> = if alpha_x == 0.0 && alpha_y == 0.0 {
+            None
+        } else {
+            Some(Arc::from(TrowbridgeReitzDistribution::init(alpha_x, alpha_y, true)))
+        };
+        let adjoint_mode = if mode == TransportMode::Radiance { TransportMode::Importance } else { TransportMode::Radiance };
+
+        Self {
+            bxdf: Arc::from(DielectricBxDF::init(eta, distribution.clone(), mode)),
+            adjoint: Arc::from(DielectricBxDF::init(eta, distribution, adjoint_mode))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Top,
+    Bottom
+}
+
+// a direction sampled at one of the interfaces
+struct InterfaceSample {
+    f: Spectrum,
+    wi: Vector3,
+    pdf: Float,
+    sampled_type: u32
+}
+
+fn is_specular(t: u32) -> bool {
+    t & BxDFType::Specular as u32 != 0
+}
+
+fn is_non_specular(t: u32) -> bool {
+    t & (BxDFType::Diffuse as u32 | BxDFType::Glossy as u32) != 0
+}
+
+fn power_heuristic(f_pdf: Float, g_pdf: Float) -> Float {
+    let (f, g) = (f_pdf * f_pdf, g_pdf * g_pdf);
+    if f.is_infinite() { 1.0 } else { f / (f + g) }
+}
+
+fn sample_exponential(u: Float, a: Float) -> Float {
+    -(1.0 - u).ln() / a
+}
+
+// transmittance through a slab of unit extinction along w, dz is the depth crossed
+fn tr(dz: Float, w: &Vector3) -> Float {
+    if dz.abs() <= Float::MIN_POSITIVE {
+        return 1.0;
+    }
+
+    (-(dz / w.z).abs()).exp()
+}
+
+// Position free Monte Carlo evaluation of two interfaces with a homogeneous medium between them
+// (Guo et al. 2018). f and pdf are stochastic estimates: random walks bounce between the
+// interfaces and are connected to the end direction at every vertex. The estimates are unbiased
+// and deterministic for a given pair of directions. The top interface faces +z and the bottom one
+// faces -z, so its BxDF is evaluated with flipped directions
+#[derive(Debug, Clone)]
+pub struct LayeredBxDF {
+    top: LayerInterface,
+    bottom: LayerInterface,
+    // in mean free paths of the medium
+    thickness: Float,
+    // single scattering albedo of the medium, none at 0
+    albedo: Spectrum,
+    // Henyey-Greenstein asymmetry of the medium
+    g: Float,
+    max_depth: u32,
+    n_samples: u32,
+    // entering from below behaves as entering from above, for opaque stacks
+    two_sided: bool
+}
+
+impl LayeredBxDF {
+    #[allow(clippy::too_many_arguments)]
+    pub fn init(top: LayerInterface, bottom: LayerInterface, thickness: Float, albedo: &Spectrum, g: Float,
+        max_depth: u32, n_samples: u32, two_sided: bool) -> Self {
+        Self {
+            top,
+            bottom,
+            thickness: thickness.max(Float::MIN_POSITIVE),
+            albedo: *albedo,
+            g,
+            max_depth,
+            n_samples: n_samples.max(1),
+            two_sided
+        }
+    }
+
+    fn interface(&self, layer: Layer) -> &LayerInterface {
+        if layer == Layer::Top { &self.top } else { &self.bottom }
+    }
+
+    fn has_albedo(&self) -> bool {
+        self.albedo.max() > 0.0
+    }
+
+    fn interface_type(&self, layer: Layer) -> u32 {
+        self.interface(layer).bxdf.bxdf_type()
+    }
+
+    fn interface_f(&self, layer: Layer, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let bxdf = &self.interface(layer).bxdf;
+        if layer == Layer::Top { bxdf.f(wo, wi) } else { bxdf.f(&-wo, &-wi) }
+    }
+
+    fn interface_pdf(&self, layer: Layer, wo: &Vector3, wi: &Vector3, adjoint: bool) -> Float {
+        let interface = self.interface(layer);
+        let bxdf = if adjoint { &interface.adjoint } else { &interface.bxdf };
+        if layer == Layer::Top { bxdf.pdf(wo, wi) } else { bxdf.pdf(&-wo, &-wi) }
+    }
+
+    // Samples the interface, keeping only the lobes in restrict_to. Rejecting the others keeps
+    // the estimate unbiased, with the unconditional pdf as the density of the kept directions
+    fn interface_sample(&self, layer: Layer, wo: &Vector3, rng: &mut RNG, adjoint: bool, restrict_to: u32) -> Option<InterfaceSample> {
+        let interface = self.interface(layer);
+        let bxdf = if adjoint { &interface.adjoint } else { &interface.bxdf };
+        let u = Point2::new(rng.uniform_float(), rng.uniform_float());
+
+        let wo = if layer == Layer::Top { *wo } else { -wo };
+        let mut wi = Vector3::new(0.0, 0.0, 0.0);
+        let mut pdf = 0.0;
+        let mut sampled_type = bxdf.bxdf_type();
+        let f = bxdf.sample_f(&wo, &mut wi, &u, &mut pdf, &mut sampled_type);
+        if pdf == 0.0 || f.max() <= 0.0 || wi.z == 0.0 {
+            return None;
+        }
+
+        let lobe = if same_hemisphere(&wo, &wi) { BxDFType::Reflection } else { BxDFType::Transmission };
+        if restrict_to & lobe as u32 == 0 {
+            return None;
+        }
+        sampled_type = (sampled_type & !(BxDFType::Reflection as u32 | BxDFType::Transmission as u32)) | lobe as u32;
+
+        Some(InterfaceSample {
+            f,
+            wi: if layer == Layer::Top { wi } else { -wi },
+            pdf,
+            sampled_type
+        })
+    }
+}
+
+impl BxDF for LayeredBxDF {
+    fn bxdf_type(&self) -> u32 {
+        let top = self.interface_type(Layer::Top);
+        let bottom = self.interface_type(Layer::Bottom);
+
+        let mut t = BxDFType::Reflection as u32;
+        if is_specular(top) {
+            t |= BxDFType::Specular as u32;
+        }
+        if (top | bottom) & BxDFType::Diffuse as u32 != 0 || self.has_albedo() {
+            t |= BxDFType::Diffuse as u32;
+        } else if (top | bottom) & BxDFType::Glossy as u32 != 0 {
+            t |= BxDFType::Glossy as u32;
+        }
+        if top & bottom & BxDFType::Transmission as u32 != 0 {
+            t |= BxDFType::Transmission as u32;
+        }
+
+        t
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let (mut wo, mut wi) = (*wo, *wi);
+        if self.two_sided && wo.z < 0.0 {
+            wo = -wo;
+            wi = -wi;
+        }
+
+        let reflection = BxDFType::Reflection as u32;
+        let transmission = BxDFType::Transmission as u32;
+
+        let entered_top = self.two_sided || wo.z > 0.0;
+        let enter = if entered_top { Layer::Top } else { Layer::Bottom };
+        // the walk leaves through the exit interface, toward wi
+        let (exit, non_exit) = if same_hemisphere(&wo, &wi) ^ entered_top {
+            (Layer::Bottom, Layer::Top)
+        } else {
+            (Layer::Top, Layer::Bottom)
+        };
+        let exit_z = if exit == Layer::Bottom { 0.0 } else { self.thickness };
+        let exit_specular = is_specular(self.interface_type(exit));
+        let non_exit_specular = is_specular(self.interface_type(non_exit));
+
+        let mut f = Spectrum::new(0.0, 0.0, 0.0);
+        // reflection off the entrance interface needs no walk
+        if same_hemisphere(&wo, &wi) {
+            f = self.interface_f(enter, &wo, &wi) * self.n_samples as Float;
+        }
+
+        let mut rng = RNG::init(hash_floats(&[wo.x, wo.y, wo.z, wi.x, wi.y, wi.z]));
+        let phase = HenyeyGreenstein::init(self.g);
+
+        for _ in 0..self.n_samples {
+            // the direction into the layers, and one leaving toward wi traced backwards from it
+            let wos = match self.interface_sample(enter, &wo, &mut rng, false, transmission) {
+                Some(wos) => wos,
+                None => continue
+            };
+            let wis = match self.interface_sample(exit, &wi, &mut rng, true, transmission) {
+                Some(wis) => wis,
+                None => continue
+            };
+
+            let mut beta = wos.f * abs_cos_theta(&wos.wi) / wos.pdf;
+            let mut z = if entered_top { self.thickness } else { 0.0 };
+            let mut w = wos.wi;
+
+            for depth in 0..self.max_depth {
+                // russian roulette once the path carries little
+                if depth > 3 && beta.max() < 0.25 {
+                    let q = (1.0 - beta.max()).max(0.0);
+                    if rng.uniform_float() < q {
+                        break;
+                    }
+                    beta /= 1.0 - q;
+                }
+
+                if !self.has_albedo() {
+                    // straight to the other interface
+                    z = if z == self.thickness { 0.0 } else { self.thickness };
+                    beta *= tr(self.thickness, &w);
+                } else {
+                    let dz = sample_exponential(rng.uniform_float(), 1.0 / w.z.abs());
+                    let zp = if w.z > 0.0 { z + dz } else { z - dz };
+                    if 0.0 < zp && zp < self.thickness {
+                        // scattering in the medium, connect to wis through the exit interface
+                        let wt = if exit_specular { 1.0 } else { power_heuristic(wis.pdf, phase.p(&-w, &-wis.wi)) };
+                        f += beta.component_mul(&self.albedo).component_mul(&wis.f) * phase.p(&-w, &-wis.wi) * wt *
+                            tr(zp - exit_z, &wis.wi) / wis.pdf;
+
+                        let mut wp = Vector3::new(0.0, 0.0, 0.0);
+                        let u = Point2::new(rng.uniform_float(), rng.uniform_float());
+                        let p = phase.sample_p(&-w, &mut wp, &u);
+                        if p == 0.0 || wp.z == 0.0 {
+                            continue;
+                        }
+                        // p over its pdf is one
+                        beta = beta.component_mul(&self.albedo);
+                        w = wp;
+                        z = zp;
+
+                        // and connect the phase sample through the exit interface
+                        if ((z < exit_z && w.z > 0.0) || (z > exit_z && w.z < 0.0)) && !exit_specular {
+                            let f_exit = self.interface_f(exit, &-w, &wi);
+                            if f_exit.max() > 0.0 {
+                                // wis would have found this direction traced from wi
+                                let exit_pdf = self.interface_pdf(exit, &wi, &-w, true);
+                                let wt = power_heuristic(p, exit_pdf);
+                                f += beta.component_mul(&f_exit) * tr(zp - exit_z, &w) * wt;
+                            }
+                        }
+                        continue;
+                    }
+                    z = zp.clamp(0.0, self.thickness);
+                }
+
+                if z == exit_z {
+                    // reflection back into the layers from the exit interface
+                    let bs = match self.interface_sample(exit, &-w, &mut rng, false, reflection) {
+                        Some(bs) => bs,
+                        None => break
+                    };
+                    beta = beta.component_mul(&bs.f) * abs_cos_theta(&bs.wi) / bs.pdf;
+                    w = bs.wi;
+                } else {
+                    // connect to wis through the non exit interface
+                    if !non_exit_specular {
+                        let wt = if exit_specular { 1.0 } else { power_heuristic(wis.pdf, self.interface_pdf(non_exit, &-w, &-wis.wi, false)) };
+                        f += beta.component_mul(&self.interface_f(non_exit, &-w, &-wis.wi)).component_mul(&wis.f) *
+                            abs_cos_theta(&wis.wi) * wt * tr(self.thickness, &wis.wi) / wis.pdf;
+                    }
+
+                    let bs = match self.interface_sample(non_exit, &-w, &mut rng, false, reflection) {
+                        Some(bs) => bs,
+                        None => break
+                    };
+                    beta = beta.component_mul(&bs.f) * abs_cos_theta(&bs.wi) / bs.pdf;
+                    w = bs.wi;
+
+                    // and connect the sampled direction through the exit interface
+                    if !exit_specular {
+                        let f_exit = self.interface_f(exit, &-w, &wi);
+                        if f_exit.max() > 0.0 {
+                            let wt = if non_exit_specular { 1.0 } else { power_heuristic(bs.pdf, self.interface_pdf(exit, &wi, &-w, true)) };
+                            f += beta.component_mul(&f_exit) * tr(self.thickness, &bs.wi) * wt;
+                        }
+                    }
+                }
+            }
+        }
+
+        f / self.n_samples as Float
+    }
+
+    // The returned f and pdf are those of the whole sampled path, their ratio is an unbiased
+    // weight but the pdf is not the marginal one, see pdf_is_proportional
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, sampled_type: &mut u32) -> Spectrum {
+        let mut wo = *wo;
+        let flip_wi = self.two_sided && wo.z < 0.0;
+        if flip_wi {
+            wo = -wo;
+        }
+
+        let entered_top = self.two_sided || wo.z > 0.0;
+        let enter = if entered_top { Layer::Top } else { Layer::Bottom };
+
+        // the entrance sample uses u, the rest of the walk is seeded by it
+        let mut rng = RNG::init(hash_floats(&[wo.x, wo.y, wo.z, u.x, u.y]));
+        let bs = {
+            let interface = self.interface(enter);
+            let wo_local = if enter == Layer::Top { wo } else { -wo };
+            let mut w = Vector3::new(0.0, 0.0, 0.0);
+            let mut bs_pdf = 0.0;
+            let mut bs_type = interface.bxdf.bxdf_type();
+            let f = interface.bxdf.sample_f(&wo_local, &mut w, u, &mut bs_pdf, &mut bs_type);
+            if bs_pdf == 0.0 || f.max() <= 0.0 || w.z == 0.0 {
+                return Spectrum::new(0.0, 0.0, 0.0);
+            }
+            InterfaceSample {
+                f,
+                wi: if enter == Layer::Top { w } else { -w },
+                pdf: bs_pdf,
+                sampled_type: bs_type
+            }
+        };
+
+        if same_hemisphere(&wo, &bs.wi) {
+            *wi = if flip_wi { -bs.wi } else { bs.wi };
+            *pdf = bs.pdf;
+            *sampled_type = (bs.sampled_type & !(BxDFType::Transmission as u32)) | BxDFType::Reflection as u32;
+            return bs.f;
+        }
+
+        let mut w = bs.wi;
+        let mut specular_path = is_specular(bs.sampled_type);
+        let mut f = bs.f * abs_cos_theta(&bs.wi);
+        let mut path_pdf = bs.pdf;
+        let mut z = if entered_top { self.thickness } else { 0.0 };
+        let phase = HenyeyGreenstein::init(self.g);
+
+        for depth in 0..self.max_depth {
+            let rr_beta = f.max() / path_pdf;
+            if depth > 3 && rr_beta < 0.25 {
+                let q = (1.0 - rr_beta).max(0.0);
+                if rng.uniform_float() < q {
+                    return Spectrum::new(0.0, 0.0, 0.0);
+                }
+                path_pdf *= 1.0 - q;
+            }
+            if w.z == 0.0 {
+                return Spectrum::new(0.0, 0.0, 0.0);
+            }
+
+            if self.has_albedo() {
+                let dz = sample_exponential(rng.uniform_float(), 1.0 / abs_cos_theta(&w));
+                let zp = if w.z > 0.0 { z + dz } else { z - dz };
+                if zp == z {
+                    return Spectrum::new(0.0, 0.0, 0.0);
+                }
+                if 0.0 < zp && zp < self.thickness {
+                    let mut wp = Vector3::new(0.0, 0.0, 0.0);
+                    let u = Point2::new(rng.uniform_float(), rng.uniform_float());
+                    let p = phase.sample_p(&-w, &mut wp, &u);
+                    if p == 0.0 || wp.z == 0.0 {
+                        return Spectrum::new(0.0, 0.0, 0.0);
+                    }
+                    f = f.component_mul(&self.albedo) * p;
+                    path_pdf *= p;
+                    specular_path = false;
+                    w = wp;
+                    z = zp;
+                    continue;
+                }
+                z = zp.clamp(0.0, self.thickness);
+            } else {
+                z = if z == self.thickness { 0.0 } else { self.thickness };
+                f *= tr(self.thickness, &w);
+            }
+
+            let layer = if z == 0.0 { Layer::Bottom } else { Layer::Top };
+            let bs = match self.interface_sample(layer, &-w, &mut rng, false, BxDFType::Reflection as u32 | BxDFType::Transmission as u32) {
+                Some(bs) => bs,
+                None => return Spectrum::new(0.0, 0.0, 0.0)
+            };
+            f = f.component_mul(&bs.f);
+            path_pdf *= bs.pdf;
+            specular_path &= is_specular(bs.sampled_type);
+            w = bs.wi;
+
+            // out of the layers
+            if bs.sampled_type & BxDFType::Transmission as u32 != 0 {
+                let lobe = if same_hemisphere(&wo, &w) { BxDFType::Reflection } else { BxDFType::Transmission };
+                let kind = if specular_path { BxDFType::Specular } else { BxDFType::Glossy };
+                *sampled_type = lobe as u32 | kind as u32;
+                *wi = if flip_wi { -w } else { w };
+                *pdf = path_pdf;
+                return f;
+            }
+
+            f *= abs_cos_theta(&bs.wi);
+        }
+
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    // A stochastic estimate mixed with a uniform pdf, which keeps it positive wherever f is
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        let (mut wo, mut wi) = (*wo, *wi);
+        if self.two_sided && wo.z < 0.0 {
+            wo = -wo;
+            wi = -wi;
+        }
+
+        let all = BxDFType::Reflection as u32 | BxDFType::Transmission as u32;
+        let mut rng = RNG::init(hash_floats(&[wi.x, wi.y, wi.z, wo.x, wo.y, wo.z]));
+
+        let entered_top = self.two_sided || wo.z > 0.0;
+        let mut pdf_sum = 0.0;
+        if same_hemisphere(&wo, &wi) {
+            let enter = if entered_top { Layer::Top } else { Layer::Bottom };
+            pdf_sum += self.n_samples as Float * self.interface_pdf(enter, &wo, &wi, false);
+        }
+
+        for _ in 0..self.n_samples {
+            if same_hemisphere(&wo, &wi) {
+                // transmission, reflection at the other interface, transmission
+                let (r_layer, t_layer) = if entered_top { (Layer::Bottom, Layer::Top) } else { (Layer::Top, Layer::Bottom) };
+                let wos = self.interface_sample(t_layer, &wo, &mut rng, false, BxDFType::Transmission as u32);
+                let wis = self.interface_sample(t_layer, &wi, &mut rng, true, BxDFType::Transmission as u32);
+                let (wos, wis) = match (wos, wis) {
+                    (Some(wos), Some(wis)) => (wos, wis),
+                    _ => continue
+                };
+
+                if !is_non_specular(self.interface_type(t_layer)) {
+                    pdf_sum += self.interface_pdf(r_layer, &-wos.wi, &-wis.wi, false);
+                } else if let Some(rs) = self.interface_sample(r_layer, &-wos.wi, &mut rng, false, all) {
+                    if !is_non_specular(self.interface_type(r_layer)) {
+                        pdf_sum += self.interface_pdf(t_layer, &-rs.wi, &wi, false);
+                    } else {
+                        let r_pdf = self.interface_pdf(r_layer, &-wos.wi, &-wis.wi, false);
+                        pdf_sum += power_heuristic(wis.pdf, r_pdf) * r_pdf;
+
+                        let t_pdf = self.interface_pdf(t_layer, &-rs.wi, &wi, false);
+                        pdf_sum += power_heuristic(rs.pdf, t_pdf) * t_pdf;
+                    }
+                }
+            } else {
+                // transmission through both interfaces
+                let (to_layer, ti_layer) = if entered_top { (Layer::Top, Layer::Bottom) } else { (Layer::Bottom, Layer::Top) };
+                let wos = match self.interface_sample(to_layer, &wo, &mut rng, false, BxDFType::Transmission as u32) {
+                    Some(wos) => wos,
+                    None => continue
+                };
+                let wis = match self.interface_sample(ti_layer, &wi, &mut rng, true, BxDFType::Transmission as u32) {
+                    Some(wis) => wis,
+                    None => continue
+                };
+
+                if is_specular(self.interface_type(to_layer)) {
+                    pdf_sum += self.interface_pdf(ti_layer, &-wos.wi, &wi, false);
+                } else if is_specular(self.interface_type(ti_layer)) {
+                    pdf_sum += self.interface_pdf(to_layer, &wo, &-wis.wi, false);
+                } else {
+                    pdf_sum += (self.interface_pdf(to_layer, &wo, &-wis.wi, false) + self.interface_pdf(ti_layer, &-wos.wi, &wi, false)) / 2.0;
+                }
+            }
+        }
+
+        lerp(0.9, 1.0 / (4.0 * PI), pdf_sum / self.n_samples as Float)
+    }
+
+    fn pdf_is_proportional(&self) -> bool {
+        true
+    }
+}
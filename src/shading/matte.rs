@@ -0,0 +1,40 @@
+use crate::common::*;
+
+// Purely diffuse surface, Lambertian at sigma 0 and Oren-Nayar otherwise
+#[derive(Debug, Clone)]
+pub struct MatteMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    // degrees
+    sigma: Arc<dyn Texture<Float>>
+}
+
+impl MatteMaterial {
+    pub fn init(kd: Arc<dyn Texture<Spectrum>>, sigma: Arc<dyn Texture<Float>>) -> Self {
+        Self {
+            kd,
+            sigma
+        }
+    }
+
+    pub fn init_constant(kd: &Spectrum, sigma: Float) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(*kd)), Arc::from(ConstantTexture::init(sigma)))
+    }
+}
+
+impl Material for MatteMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+
+        let r = self.kd.evaluate(isect).map(|c| c.max(0.0));
+        let sigma = self.sigma.evaluate(isect).clamp(0.0, 90.0);
+        if r != Spectrum::new(0.0, 0.0, 0.0) {
+            if sigma == 0.0 {
+                bsdf.add(Arc::from(LambertianReflection::init(&r)));
+            } else {
+                bsdf.add(Arc::from(OrenNayar::init(&r, sigma)));
+            }
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
@@ -0,0 +1,272 @@
+use std::fs;
+
+use crate::common::*;
+
+// MERL table resolution in theta half, theta diff and phi diff
+const MERL_THETA_H: usize = 90;
+const MERL_THETA_D: usize = 90;
+const MERL_PHI_D: usize = 180;
+const MERL_SIZE: usize = MERL_THETA_H * MERL_THETA_D * MERL_PHI_D;
+// the stored values are scaled per channel
+const MERL_SCALE: [Float; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+// resolution of the sampling tables, per outgoing elevation over incident elevation and azimuth
+const SAMPLE_THETA_O: usize = 32;
+const SAMPLE_THETA_I: usize = 32;
+const SAMPLE_PHI: usize = 64;
+// share of a uniform floor in the tables, so every direction stays reachable
+const SAMPLE_FLOOR: Float = 0.05;
+
+// rotates v around a unit axis
+fn rotate_vector(v: &Vector3, axis: &Vector3, angle: Float) -> Vector3 {
+    let (sin, cos) = angle.sin_cos();
+
+    v * cos + axis * (axis.dot(v) * (1.0 - cos)) + axis.cross(v) * sin
+}
+
+// An isotropic BRDF measured by MERL, tabulated over the half and difference angles of
+// Rusinkiewicz. Also keeps tables for importance sampling it
+#[derive(Debug, Clone)]
+pub struct MerlBrdf {
+    // the three channels one after the other, unscaled
+    data: Vec<Float>,
+    // per outgoing elevation bin, over (theta_i, phi_i - phi_o) bins
+    sampling: Vec<AliasTable>
+}
+
+impl MerlBrdf {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Could not read MERL file {}: {}", path, e))?;
+
+        Self::parse(&bytes)
+    }
+
+    // three little endian i32 dimensions, then the red, green and blue f64 tables
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 12 {
+            return Err("MERL file is too short for its header".to_string());
+        }
+        let dim = |i: usize| i32::from_le_bytes([bytes[4 * i], bytes[4 * i + 1], bytes[4 * i + 2], bytes[4 * i + 3]]);
+        let dims = (dim(0), dim(1), dim(2));
+        if dims != (MERL_THETA_H as i32, MERL_THETA_D as i32, MERL_PHI_D as i32) {
+            return Err(format!("Unexpected MERL dimensions {} x {} x {}", dims.0, dims.1, dims.2));
+        }
+
+        let values = &bytes[12..];
+        if values.len() != 3 * MERL_SIZE * 8 {
+            return Err(format!("MERL data has {} bytes, expected {}", values.len(), 3 * MERL_SIZE * 8));
+        }
+        let data = values.chunks_exact(8).map(|c| {
+            f64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]) as Float
+        }).collect();
+
+        Ok(Self::init(data))
+    }
+
+    // data holds the three channels of MERL_SIZE values each, as in the file
+    pub fn init(data: Vec<Float>) -> Self {
+        assert_eq!(data.len(), 3 * MERL_SIZE, "MERL data has the wrong size");
+
+        let mut brdf = Self {
+            data,
+            sampling: Vec::new()
+        };
+        brdf.sampling = (0..SAMPLE_THETA_O).map(|i| brdf.sampling_table(i)).collect();
+
+        brdf
+    }
+
+    // table index of the half and difference angles of a pair of directions above the surface
+    fn index(wo: &Vector3, wi: &Vector3) -> usize {
+        let half = (wi + wo).normalize();
+        let theta_half = half.z.clamp(-1.0, 1.0).acos();
+        let phi_half = half.y.atan2(half.x);
+
+        // wi seen from the half vector
+        let diff = rotate_vector(&rotate_vector(wi, &Vector3::new(0.0, 0.0, 1.0), -phi_half), &Vector3::new(0.0, 1.0, 0.0), -theta_half);
+        let theta_diff = diff.z.clamp(-1.0, 1.0).acos();
+        let mut phi_diff = diff.y.atan2(diff.x);
+
+        // theta half is stored with a square root spacing, denser around the highlight
+        let theta_h_index = if theta_half <= 0.0 {
+            0
+        } else {
+            ((theta_half / (PI / 2.0) * MERL_THETA_H as Float * MERL_THETA_H as Float).sqrt() as usize).min(MERL_THETA_H - 1)
+        };
+        let theta_d_index = ((theta_diff / (PI / 2.0) * MERL_THETA_D as Float) as usize).min(MERL_THETA_D - 1);
+        // reciprocity makes phi diff periodic over pi
+        if phi_diff < 0.0 {
+            phi_diff += PI;
+        }
+        let phi_d_index = ((phi_diff / PI * MERL_PHI_D as Float) as usize).min(MERL_PHI_D - 1);
+
+        phi_d_index + theta_d_index * MERL_PHI_D + theta_h_index * MERL_PHI_D * MERL_THETA_D
+    }
+
+    // both directions have to be above the surface
+    pub fn evaluate(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let i = Self::index(wo, wi);
+
+        // missing measurements are stored as negative values
+        Spectrum::new(
+            (self.data[i] * MERL_SCALE[0]).max(0.0),
+            (self.data[i + MERL_SIZE] * MERL_SCALE[1]).max(0.0),
+            (self.data[i + 2 * MERL_SIZE] * MERL_SCALE[2]).max(0.0)
+        )
+    }
+
+    fn theta_o_bin(wo: &Vector3) -> usize {
+        let theta_o = abs_cos_theta(wo).min(1.0).acos();
+
+        ((theta_o / (PI / 2.0) * SAMPLE_THETA_O as Float) as usize).min(SAMPLE_THETA_O - 1)
+    }
+
+    // bin edges of the incident elevation, as cosines
+    fn cos_theta_i_range(bin: usize) -> (Float, Float) {
+        let step = PI / 2.0 / SAMPLE_THETA_I as Float;
+
+        ((bin as Float * step).cos(), ((bin + 1) as Float * step).cos())
+    }
+
+    fn bin_solid_angle(theta_i_bin: usize) -> Float {
+        let (cos0, cos1) = Self::cos_theta_i_range(theta_i_bin);
+
+        (cos0 - cos1) * 2.0 * PI / SAMPLE_PHI as Float
+    }
+
+    // luminance of f cos over each bin, seen from the middle of an outgoing elevation bin
+    fn sampling_table(&self, theta_o_bin: usize) -> AliasTable {
+        let theta_o = (theta_o_bin as Float + 0.5) / SAMPLE_THETA_O as Float * PI / 2.0;
+        let wo = spherical_direction(theta_o.sin(), theta_o.cos(), 0.0);
+
+        let mut weights = Vec::with_capacity(SAMPLE_THETA_I * SAMPLE_PHI);
+        for t in 0..SAMPLE_THETA_I {
+            let (cos0, cos1) = Self::cos_theta_i_range(t);
+            let cos_theta_i = 0.5 * (cos0 + cos1);
+            let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+            for p in 0..SAMPLE_PHI {
+                let phi = (p as Float + 0.5) / SAMPLE_PHI as Float * 2.0 * PI;
+                let wi = spherical_direction(sin_theta_i, cos_theta_i, phi);
+                weights.push(rgb_y(&self.evaluate(&wo, &wi)) * cos_theta_i * Self::bin_solid_angle(t));
+            }
+        }
+
+        let total: Float = weights.iter().sum();
+        let total_solid_angle = 2.0 * PI;
+        for (i, w) in weights.iter_mut().enumerate() {
+            let solid_angle = Self::bin_solid_angle(i / SAMPLE_PHI);
+            *w = (1.0 - SAMPLE_FLOOR) * *w + SAMPLE_FLOOR * total * solid_angle / total_solid_angle;
+            // an all black table still has to sample something
+            if total == 0.0 {
+                *w = solid_angle;
+            }
+        }
+
+        AliasTable::init(&weights)
+    }
+
+    // samples wi above the surface for wo above it
+    pub fn sample(&self, wo: &Vector3, u: &Point2) -> (Vector3, Float) {
+        let table = &self.sampling[Self::theta_o_bin(wo)];
+        // the remapped u.x picks the azimuth, u.y the elevation within the bin
+        let (bin, pmf, u_phi) = table.sample_remapped(u.x);
+        let (theta_i_bin, phi_bin) = (bin / SAMPLE_PHI, bin % SAMPLE_PHI);
+        let (cos0, cos1) = Self::cos_theta_i_range(theta_i_bin);
+        let cos_theta_i = cos0 + (cos1 - cos0) * u.y;
+        let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+        let phi = (phi_bin as Float + u_phi) / SAMPLE_PHI as Float * 2.0 * PI + wo.y.atan2(wo.x);
+
+        (spherical_direction(sin_theta_i, cos_theta_i, phi), pmf / Self::bin_solid_angle(theta_i_bin))
+    }
+
+    pub fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        let table = &self.sampling[Self::theta_o_bin(wo)];
+
+        let theta_i = abs_cos_theta(wi).min(1.0).acos();
+        let theta_i_bin = ((theta_i / (PI / 2.0) * SAMPLE_THETA_I as Float) as usize).min(SAMPLE_THETA_I - 1);
+        let mut phi = wi.y.atan2(wi.x) - wo.y.atan2(wo.x);
+        if phi < 0.0 {
+            phi += 2.0 * PI;
+        }
+        let phi_bin = ((phi / (2.0 * PI) * SAMPLE_PHI as Float) as usize).min(SAMPLE_PHI - 1);
+
+        table.pmf(theta_i_bin * SAMPLE_PHI + phi_bin) / Self::bin_solid_angle(theta_i_bin)
+    }
+}
+
+// Reflection from measured data, sampled with the tables of the data
+#[derive(Debug, Clone)]
+pub struct MeasuredBxDF {
+    brdf: Arc<MerlBrdf>
+}
+
+impl MeasuredBxDF {
+    pub fn init(brdf: Arc<MerlBrdf>) -> Self {
+        Self {
+            brdf
+        }
+    }
+}
+
+impl BxDF for MeasuredBxDF {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        if !same_hemisphere(wo, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        // the measurement is of the upper side only
+        if wo.z < 0.0 { self.brdf.evaluate(&-wo, &-wi) } else { self.brdf.evaluate(wo, wi) }
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let flip = wo.z < 0.0;
+        let (w, w_pdf) = self.brdf.sample(&if flip { -wo } else { *wo }, u);
+        *wi = if flip { -w } else { w };
+        *pdf = w_pdf;
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+
+        if wo.z < 0.0 { self.brdf.pdf(&-wo, &-wi) } else { self.brdf.pdf(wo, wi) }
+    }
+}
+
+// A measured MERL BRDF as a material, for reference renders next to analytic ones
+#[derive(Debug, Clone)]
+pub struct MeasuredMaterial {
+    brdf: Arc<MerlBrdf>
+}
+
+impl MeasuredMaterial {
+    pub fn init(brdf: Arc<MerlBrdf>) -> Self {
+        Self {
+            brdf
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        Ok(Self::init(Arc::from(MerlBrdf::from_file(path)?)))
+    }
+}
+
+impl Material for MeasuredMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+        bsdf.add(Arc::from(MeasuredBxDF::init(self.brdf.clone())));
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
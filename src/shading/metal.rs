@@ -0,0 +1,92 @@
+use crate::common::*;
+
+// Rough conductor with a GGX distribution
+#[derive(Debug, Clone)]
+pub struct MetalMaterial {
+    eta: Arc<dyn Texture<Spectrum>>,
+    k: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    // anisotropic roughness along dpdu and dpdv, roughness is used when unset
+    u_roughness: Option<Arc<dyn Texture<Float>>>,
+    v_roughness: Option<Arc<dyn Texture<Float>>>,
+    // roughness is in [0, 1] and gets mapped to alpha, otherwise it is alpha
+    remap_roughness: bool,
+    // add back the energy lost to multiple scattering between microfacets
+    energy_compensation: bool,
+    // nm, an interference film on the surface when set
+    film_thickness: Option<Arc<dyn Texture<Float>>>,
+    film_eta: Arc<dyn Texture<Float>>
+}
+
+impl MetalMaterial {
+    pub fn init(eta: Arc<dyn Texture<Spectrum>>, k: Arc<dyn Texture<Spectrum>>, roughness: Arc<dyn Texture<Float>>,
+        u_roughness: Option<Arc<dyn Texture<Float>>>, v_roughness: Option<Arc<dyn Texture<Float>>>, remap_roughness: bool) -> Self {
+        Self {
+            eta,
+            k,
+            roughness,
+            u_roughness,
+            v_roughness,
+            remap_roughness,
+            energy_compensation: true,
+            film_thickness: None,
+            film_eta: Arc::from(ConstantTexture::init(1.33))
+        }
+    }
+
+    // a metal from the table in metals.rs, None for unknown names
+    pub fn init_named(name: &str, roughness: Float) -> Option<Self> {
+        let (eta, k) = metal_eta_k(name)?;
+
+        Some(Self::init(Arc::from(ConstantTexture::init(eta)), Arc::from(ConstantTexture::init(k)),
+            Arc::from(ConstantTexture::init(roughness)), None, None, true))
+    }
+
+    pub fn set_energy_compensation(&mut self, energy_compensation: bool) {
+        self.energy_compensation = energy_compensation;
+    }
+
+    // a thin film of this thickness in nm and index of refraction over the surface
+    pub fn set_thin_film(&mut self, thickness: Arc<dyn Texture<Float>>, eta: Arc<dyn Texture<Float>>) {
+        self.film_thickness = Some(thickness);
+        self.film_eta = eta;
+    }
+}
+
+impl Material for MetalMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+
+        let mut u_rough = self.u_roughness.as_ref().unwrap_or(&self.roughness).evaluate(isect);
+        let mut v_rough = self.v_roughness.as_ref().unwrap_or(&self.roughness).evaluate(isect);
+        if self.remap_roughness {
+            u_rough = roughness_to_alpha(u_rough);
+            v_rough = roughness_to_alpha(v_rough);
+        }
+
+        let eta = self.eta.evaluate(isect);
+        let k = self.k.evaluate(isect);
+        let fresnel: Arc<dyn Fresnel> = match &self.film_thickness {
+            Some(thickness) => {
+                let film = ThinFilm::init(thickness.evaluate(isect), self.film_eta.evaluate(isect));
+                Arc::from(FresnelThinFilmConductor::init(1.0, &eta, &k, film))
+            }
+            None => Arc::from(FresnelConductor::init(&Spectrum::new(1.0, 1.0, 1.0), &eta, &k))
+        };
+        let one = Spectrum::new(1.0, 1.0, 1.0);
+        let distribution = TrowbridgeReitzDistribution::init(u_rough, v_rough, true);
+        if self.energy_compensation {
+            let alpha = (u_rough * v_rough).sqrt();
+            let missing = conductor_missing_energy(alpha);
+            if missing > 1e-3 {
+                // averaged over the bare metal, a film's spectral Fresnel is far too costly to
+                // average at every shading point and only tints the single scattering lobe
+                let bare = FresnelConductor::init(&one, &eta, &k);
+                bsdf.add_weighted(Arc::from(ConductorCompensation::init(&one, alpha, &bare)), missing);
+            }
+        }
+        bsdf.add(Arc::from(MicrofacetReflection::init(&one, Arc::from(distribution), fresnel)));
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
@@ -0,0 +1,13 @@
+use crate::common::*;
+
+// Complex index of refraction (eta, k) of common metals, averaged over the red, green and blue
+// parts of the visible spectrum
+pub fn metal_eta_k(name: &str) -> Option<(Spectrum, Spectrum)> {
+    match name.to_lowercase().as_str() {
+        "gold" | "au" => Some((Spectrum::new(0.143, 0.374, 1.442), Spectrum::new(3.983, 2.385, 1.603))),
+        "silver" | "ag" => Some((Spectrum::new(0.155, 0.117, 0.138), Spectrum::new(4.828, 3.122, 2.147))),
+        "copper" | "cu" => Some((Spectrum::new(0.200, 0.924, 1.102), Spectrum::new(3.912, 2.452, 2.142))),
+        "aluminum" | "aluminium" | "al" => Some((Spectrum::new(1.657, 0.880, 0.521), Spectrum::new(9.224, 6.270, 4.837))),
+        _ => None
+    }
+}
@@ -0,0 +1,273 @@
+use crate::common::*;
+
+// Maps a user facing roughness in [0, 1] to the alpha of the distributions, so that roughness
+// changes the look roughly linearly
+pub fn roughness_to_alpha(roughness: Float) -> Float {
+    let x = roughness.max(1e-3).ln();
+
+    1.62142 + 0.819955 * x + 0.1734 * x * x + 0.0171201 * x * x * x + 0.000640711 * x * x * x * x
+}
+
+// Distribution of microfacet normals wh around +z of the shading frame
+pub trait MicrofacetDistribution: Debug {
+    // differential area of microfacets with normal wh
+    fn d(&self, wh: &Vector3) -> Float;
+    // invisible masked area per visible area, in direction w
+    fn lambda(&self, w: &Vector3) -> Float;
+    fn sample_wh(&self, wo: &Vector3, u: &Point2) -> Vector3;
+    fn sample_visible_area(&self) -> bool;
+
+    // Smith masking
+    fn g1(&self, w: &Vector3) -> Float {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Smith masking-shadowing
+    fn g(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    fn pdf(&self, wo: &Vector3, wh: &Vector3) -> Float {
+        if self.sample_visible_area() {
+            self.d(wh) * self.g1(wo) * wo.dot(wh).abs() / abs_cos_theta(wo)
+        } else {
+            self.d(wh) * abs_cos_theta(wh)
+        }
+    }
+}
+
+// azimuth of a sampled normal for anisotropic alphas
+fn sample_anisotropic_phi(alpha_x: Float, alpha_y: Float, u: Float) -> Float {
+    let mut phi = (alpha_y / alpha_x * (2.0 * PI * u + 0.5 * PI).tan()).atan();
+    if u > 0.5 {
+        phi += PI;
+    }
+
+    phi
+}
+
+#[derive(Debug, Clone)]
+pub struct BeckmannDistribution {
+    alpha_x: Float,
+    alpha_y: Float,
+    sample_visible_area: bool
+}
+
+impl BeckmannDistribution {
+    pub fn init(alpha_x: Float, alpha_y: Float, sample_visible_area: bool) -> Self {
+        Self {
+            alpha_x: alpha_x.max(1e-3),
+            alpha_y: alpha_y.max(1e-3),
+            sample_visible_area
+        }
+    }
+
+    // slopes of a visible normal for the unit roughness distribution, seen from cos_theta_i
+    fn sample11(cos_theta_i: Float, u1: Float, u2: Float) -> (Float, Float) {
+        // normal incidence is the plain distribution
+        if cos_theta_i > 0.9999 {
+            let r = (-(1.0 - u1).ln()).sqrt();
+            let phi = 2.0 * PI * u2;
+            return (r * phi.cos(), r * phi.sin());
+        }
+
+        let sin_theta_i = (1.0 - cos_theta_i * cos_theta_i).max(0.0).sqrt();
+        let tan_theta_i = sin_theta_i / cos_theta_i;
+        let cot_theta_i = 1.0 / tan_theta_i;
+
+        // invert the slope cdf with bisection and Newton steps, starting from a fitted guess
+        let mut a = -1.0;
+        let mut c = erf(cot_theta_i);
+        let sample_x = u1.max(1e-6);
+
+        let theta_i = cos_theta_i.acos();
+        let fit = 1.0 + theta_i * (-0.876 + theta_i * (0.4265 - 0.0594 * theta_i));
+        let mut b = c - (1.0 + c) * (1.0 - sample_x).powf(fit);
+
+        let sqrt_pi_inv = 1.0 / PI.sqrt();
+        let normalization = 1.0 / (1.0 + c + sqrt_pi_inv * tan_theta_i * (-cot_theta_i * cot_theta_i).exp());
+
+        for _ in 0..9 {
+            if !(b >= a && b <= c) {
+                b = 0.5 * (a + c);
+            }
+
+            let inv_erf = erf_inv(b);
+            let value = normalization * (1.0 + b + sqrt_pi_inv * tan_theta_i * (-inv_erf * inv_erf).exp()) - sample_x;
+            let derivative = normalization * (1.0 - inv_erf * tan_theta_i);
+            if value.abs() < 1e-5 {
+                break;
+            }
+
+            if value > 0.0 {
+                c = b;
+            } else {
+                a = b;
+            }
+            b -= value / derivative;
+        }
+
+        (erf_inv(b), erf_inv(2.0 * u2.max(1e-6) - 1.0))
+    }
+
+    fn sample_visible(&self, wi: &Vector3, u: &Point2) -> Vector3 {
+        // stretch to the unit roughness configuration
+        let wi_stretched = Vector3::new(self.alpha_x * wi.x, self.alpha_y * wi.y, wi.z).normalize();
+
+        let (slope_x, slope_y) = Self::sample11(cos_theta(&wi_stretched), u.x, u.y);
+
+        // rotate and unstretch
+        let cos_phi = cos_phi(&wi_stretched);
+        let sin_phi = sin_phi(&wi_stretched);
+        let rotated_x = cos_phi * slope_x - sin_phi * slope_y;
+        let rotated_y = sin_phi * slope_x + cos_phi * slope_y;
+
+        Vector3::new(-self.alpha_x * rotated_x, -self.alpha_y * rotated_y, 1.0).normalize()
+    }
+}
+
+impl MicrofacetDistribution for BeckmannDistribution {
+    fn d(&self, wh: &Vector3) -> Float {
+        let tan2_theta = tan2_theta(wh);
+        if tan2_theta.is_infinite() {
+            return 0.0;
+        }
+        let cos4_theta = cos2_theta(wh) * cos2_theta(wh);
+
+        (-tan2_theta * (cos2_phi(wh) / (self.alpha_x * self.alpha_x) + sin2_phi(wh) / (self.alpha_y * self.alpha_y))).exp() /
+            (PI * self.alpha_x * self.alpha_y * cos4_theta)
+    }
+
+    fn lambda(&self, w: &Vector3) -> Float {
+        let abs_tan_theta = tan_theta(w).abs();
+        if abs_tan_theta.is_infinite() {
+            return 0.0;
+        }
+
+        let alpha = (cos2_phi(w) * self.alpha_x * self.alpha_x + sin2_phi(w) * self.alpha_y * self.alpha_y).sqrt();
+        let a = 1.0 / (alpha * abs_tan_theta);
+        if a >= 1.6 {
+            return 0.0;
+        }
+
+        // rational fit of the erfc form
+        (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+    }
+
+    fn sample_wh(&self, wo: &Vector3, u: &Point2) -> Vector3 {
+        if self.sample_visible_area {
+            let flip = wo.z < 0.0;
+            let wh = self.sample_visible(&if flip { -wo } else { *wo }, u);
+            return if flip { -wh } else { wh };
+        }
+
+        let log_sample = (1.0 - u.x).ln();
+        let (tan2_theta, phi) = if self.alpha_x == self.alpha_y {
+            (-self.alpha_x * self.alpha_x * log_sample, u.y * 2.0 * PI)
+        } else {
+            let phi = sample_anisotropic_phi(self.alpha_x, self.alpha_y, u.y);
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let tan2_theta = -log_sample / (cos_phi * cos_phi / (self.alpha_x * self.alpha_x) + sin_phi * sin_phi / (self.alpha_y * self.alpha_y));
+            (tan2_theta, phi)
+        };
+
+        let cos_theta = 1.0 / (1.0 + tan2_theta).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let wh = spherical_direction(sin_theta, cos_theta, phi);
+
+        if same_hemisphere(wo, &wh) { wh } else { -wh }
+    }
+
+    fn sample_visible_area(&self) -> bool {
+        self.sample_visible_area
+    }
+}
+
+// Trowbridge-Reitz, also known as GGX. Its longer tails give metals a softer highlight
+#[derive(Debug, Clone)]
+pub struct TrowbridgeReitzDistribution {
+    alpha_x: Float,
+    alpha_y: Float,
+    sample_visible_area: bool
+}
+
+impl TrowbridgeReitzDistribution {
+    pub fn init(alpha_x: Float, alpha_y: Float, sample_visible_area: bool) -> Self {
+        Self {
+            alpha_x: alpha_x.max(1e-3),
+            alpha_y: alpha_y.max(1e-3),
+            sample_visible_area
+        }
+    }
+
+    // Heitz 2018, sampling the visible normals as a projected hemisphere
+    fn sample_visible(&self, wo: &Vector3, u: &Point2) -> Vector3 {
+        let vh = Vector3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+
+        let len2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len2 > 0.0 { Vector3::new(-vh.y, vh.x, 0.0) / len2.sqrt() } else { Vector3::new(1.0, 0.0, 0.0) };
+        let t2 = vh.cross(&t1);
+
+        let r = u.x.sqrt();
+        let phi = 2.0 * PI * u.y;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        Vector3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+impl MicrofacetDistribution for TrowbridgeReitzDistribution {
+    fn d(&self, wh: &Vector3) -> Float {
+        let tan2_theta = tan2_theta(wh);
+        if tan2_theta.is_infinite() {
+            return 0.0;
+        }
+        let cos4_theta = cos2_theta(wh) * cos2_theta(wh);
+        let e = (cos2_phi(wh) / (self.alpha_x * self.alpha_x) + sin2_phi(wh) / (self.alpha_y * self.alpha_y)) * tan2_theta;
+
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos4_theta * (1.0 + e) * (1.0 + e))
+    }
+
+    fn lambda(&self, w: &Vector3) -> Float {
+        let abs_tan_theta = tan_theta(w).abs();
+        if abs_tan_theta.is_infinite() {
+            return 0.0;
+        }
+
+        let alpha = (cos2_phi(w) * self.alpha_x * self.alpha_x + sin2_phi(w) * self.alpha_y * self.alpha_y).sqrt();
+        let alpha2_tan2_theta = (alpha * abs_tan_theta) * (alpha * abs_tan_theta);
+
+        (-1.0 + (1.0 + alpha2_tan2_theta).sqrt()) / 2.0
+    }
+
+    fn sample_wh(&self, wo: &Vector3, u: &Point2) -> Vector3 {
+        if self.sample_visible_area {
+            let flip = wo.z < 0.0;
+            let wh = self.sample_visible(&if flip { -wo } else { *wo }, u);
+            return if flip { -wh } else { wh };
+        }
+
+        let (tan2_theta, phi) = if self.alpha_x == self.alpha_y {
+            (self.alpha_x * self.alpha_x * u.x / (1.0 - u.x), u.y * 2.0 * PI)
+        } else {
+            let phi = sample_anisotropic_phi(self.alpha_x, self.alpha_y, u.y);
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let alpha2 = 1.0 / (cos_phi * cos_phi / (self.alpha_x * self.alpha_x) + sin_phi * sin_phi / (self.alpha_y * self.alpha_y));
+            (alpha2 * u.x / (1.0 - u.x), phi)
+        };
+
+        let cos_theta = 1.0 / (1.0 + tan2_theta).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let wh = spherical_direction(sin_theta, cos_theta, phi);
+
+        if same_hemisphere(wo, &wh) { wh } else { -wh }
+    }
+
+    fn sample_visible_area(&self) -> bool {
+        self.sample_visible_area
+    }
+}
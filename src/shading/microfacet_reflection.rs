@@ -0,0 +1,69 @@
+use crate::common::*;
+
+// Torrance-Sparrow reflection from a rough surface of perfect mirror microfacets
+#[derive(Debug, Clone)]
+pub struct MicrofacetReflection {
+    r: Spectrum,
+    distribution: Arc<dyn MicrofacetDistribution>,
+    fresnel: Arc<dyn Fresnel>
+}
+
+impl MicrofacetReflection {
+    pub fn init(r: &Spectrum, distribution: Arc<dyn MicrofacetDistribution>, fresnel: Arc<dyn Fresnel>) -> Self {
+        Self {
+            r: *r,
+            distribution,
+            fresnel
+        }
+    }
+}
+
+impl BxDF for MicrofacetReflection {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let cos_theta_o = abs_cos_theta(wo);
+        let cos_theta_i = abs_cos_theta(wi);
+        let wh = wi + wo;
+        if cos_theta_i == 0.0 || cos_theta_o == 0.0 || wh == Vector3::new(0.0, 0.0, 0.0) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+        let wh = wh.normalize();
+
+        let f = self.fresnel.evaluate(wi.dot(&face_forward(&wh, &Vector3::new(0.0, 0.0, 1.0))));
+
+        self.r.component_mul(&f) * self.distribution.d(&wh) * self.distribution.g(wo, wi) / (4.0 * cos_theta_i * cos_theta_o)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let wh = self.distribution.sample_wh(wo, u);
+        if wo.dot(&wh) < 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        *wi = reflect(wo, &wh);
+        if !same_hemisphere(wo, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        *pdf = self.distribution.pdf(wo, &wh) / (4.0 * wo.dot(&wh));
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        if !same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+
+        let wh = (wo + wi).normalize();
+
+        self.distribution.pdf(wo, &wh) / (4.0 * wo.dot(&wh))
+    }
+}
@@ -0,0 +1,117 @@
+use crate::common::*;
+
+// Walter et al. 2007, refraction through a rough dielectric boundary. eta_a is outside (+z) and
+// eta_b inside
+#[derive(Debug, Clone)]
+pub struct MicrofacetTransmission {
+    t: Spectrum,
+    distribution: Arc<dyn MicrofacetDistribution>,
+    eta_a: Float,
+    eta_b: Float,
+    fresnel: Arc<dyn Fresnel>,
+    mode: TransportMode
+}
+
+impl MicrofacetTransmission {
+    pub fn init(t: &Spectrum, distribution: Arc<dyn MicrofacetDistribution>, eta_a: Float, eta_b: Float, mode: TransportMode) -> Self {
+        Self {
+            t: *t,
+            distribution,
+            eta_a,
+            eta_b,
+            fresnel: Arc::from(FresnelDielectric::init(eta_a, eta_b)),
+            mode
+        }
+    }
+
+    // replaces the Fresnel term of the interface, which has to describe a non absorbing one
+    pub fn set_fresnel(&mut self, fresnel: Arc<dyn Fresnel>) {
+        self.fresnel = fresnel;
+    }
+}
+
+impl BxDF for MicrofacetTransmission {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Transmission as u32 | BxDFType::Glossy as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        if same_hemisphere(wo, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let cos_theta_o = cos_theta(wo);
+        let cos_theta_i = cos_theta(wi);
+        if cos_theta_i == 0.0 || cos_theta_o == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        // generalized half vector for refraction
+        let eta = if cos_theta_o > 0.0 { self.eta_b / self.eta_a } else { self.eta_a / self.eta_b };
+        let mut wh = (wo + wi * eta).normalize();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+
+        // both directions on the same side of the microfacet is not a refraction
+        if wo.dot(&wh) * wi.dot(&wh) > 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        // microfacets facing away from either direction are never sampled, so they don't scatter
+        if wh.dot(wi) * cos_theta_i < 0.0 || wh.dot(wo) * cos_theta_o < 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let f = self.fresnel.evaluate(wo.dot(&wh));
+
+        let sqrt_denom = wo.dot(&wh) + eta * wi.dot(&wh);
+        let factor = if self.mode == TransportMode::Radiance { 1.0 / eta } else { 1.0 };
+
+        (Spectrum::new(1.0, 1.0, 1.0) - f).component_mul(&self.t) *
+            (self.distribution.d(&wh) * self.distribution.g(wo, wi) * eta * eta * wi.dot(&wh).abs() * wo.dot(&wh).abs() * factor * factor /
+            (cos_theta_i * cos_theta_o * sqrt_denom * sqrt_denom)).abs()
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        if wo.z == 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let wh = self.distribution.sample_wh(wo, u);
+        if wo.dot(&wh) < 0.0 {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let eta = if cos_theta(wo) > 0.0 { self.eta_a / self.eta_b } else { self.eta_b / self.eta_a };
+        if !refract(wo, &wh, eta, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        *pdf = self.pdf(wo, wi);
+
+        self.f(wo, wi)
+    }
+
+    fn pdf(&self, wo: &Vector3, wi: &Vector3) -> Float {
+        if same_hemisphere(wo, wi) {
+            return 0.0;
+        }
+
+        let eta = if cos_theta(wo) > 0.0 { self.eta_b / self.eta_a } else { self.eta_a / self.eta_b };
+        let mut wh = (wo + wi * eta).normalize();
+        if wh.z < 0.0 {
+            wh = -wh;
+        }
+
+        if wo.dot(&wh) * wi.dot(&wh) > 0.0 || wh.dot(wi) * cos_theta(wi) < 0.0 || wh.dot(wo) * cos_theta(wo) < 0.0 {
+            return 0.0;
+        }
+
+        // change of variables from the half vector to the refracted direction
+        let sqrt_denom = wo.dot(&wh) + eta * wi.dot(&wh);
+        let dwh_dwi = ((eta * eta * wi.dot(&wh)) / (sqrt_denom * sqrt_denom)).abs();
+
+        self.distribution.pdf(wo, &wh) * dwh_dwi
+    }
+}
@@ -0,0 +1,47 @@
+use crate::common::*;
+
+// Blends two materials by a mask, 0 is all of the first and 1 all of the second. Each shading
+// point uses one of them, picked by a hash of its position, so the BSDF stays as small as the
+// chosen material's and every path reaching the point sees the same choice
+#[derive(Debug, Clone)]
+pub struct MixMaterial {
+    materials: [Arc<dyn Material>; 2],
+    amount: Arc<dyn Texture<Float>>
+}
+
+impl MixMaterial {
+    pub fn init(m1: Arc<dyn Material>, m2: Arc<dyn Material>, amount: Arc<dyn Texture<Float>>) -> Self {
+        Self {
+            materials: [m1, m2],
+            amount
+        }
+    }
+
+    pub fn init_constant(m1: Arc<dyn Material>, m2: Arc<dyn Material>, amount: Float) -> Self {
+        Self::init(m1, m2, Arc::from(ConstantTexture::init(amount)))
+    }
+
+    pub fn choose_material(&self, isect: &SurfaceInteraction) -> &Arc<dyn Material> {
+        let amount = self.amount.evaluate(isect);
+        if amount <= 0.0 {
+            return &self.materials[0];
+        }
+        if amount >= 1.0 {
+            return &self.materials[1];
+        }
+
+        // the top 24 bits of the hash as a uniform value in [0, 1)
+        let p = isect.interaction.p;
+        let u = (hash_floats(&[p.x, p.y, p.z]) >> 40) as Float / (1u64 << 24) as Float;
+
+        if amount < u { &self.materials[0] } else { &self.materials[1] }
+    }
+}
+
+impl Material for MixMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, allow_multiple_lobes: bool) {
+        let material = self.choose_material(isect).clone();
+
+        material.compute_scattering_function(isect, mode, allow_multiple_lobes);
+    }
+}
@@ -1,5 +1,71 @@
+pub mod bxdf;
 pub mod bsdf;
 pub mod material;
+pub mod displacement;
+pub mod bssrdf;
 
-pub use bsdf::{BSDF, BSSRDF};
-pub use material::Material;
\ No newline at end of file
+pub use bxdf::*;
+pub use bsdf::{BSDF, MAX_BXDFS};
+pub use material::Material;
+pub use displacement::{Displacement, DisplacedMaterial, NormalMap, bump, normal_map};
+pub use bssrdf::{BSSRDF, BSSRDFTable, TabulatedBSSRDF, SeparableBSSRDFAdapter, compute_beam_diffusion_bssrdf, subsurface_from_diffuse, fresnel_moment1, fresnel_moment2};
+
+pub mod fresnel;
+pub mod microfacet;
+pub mod lambertian;
+pub mod oren_nayar;
+pub mod microfacet_reflection;
+pub mod microfacet_transmission;
+pub mod specular;
+pub mod coated_diffuse;
+pub mod disney;
+pub mod dielectric;
+pub mod layered;
+pub mod hair;
+pub mod measured;
+pub mod random_walk;
+pub mod thin_dielectric;
+pub mod energy_compensation;
+pub mod cloth;
+pub mod thin_film;
+
+pub use fresnel::{Fresnel, FresnelConductor, FresnelDielectric, FresnelNoOp, fr_dielectric, fr_conductor};
+pub use microfacet::{MicrofacetDistribution, BeckmannDistribution, TrowbridgeReitzDistribution, roughness_to_alpha};
+pub use lambertian::{LambertianReflection, LambertianTransmission};
+pub use oren_nayar::OrenNayar;
+pub use microfacet_reflection::MicrofacetReflection;
+pub use microfacet_transmission::MicrofacetTransmission;
+pub use specular::{SpecularReflection, SpecularTransmission, FresnelSpecular};
+pub use coated_diffuse::{CoatedDiffuseReflection, CoatedDiffuseMaterial};
+pub use disney::{DisneyDiffuse, DisneyFakeSS, DisneyRetro, DisneySheen, DisneyClearcoat, DisneyFresnel, DisneyMicrofacetDistribution, DisneyMaterial};
+pub use dielectric::DielectricBxDF;
+pub use layered::{LayerInterface, LayeredBxDF};
+pub use hair::{HairBSDF, HairMaterial, hair_sigma_a_from_concentration, hair_sigma_a_from_reflectance};
+pub use measured::{MerlBrdf, MeasuredBxDF, MeasuredMaterial};
+pub use random_walk::RandomWalkBSSRDF;
+pub use thin_dielectric::{ThinDielectricBxDF, ThinDielectricMaterial};
+pub use energy_compensation::{ConductorCompensation, DielectricCompensation, conductor_missing_energy, dielectric_missing_energy};
+pub use cloth::{CharlieSheen, AshikhminVelvet, ClothMaterial};
+pub use thin_film::{ThinFilm, FresnelThinFilmDielectric, FresnelThinFilmConductor};
+
+pub mod metals;
+pub mod matte;
+pub mod metal;
+pub mod glass;
+pub mod plastic;
+pub mod coated_conductor;
+pub mod mix;
+pub mod subsurface;
+pub mod translucent;
+pub mod graph;
+
+pub use metals::metal_eta_k;
+pub use matte::MatteMaterial;
+pub use metal::MetalMaterial;
+pub use glass::GlassMaterial;
+pub use plastic::PlasticMaterial;
+pub use coated_conductor::CoatedConductorMaterial;
+pub use mix::MixMaterial;
+pub use subsurface::{SubsurfaceMaterial, subsurface_coefficients};
+pub use translucent::TranslucentMaterial;
+pub use graph::{GraphType, GraphInput, GraphNode, ShadingGraph, GraphMaterial};
\ No newline at end of file
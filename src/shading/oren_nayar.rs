@@ -0,0 +1,49 @@
+use crate::common::*;
+
+// Diffuse reflection from a surface of V-shaped microfacets, sigma is the standard deviation of
+// the facet angle in degrees
+#[derive(Debug, Clone)]
+pub struct OrenNayar {
+    r: Spectrum,
+    a: Float,
+    b: Float
+}
+
+impl OrenNayar {
+    pub fn init(r: &Spectrum, sigma: Float) -> Self {
+        let sigma = sigma.to_radians();
+        let sigma2 = sigma * sigma;
+
+        Self {
+            r: *r,
+            a: 1.0 - (sigma2 / (2.0 * (sigma2 + 0.33))),
+            b: 0.45 * sigma2 / (sigma2 + 0.09)
+        }
+    }
+}
+
+impl BxDF for OrenNayar {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Diffuse as u32
+    }
+
+    fn f(&self, wo: &Vector3, wi: &Vector3) -> Spectrum {
+        let sin_theta_i = sin_theta(wi);
+        let sin_theta_o = sin_theta(wo);
+
+        // cos(phi_i - phi_o) term
+        let mut max_cos = 0.0;
+        if sin_theta_i > 1e-4 && sin_theta_o > 1e-4 {
+            let d_cos = cos_phi(wi) * cos_phi(wo) + sin_phi(wi) * sin_phi(wo);
+            max_cos = d_cos.max(0.0);
+        }
+
+        let (sin_alpha, tan_beta) = if abs_cos_theta(wi) > abs_cos_theta(wo) {
+            (sin_theta_o, sin_theta_i / abs_cos_theta(wi))
+        } else {
+            (sin_theta_i, sin_theta_o / abs_cos_theta(wo))
+        };
+
+        self.r * (1.0 / PI) * (self.a + self.b * max_cos * sin_alpha * tan_beta)
+    }
+}
@@ -0,0 +1,51 @@
+use crate::common::*;
+
+// Diffuse base with a glossy dielectric highlight on top
+#[derive(Debug, Clone)]
+pub struct PlasticMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    ks: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    remap_roughness: bool
+}
+
+impl PlasticMaterial {
+    pub fn init(kd: Arc<dyn Texture<Spectrum>>, ks: Arc<dyn Texture<Spectrum>>, roughness: Arc<dyn Texture<Float>>, remap_roughness: bool) -> Self {
+        Self {
+            kd,
+            ks,
+            roughness,
+            remap_roughness
+        }
+    }
+
+    pub fn init_constant(kd: &Spectrum, ks: &Spectrum, roughness: Float) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(*kd)), Arc::from(ConstantTexture::init(*ks)),
+            Arc::from(ConstantTexture::init(roughness)), true)
+    }
+}
+
+impl Material for PlasticMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        let mut bsdf = BSDF::init(isect, 1.0);
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+
+        let kd = self.kd.evaluate(isect).map(|c| c.max(0.0));
+        if kd != black {
+            bsdf.add(Arc::from(LambertianReflection::init(&kd)));
+        }
+
+        let ks = self.ks.evaluate(isect).map(|c| c.max(0.0));
+        if ks != black {
+            let fresnel = FresnelDielectric::init(1.0, 1.5);
+            let mut rough = self.roughness.evaluate(isect);
+            if self.remap_roughness {
+                rough = roughness_to_alpha(rough);
+            }
+            let distribution = TrowbridgeReitzDistribution::init(rough, rough, true);
+            bsdf.add(Arc::from(MicrofacetReflection::init(&ks, Arc::from(distribution), Arc::from(fresnel))));
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::common::*;
+use crate::shading::bssrdf::material_id;
+
+// Subsurface transport found by tracing a path through the interior of a closed surface as a
+// participating medium until it leaves through the surface again. Unlike diffusion profiles this
+// holds up on thin and concave parts, but it has no closed form so it can only be sampled
+#[derive(Debug, Clone)]
+pub struct RandomWalkBSSRDF {
+    po: Interaction,
+    ns: Vector3,
+    eta: Float,
+    mode: TransportMode,
+    material: usize,
+    medium: Arc<dyn Medium>,
+    max_depth: u32
+}
+
+impl RandomWalkBSSRDF {
+    pub fn init(po: &SurfaceInteraction, material: &dyn Material, mode: TransportMode, eta: Float, medium: Arc<dyn Medium>,
+        max_depth: u32) -> Self {
+        Self {
+            po: po.interaction.clone(),
+            ns: po.shading.n,
+            eta,
+            mode,
+            material: material_id(material),
+            medium,
+            max_depth
+        }
+    }
+}
+
+impl BSSRDF for RandomWalkBSSRDF {
+    // the walk can only be sampled
+    fn s(&self, _pi: &SurfaceInteraction, _wi: &Vector3) -> Spectrum {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    // returns the throughput of the walk with a pdf of 1
+    fn sample_s(&self, scene: &dyn Primitive, u1: Float, u2: &Point2, si: &mut SurfaceInteraction, pdf: &mut Float) -> Spectrum {
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+        let p = self.po.p;
+        let mut rng = RNG::init(hash_floats(&[u1, u2.x, u2.y, p.x, p.y, p.z]));
+
+        // the walk starts along wo refracted into the surface, with the light the boundary lets through
+        let n = face_forward(&self.ns, &self.po.wo);
+        let mut wt = Vector3::new(0.0, 0.0, 0.0);
+        if !refract(&self.po.wo, &n, 1.0 / self.eta, &mut wt) {
+            return black;
+        }
+        let mut ray = self.po.spawn_ray(&wt);
+        ray.medium = Some(self.medium.clone());
+
+        let mut beta = Spectrum::new(1.0, 1.0, 1.0) * (1.0 - fr_dielectric(self.po.wo.dot(&n), 1.0, self.eta));
+        for depth in 0..self.max_depth {
+            // an open surface lets the walk escape
+            let mut hit = SurfaceInteraction::new();
+            if !scene.intersect(&mut ray, &mut hit) {
+                return black;
+            }
+
+            let mut mi = None;
+            beta = beta.component_mul(&self.medium.sample(&ray, &mut rng, &mut mi));
+            if beta == black {
+                return black;
+            }
+
+            match mi {
+                Some(mi) => {
+                    let mut wi = Vector3::new(0.0, 0.0, 0.0);
+                    let u = Point2::new(rng.uniform_float(), rng.uniform_float());
+                    mi.phase.sample_p(&mi.interaction.wo, &mut wi, &u);
+                    ray = mi.interaction.spawn_ray(&wi);
+                    ray.medium = Some(self.medium.clone());
+
+                    // russian roulette once the walk is long enough and has lost some of its energy
+                    if depth > 3 && beta.max() < 1.0 {
+                        let q = (1.0 - beta.max()).max(0.05);
+                        if rng.uniform_float() < q {
+                            return black;
+                        }
+                        beta /= 1.0 - q;
+                    }
+                }
+                None => {
+                    // anything else inside the object ends the walk
+                    let same_material = hit.primitive.as_ref()
+                        .and_then(|p| p.get_material())
+                        .is_some_and(|m| material_id(m.as_ref()) == self.material);
+                    if !same_material {
+                        return black;
+                    }
+
+                    // light leaves on the side the walk arrived from inside
+                    if hit.shading.n.dot(&ray.d) < 0.0 {
+                        hit.shading.n = -hit.shading.n;
+                        hit.interaction.n = -hit.interaction.n;
+                    }
+
+                    // the boundary reflects part of the walk back inside, all of it past the critical angle
+                    let cos_inside = hit.shading.n.dot(&ray.d);
+                    if rng.uniform_float() < fr_dielectric(cos_inside, self.eta, 1.0) {
+                        let wr = reflect(&-ray.d, &hit.shading.n);
+                        ray = hit.interaction.spawn_ray(&wr);
+                        ray.medium = Some(self.medium.clone());
+                        continue;
+                    }
+
+                    let mut bsdf = BSDF::init(&hit, 1.0);
+                    bsdf.add(Arc::from(SeparableBSSRDFAdapter::init(self.eta, self.mode)));
+                    hit.bsdf = Some(Arc::from(bsdf));
+                    hit.interaction.wo = hit.shading.n;
+                    *si = hit;
+                    *pdf = 1.0;
+
+                    return beta;
+                }
+            }
+        }
+
+        black
+    }
+}
@@ -0,0 +1,162 @@
+use crate::common::*;
+
+// Perfect mirror reflection, scaled by a Fresnel term
+#[derive(Debug, Clone)]
+pub struct SpecularReflection {
+    r: Spectrum,
+    fresnel: Arc<dyn Fresnel>
+}
+
+impl SpecularReflection {
+    pub fn init(r: &Spectrum, fresnel: Arc<dyn Fresnel>) -> Self {
+        Self {
+            r: *r,
+            fresnel
+        }
+    }
+}
+
+impl BxDF for SpecularReflection {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Specular as u32
+    }
+
+    // a delta distribution, only sample_f can return it
+    fn f(&self, _wo: &Vector3, _wi: &Vector3) -> Spectrum {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, _u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        *wi = Vector3::new(-wo.x, -wo.y, wo.z);
+        *pdf = 1.0;
+
+        self.fresnel.evaluate(cos_theta(wi)).component_mul(&self.r) / abs_cos_theta(wi)
+    }
+
+    fn pdf(&self, _wo: &Vector3, _wi: &Vector3) -> Float {
+        0.0
+    }
+}
+
+// Perfect refraction through a dielectric boundary, eta_a is outside (+z) and eta_b inside
+#[derive(Debug, Clone)]
+pub struct SpecularTransmission {
+    t: Spectrum,
+    eta_a: Float,
+    eta_b: Float,
+    fresnel: Arc<dyn Fresnel>,
+    mode: TransportMode
+}
+
+impl SpecularTransmission {
+    pub fn init(t: &Spectrum, eta_a: Float, eta_b: Float, mode: TransportMode) -> Self {
+        Self {
+            t: *t,
+            eta_a,
+            eta_b,
+            fresnel: Arc::from(FresnelDielectric::init(eta_a, eta_b)),
+            mode
+        }
+    }
+
+    // replaces the Fresnel term of the interface, which has to describe a non absorbing one
+    pub fn set_fresnel(&mut self, fresnel: Arc<dyn Fresnel>) {
+        self.fresnel = fresnel;
+    }
+}
+
+impl BxDF for SpecularTransmission {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Transmission as u32 | BxDFType::Specular as u32
+    }
+
+    fn f(&self, _wo: &Vector3, _wi: &Vector3) -> Spectrum {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, _u: &Point2, pdf: &mut Float, _sampled_type: &mut u32) -> Spectrum {
+        let entering = cos_theta(wo) > 0.0;
+        let (eta_i, eta_t) = if entering { (self.eta_a, self.eta_b) } else { (self.eta_b, self.eta_a) };
+
+        if !refract(wo, &face_forward(&Vector3::new(0.0, 0.0, 1.0), wo), eta_i / eta_t, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+        *pdf = 1.0;
+
+        let mut ft = self.t.component_mul(&(Spectrum::new(1.0, 1.0, 1.0) - self.fresnel.evaluate(cos_theta(wi))));
+        // radiance is compressed into a smaller solid angle, importance is not
+        if self.mode == TransportMode::Radiance {
+            ft *= (eta_i * eta_i) / (eta_t * eta_t);
+        }
+
+        ft / abs_cos_theta(wi)
+    }
+
+    fn pdf(&self, _wo: &Vector3, _wi: &Vector3) -> Float {
+        0.0
+    }
+}
+
+// Specular reflection and transmission together, choosing between them by the Fresnel term
+#[derive(Debug, Clone)]
+pub struct FresnelSpecular {
+    r: Spectrum,
+    t: Spectrum,
+    eta_a: Float,
+    eta_b: Float,
+    mode: TransportMode
+}
+
+impl FresnelSpecular {
+    pub fn init(r: &Spectrum, t: &Spectrum, eta_a: Float, eta_b: Float, mode: TransportMode) -> Self {
+        Self {
+            r: *r,
+            t: *t,
+            eta_a,
+            eta_b,
+            mode
+        }
+    }
+}
+
+impl BxDF for FresnelSpecular {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Transmission as u32 | BxDFType::Specular as u32
+    }
+
+    fn f(&self, _wo: &Vector3, _wi: &Vector3) -> Spectrum {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, sampled_type: &mut u32) -> Spectrum {
+        let f = fr_dielectric(cos_theta(wo), self.eta_a, self.eta_b);
+
+        if u.x < f {
+            *wi = Vector3::new(-wo.x, -wo.y, wo.z);
+            *sampled_type = BxDFType::Reflection as u32 | BxDFType::Specular as u32;
+            *pdf = f;
+
+            return self.r * f / abs_cos_theta(wi);
+        }
+
+        let entering = cos_theta(wo) > 0.0;
+        let (eta_i, eta_t) = if entering { (self.eta_a, self.eta_b) } else { (self.eta_b, self.eta_a) };
+
+        if !refract(wo, &face_forward(&Vector3::new(0.0, 0.0, 1.0), wo), eta_i / eta_t, wi) {
+            return Spectrum::new(0.0, 0.0, 0.0);
+        }
+
+        let mut ft = self.t * (1.0 - f);
+        if self.mode == TransportMode::Radiance {
+            ft *= (eta_i * eta_i) / (eta_t * eta_t);
+        }
+        *sampled_type = BxDFType::Transmission as u32 | BxDFType::Specular as u32;
+        *pdf = 1.0 - f;
+
+        ft / abs_cos_theta(wi)
+    }
+
+    fn pdf(&self, _wo: &Vector3, _wi: &Vector3) -> Float {
+        0.0
+    }
+}
@@ -0,0 +1,123 @@
+use crate::common::*;
+
+// Scattering coefficients (sigma_a, sigma_s) in mm^-1 of measured media from Jensen et al. 2001,
+// sigma_s already reduced so they go with g = 0
+pub fn subsurface_coefficients(name: &str) -> Option<(Spectrum, Spectrum)> {
+    match name.to_lowercase().as_str() {
+        "apple" => Some((Spectrum::new(0.0030, 0.0034, 0.046), Spectrum::new(2.29, 2.39, 1.97))),
+        "chicken1" => Some((Spectrum::new(0.015, 0.077, 0.19), Spectrum::new(0.15, 0.21, 0.38))),
+        "chicken2" => Some((Spectrum::new(0.018, 0.088, 0.20), Spectrum::new(0.19, 0.25, 0.32))),
+        "cream" => Some((Spectrum::new(0.0002, 0.0028, 0.0163), Spectrum::new(7.38, 5.47, 3.15))),
+        "ketchup" => Some((Spectrum::new(0.061, 0.97, 1.45), Spectrum::new(0.18, 0.07, 0.03))),
+        "marble" => Some((Spectrum::new(0.0021, 0.0041, 0.0071), Spectrum::new(2.19, 2.62, 3.00))),
+        "potato" => Some((Spectrum::new(0.0024, 0.0090, 0.12), Spectrum::new(0.68, 0.70, 0.55))),
+        "skimmilk" => Some((Spectrum::new(0.0014, 0.0025, 0.0142), Spectrum::new(0.70, 1.22, 1.90))),
+        "skin1" => Some((Spectrum::new(0.032, 0.17, 0.48), Spectrum::new(0.74, 0.88, 1.01))),
+        "skin2" => Some((Spectrum::new(0.013, 0.070, 0.145), Spectrum::new(1.09, 1.59, 1.79))),
+        "spectralon" => Some((Spectrum::new(0.0, 0.0, 0.0), Spectrum::new(11.6, 20.4, 14.9))),
+        "wholemilk" => Some((Spectrum::new(0.0011, 0.0024, 0.014), Spectrum::new(2.55, 3.21, 3.77))),
+        _ => None
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Coefficients {
+    Scattering {
+        sigma_a: Arc<dyn Texture<Spectrum>>,
+        sigma_s: Arc<dyn Texture<Spectrum>>
+    },
+    // the albedo seen from outside and the mean free path
+    Diffuse {
+        reflectance: Arc<dyn Texture<Spectrum>>,
+        mfp: Arc<dyn Texture<Spectrum>>
+    }
+}
+
+// Translucent material such as skin, marble or wax. Light crosses a dielectric boundary and
+// scatters below the surface, described by a tabulated BSSRDF or traced by random walks
+#[derive(Debug, Clone)]
+pub struct SubsurfaceMaterial {
+    coefficients: Coefficients,
+    // multiplies the coefficients, to convert them to scene units
+    scale: Float,
+    eta: Float,
+    g: Float,
+    boundary: GlassMaterial,
+    table: Arc<BSSRDFTable>,
+    // trace the interior as a medium instead of looking up the diffusion profile
+    random_walk_depth: Option<u32>
+}
+
+impl SubsurfaceMaterial {
+    pub fn init(sigma_a: Arc<dyn Texture<Spectrum>>, sigma_s: Arc<dyn Texture<Spectrum>>, scale: Float, eta: Float, g: Float) -> Self {
+        Self::init_coefficients(Coefficients::Scattering { sigma_a, sigma_s }, scale, eta, g)
+    }
+
+    // the medium that gives the reflectance with light spreading over about mfp
+    pub fn init_diffuse(reflectance: Arc<dyn Texture<Spectrum>>, mfp: Arc<dyn Texture<Spectrum>>, eta: Float, g: Float) -> Self {
+        Self::init_coefficients(Coefficients::Diffuse { reflectance, mfp }, 1.0, eta, g)
+    }
+
+    // a medium from subsurface_coefficients, scale gives scene units per mm
+    pub fn init_named(name: &str, scale: Float, eta: Float) -> Option<Self> {
+        let (sigma_a, sigma_s) = subsurface_coefficients(name)?;
+
+        Some(Self::init(Arc::from(ConstantTexture::init(sigma_a)), Arc::from(ConstantTexture::init(sigma_s)), 1.0 / scale, eta, 0.0))
+    }
+
+    fn init_coefficients(coefficients: Coefficients, scale: Float, eta: Float, g: Float) -> Self {
+        let mut table = BSSRDFTable::init(100, 64);
+        compute_beam_diffusion_bssrdf(g, eta, &mut table);
+
+        let one = Spectrum::new(1.0, 1.0, 1.0);
+        Self {
+            coefficients,
+            scale,
+            eta,
+            g,
+            boundary: GlassMaterial::init_constant(&one, &one, 0.0, eta),
+            table: Arc::from(table),
+            random_walk_depth: None
+        }
+    }
+
+    // the reflection and transmission of the boundary, smooth by default
+    pub fn set_boundary(&mut self, kr: Arc<dyn Texture<Spectrum>>, kt: Arc<dyn Texture<Spectrum>>, u_roughness: Arc<dyn Texture<Float>>,
+        v_roughness: Arc<dyn Texture<Float>>, remap_roughness: bool) {
+        self.boundary = GlassMaterial::init(kr, kt, u_roughness, v_roughness, Arc::from(ConstantTexture::init(self.eta)), remap_roughness);
+    }
+
+    // random walks through the interior for thin features where diffusion breaks down. The
+    // medium inside the primitive's MediumInterface is used when there is one, otherwise a
+    // homogeneous medium with the material's coefficients
+    pub fn set_random_walk(&mut self, max_depth: u32) {
+        self.random_walk_depth = Some(max_depth);
+    }
+}
+
+impl Material for SubsurfaceMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, allow_multiple_lobes: bool) {
+        self.boundary.compute_scattering_function(isect, mode, allow_multiple_lobes);
+
+        let (sigma_a, sigma_s) = match &self.coefficients {
+            Coefficients::Scattering { sigma_a, sigma_s } => (sigma_a.evaluate(isect), sigma_s.evaluate(isect)),
+            Coefficients::Diffuse { reflectance, mfp } => {
+                let reflectance = reflectance.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+                subsurface_from_diffuse(&self.table, &reflectance, &mfp.evaluate(isect))
+            }
+        };
+
+        let sigma_a = sigma_a * self.scale;
+        let sigma_s = sigma_s * self.scale;
+
+        isect.bssrdf = match self.random_walk_depth {
+            Some(max_depth) => {
+                let medium = isect.interaction.medium_interface.as_ref()
+                    .and_then(|mi| mi.inside.clone())
+                    .unwrap_or_else(|| Arc::from(HomogeneousMedium::init(&sigma_a, &sigma_s, self.g)));
+                Some(Arc::from(RandomWalkBSSRDF::init(isect, self, mode, self.eta, medium, max_depth)))
+            }
+            None => Some(Arc::from(TabulatedBSSRDF::init(isect, self, mode, self.eta, &sigma_a, &sigma_s, self.table.clone())))
+        };
+    }
+}
@@ -0,0 +1,90 @@
+use crate::common::*;
+
+// Thin dielectric slab such as a window pane. Light bounces between the two parallel faces
+// without refracting sideways, so it leaves either mirrored or straight through
+#[derive(Debug, Clone)]
+pub struct ThinDielectricBxDF {
+    eta: Float
+}
+
+impl ThinDielectricBxDF {
+    pub fn init(eta: Float) -> Self {
+        Self {
+            eta
+        }
+    }
+
+    // reflectance and transmittance of the slab, summed over every internal bounce
+    fn reflectance_transmittance(&self, wo: &Vector3) -> (Float, Float) {
+        let mut r = fr_dielectric(abs_cos_theta(wo), 1.0, self.eta);
+        let mut t = 1.0 - r;
+        if r < 1.0 {
+            r += t * t * r / (1.0 - r * r);
+            t = 1.0 - r;
+        }
+
+        (r, t)
+    }
+}
+
+impl BxDF for ThinDielectricBxDF {
+    fn bxdf_type(&self) -> u32 {
+        BxDFType::Reflection as u32 | BxDFType::Transmission as u32 | BxDFType::Specular as u32
+    }
+
+    fn f(&self, _wo: &Vector3, _wi: &Vector3) -> Spectrum {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+
+    fn sample_f(&self, wo: &Vector3, wi: &mut Vector3, u: &Point2, pdf: &mut Float, sampled_type: &mut u32) -> Spectrum {
+        let (r, t) = self.reflectance_transmittance(wo);
+
+        if u.x < r {
+            *wi = Vector3::new(-wo.x, -wo.y, wo.z);
+            *sampled_type = BxDFType::Reflection as u32 | BxDFType::Specular as u32;
+            *pdf = r;
+
+            let f = r / abs_cos_theta(wi);
+            return Spectrum::new(f, f, f);
+        }
+
+        *wi = -wo;
+        *sampled_type = BxDFType::Transmission as u32 | BxDFType::Specular as u32;
+        *pdf = t;
+
+        let f = t / abs_cos_theta(wi);
+        Spectrum::new(f, f, f)
+    }
+
+    fn pdf(&self, _wo: &Vector3, _wi: &Vector3) -> Float {
+        0.0
+    }
+}
+
+// Window glass and other thin sheets, the light that passes is not displaced or focused
+#[derive(Debug, Clone)]
+pub struct ThinDielectricMaterial {
+    index: Arc<dyn Texture<Float>>
+}
+
+impl ThinDielectricMaterial {
+    pub fn init(index: Arc<dyn Texture<Float>>) -> Self {
+        Self {
+            index
+        }
+    }
+
+    pub fn init_constant(index: Float) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(index)))
+    }
+}
+
+impl Material for ThinDielectricMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, _mode: TransportMode, _allow_multiple_lobes: bool) {
+        // the path leaves into the medium it came from
+        let mut bsdf = BSDF::init(isect, 1.0);
+        bsdf.add(Arc::from(ThinDielectricBxDF::init(self.index.evaluate(isect))));
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
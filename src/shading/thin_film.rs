@@ -0,0 +1,232 @@
+use std::ops::{Add, Div, Mul, Sub};
+use std::sync::OnceLock;
+
+use crate::common::*;
+
+// wavelengths in nm the film reflectance is integrated over
+const FILM_LAMBDA_MIN: Float = 380.0;
+const FILM_LAMBDA_MAX: Float = 780.0;
+const FILM_LAMBDA_STEP: Float = 5.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: Float,
+    im: Float
+}
+
+impl Complex {
+    fn new(re: Float, im: Float) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: Float) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    fn norm_sqr(self) -> Float {
+        self.re * self.re + self.im * self.im
+    }
+
+    // principal root, with a non negative real part
+    fn sqrt(self) -> Self {
+        let r = self.norm_sqr().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e^(i self)
+    fn exp_i(self) -> Self {
+        let m = (-self.im).exp();
+
+        Self::new(m * self.re.cos(), m * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    fn add(self, o: Self) -> Self { Self::new(self.re + o.re, self.im + o.im) }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self { Self::new(self.re - o.re, self.im - o.im) }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self { Self::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re) }
+}
+
+impl Div for Complex {
+    type Output = Self;
+    fn div(self, o: Self) -> Self {
+        let d = o.norm_sqr();
+        Self::new((self.re * o.re + self.im * o.im) / d, (self.im * o.re - self.re * o.im) / d)
+    }
+}
+
+// Reflectance at one wavelength of a film on a substrate of index n + i k, lit from a medium of
+// index eta_i. The Airy sum of the waves bouncing inside the film, for each polarization
+fn airy_reflectance(cos_theta_i: Float, eta_i: Float, film: &ThinFilm, substrate: Complex, lambda: Float) -> Float {
+    let one = Complex::real(1.0);
+    let n0 = Complex::real(eta_i);
+    let n1 = Complex::real(film.eta);
+    let n2 = substrate;
+
+    // n cos theta in each layer, imaginary for evanescent and absorbed waves
+    let sin2 = Complex::real(eta_i * eta_i * (1.0 - cos_theta_i * cos_theta_i).max(0.0));
+    let q0 = Complex::real(eta_i * cos_theta_i);
+    let q1 = (n1 * n1 - sin2).sqrt();
+    let q2 = (n2 * n2 - sin2).sqrt();
+
+    let r_s = |qa: Complex, qb: Complex| (qa - qb) / (qa + qb);
+    let r_p = |na: Complex, nb: Complex, qa: Complex, qb: Complex| (nb * nb * qa - na * na * qb) / (nb * nb * qa + na * na * qb);
+
+    // phase picked up by a round trip through the film
+    let phase = (Complex::real(4.0 * PI * film.thickness / lambda) * q1).exp_i();
+    let airy = |r01: Complex, r12: Complex| ((r01 + r12 * phase) / (one + r01 * r12 * phase)).norm_sqr();
+
+    let rs = airy(r_s(q0, q1), r_s(q1, q2));
+    let rp = airy(r_p(n0, n1, q0, q1), r_p(n1, n2, q1, q2));
+
+    (0.5 * (rs + rp)).clamp(0.0, 1.0)
+}
+
+// colour matching functions at the film wavelengths, and the XYZ of a constant 1
+struct FilmCmf {
+    samples: Vec<(Float, Vector3)>,
+    white: Vector3
+}
+
+fn film_cmf() -> &'static FilmCmf {
+    static TABLE: OnceLock<FilmCmf> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let n = ((FILM_LAMBDA_MAX - FILM_LAMBDA_MIN) / FILM_LAMBDA_STEP) as usize + 1;
+        let samples: Vec<(Float, Vector3)> = (0..n).map(|i| {
+            let lambda = FILM_LAMBDA_MIN + i as Float * FILM_LAMBDA_STEP;
+            (lambda, Vector3::new(cie_x(lambda), cie_y(lambda), cie_z(lambda)))
+        }).collect();
+        let white = samples.iter().fold(Vector3::new(0.0, 0.0, 0.0), |w, (_, cmf)| w + cmf);
+
+        FilmCmf { samples, white }
+    })
+}
+
+// RGB of a reflectance spectrum under an equal energy light, balanced so that a constant 1 gives
+// white by scaling XYZ to the white point of the RGB space
+fn reflectance_to_rgb<F: Fn(Float) -> Float>(r: F) -> Spectrum {
+    let cmf = film_cmf();
+    let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+    for (lambda, bar) in &cmf.samples {
+        xyz += bar * r(*lambda);
+    }
+    let white = cmf.white;
+
+    let (mut wx, mut wy, mut wz) = (0.0, 0.0, 0.0);
+    to_xyz(&Spectrum::new(1.0, 1.0, 1.0), &mut wx, &mut wy, &mut wz);
+
+    from_xyz(xyz.x * wx / white.x, xyz.y * wy / white.y, xyz.z * wz / white.z)
+}
+
+// a value per wavelength from one given per RGB channel, placed at the dominant wavelengths of the
+// primaries and linearly interpolated between them
+fn rgb_at_wavelength(s: &Spectrum, lambda: Float) -> Float {
+    const B: Float = 465.0;
+    const G: Float = 550.0;
+    const R: Float = 610.0;
+    if lambda <= B {
+        s.z
+    } else if lambda <= G {
+        lerp((lambda - B) / (G - B), s.z, s.y)
+    } else if lambda <= R {
+        lerp((lambda - G) / (R - G), s.y, s.x)
+    } else {
+        s.x
+    }
+}
+
+// A dielectric layer much thinner than the coherence length of light, coating a surface. Light
+// reflected at its two sides interferes and gives the colours of soap bubbles and oil slicks
+#[derive(Debug, Clone, Copy)]
+pub struct ThinFilm {
+    // nm
+    pub thickness: Float,
+    pub eta: Float
+}
+
+impl ThinFilm {
+    pub fn init(thickness: Float, eta: Float) -> Self {
+        Self {
+            thickness: thickness.max(0.0),
+            eta: eta.max(1.0)
+        }
+    }
+}
+
+// FresnelDielectric with a film on the interface. The film doesn't absorb, so 1 - F per channel
+// is still the transmission
+#[derive(Debug, Clone)]
+pub struct FresnelThinFilmDielectric {
+    eta_i: Float,
+    eta_t: Float,
+    film: ThinFilm
+}
+
+impl FresnelThinFilmDielectric {
+    pub fn init(eta_i: Float, eta_t: Float, film: ThinFilm) -> Self {
+        Self {
+            eta_i,
+            eta_t,
+            film
+        }
+    }
+}
+
+impl Fresnel for FresnelThinFilmDielectric {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        // from the eta_t side the film is lit through the other medium
+        let (eta_i, eta_t) = if cos_i < 0.0 { (self.eta_t, self.eta_i) } else { (self.eta_i, self.eta_t) };
+        let cos_i = cos_i.abs().min(1.0);
+
+        reflectance_to_rgb(|lambda| airy_reflectance(cos_i, eta_i, &self.film, Complex::real(eta_t), lambda)).map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+// FresnelConductor with a film on the metal, as on anodized or heat tinted metals. The conductor
+// index is interpolated over wavelength from its RGB values, and the result is rescaled by how far
+// that guess is from FresnelConductor so a vanishing film gives the bare metal back
+#[derive(Debug, Clone)]
+pub struct FresnelThinFilmConductor {
+    eta_i: Float,
+    eta_t: Spectrum,
+    k: Spectrum,
+    film: ThinFilm
+}
+
+impl FresnelThinFilmConductor {
+    pub fn init(eta_i: Float, eta_t: &Spectrum, k: &Spectrum, film: ThinFilm) -> Self {
+        Self {
+            eta_i,
+            eta_t: *eta_t,
+            k: *k,
+            film
+        }
+    }
+}
+
+impl Fresnel for FresnelThinFilmConductor {
+    fn evaluate(&self, cos_i: Float) -> Spectrum {
+        let cos_i = cos_i.abs().min(1.0);
+        let substrate = |lambda| Complex::new(rgb_at_wavelength(&self.eta_t, lambda), rgb_at_wavelength(&self.k, lambda));
+
+        let bare_film = ThinFilm::init(0.0, self.film.eta);
+        let filmed = reflectance_to_rgb(|lambda| airy_reflectance(cos_i, self.eta_i, &self.film, substrate(lambda), lambda));
+        let bare = reflectance_to_rgb(|lambda| airy_reflectance(cos_i, self.eta_i, &bare_film, substrate(lambda), lambda));
+        let eta_i = Spectrum::new(self.eta_i, self.eta_i, self.eta_i);
+        let exact = fr_conductor(cos_i, &eta_i, &self.eta_t, &self.k);
+
+        filmed.zip_zip_map(&bare, &exact, |f, b, e| if b > 1e-4 { f * e / b } else { f }).map(|c| c.clamp(0.0, 1.0))
+    }
+}
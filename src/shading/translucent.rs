@@ -0,0 +1,77 @@
+use crate::common::*;
+
+// Thin diffusing sheet such as a leaf or a paper lampshade, with diffuse and glossy lobes on both
+// sides scaled by how much is reflected and how much transmitted
+#[derive(Debug, Clone)]
+pub struct TranslucentMaterial {
+    kd: Arc<dyn Texture<Spectrum>>,
+    ks: Arc<dyn Texture<Spectrum>>,
+    roughness: Arc<dyn Texture<Float>>,
+    reflect: Arc<dyn Texture<Spectrum>>,
+    transmit: Arc<dyn Texture<Spectrum>>,
+    remap_roughness: bool
+}
+
+impl TranslucentMaterial {
+    pub fn init(kd: Arc<dyn Texture<Spectrum>>, ks: Arc<dyn Texture<Spectrum>>, roughness: Arc<dyn Texture<Float>>,
+        reflect: Arc<dyn Texture<Spectrum>>, transmit: Arc<dyn Texture<Spectrum>>, remap_roughness: bool) -> Self {
+        Self {
+            kd,
+            ks,
+            roughness,
+            reflect,
+            transmit,
+            remap_roughness
+        }
+    }
+
+    pub fn init_constant(kd: &Spectrum, ks: &Spectrum, roughness: Float, reflect: &Spectrum, transmit: &Spectrum) -> Self {
+        Self::init(Arc::from(ConstantTexture::init(*kd)), Arc::from(ConstantTexture::init(*ks)),
+            Arc::from(ConstantTexture::init(roughness)), Arc::from(ConstantTexture::init(*reflect)),
+            Arc::from(ConstantTexture::init(*transmit)), true)
+    }
+}
+
+impl Material for TranslucentMaterial {
+    fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, _allow_multiple_lobes: bool) {
+        let eta = 1.5;
+        let mut bsdf = BSDF::init(isect, eta);
+        let black = Spectrum::new(0.0, 0.0, 0.0);
+
+        let r = self.reflect.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+        let t = self.transmit.evaluate(isect).map(|c| c.clamp(0.0, 1.0));
+        if r == black && t == black {
+            isect.bsdf = Some(Arc::from(bsdf));
+            return;
+        }
+
+        let kd = self.kd.evaluate(isect).map(|c| c.max(0.0));
+        if kd != black {
+            if r != black {
+                bsdf.add(Arc::from(LambertianReflection::init(&r.component_mul(&kd))));
+            }
+            if t != black {
+                bsdf.add(Arc::from(LambertianTransmission::init(&t.component_mul(&kd))));
+            }
+        }
+
+        let ks = self.ks.evaluate(isect).map(|c| c.max(0.0));
+        if ks != black {
+            let mut rough = self.roughness.evaluate(isect);
+            if self.remap_roughness {
+                rough = roughness_to_alpha(rough);
+            }
+            let distribution: Arc<dyn MicrofacetDistribution> = Arc::from(TrowbridgeReitzDistribution::init(rough, rough, true));
+
+            if r != black {
+                let fresnel = Arc::from(FresnelDielectric::init(1.0, eta));
+                bsdf.add(Arc::from(MicrofacetReflection::init(&r.component_mul(&ks), distribution.clone(), fresnel)));
+            }
+            if t != black {
+                bsdf.add(Arc::from(MicrofacetTransmission::init(&t.component_mul(&ks), distribution, 1.0, eta, mode)));
+            }
+        }
+
+        isect.bsdf = Some(Arc::from(bsdf));
+    }
+}
@@ -1,5 +1,6 @@
 use crate::common::*;
 
-pub trait AreaLight: Debug {
-
-}
\ No newline at end of file
+pub trait AreaLight: Light {
+    // radiance leaving the point intr on the light's shape in direction w
+    fn l(&self, intr: &Interaction, w: &Vector3) -> Spectrum;
+}
@@ -75,6 +75,8 @@ struct LinearBVHNode {
     second_child_offset: Option<usize>, // nterior node
     n_primitives: usize,
     axis: usize,
+    // or of the ray visibility of everything below, lets traversal skip hidden subtrees
+    ray_visibility: u32
 }
 
 impl LinearBVHNode {
@@ -84,7 +86,8 @@ impl LinearBVHNode {
             primitives_offset: None,
             second_child_offset: None,
             n_primitives: 0usize,
-            axis: 0usize
+            axis: 0usize,
+            ray_visibility: 0u32
         }
     }
 }
@@ -299,15 +302,23 @@ impl BVHAccel {
         if node.n_primitives > 0 {
             self.nodes[cur_idx].primitives_offset = Some(node.first_prim_offset);
             self.nodes[cur_idx].n_primitives = node.n_primitives;
+
+            let prims = &self.primitives[node.first_prim_offset..(node.first_prim_offset + node.n_primitives)];
+            self.nodes[cur_idx].ray_visibility = prims.iter().fold(0u32, |v, p| v | p.ray_visibility());
         } else {
             self.nodes[cur_idx].axis = node.split_axis;
             self.nodes[cur_idx].n_primitives = 0;
+            let mut ray_visibility = 0u32;
             if let Some(left_child) = &node.children[0] {
-                self.flatten_tree(left_child, offset);
+                let left_idx = self.flatten_tree(left_child, offset);
+                ray_visibility |= self.nodes[left_idx].ray_visibility;
             }
             if let Some(right_child) = &node.children[1] {
-                self.nodes[cur_idx].second_child_offset = Some(self.flatten_tree(right_child, offset));
+                let right_idx = self.flatten_tree(right_child, offset);
+                self.nodes[cur_idx].second_child_offset = Some(right_idx);
+                ray_visibility |= self.nodes[right_idx].ray_visibility;
             }
+            self.nodes[cur_idx].ray_visibility = ray_visibility;
         }
 
         cur_idx
@@ -332,6 +343,13 @@ impl Primitive for BVHAccel {
         self.nodes[0].bounds
     }
 
+    fn ray_visibility(&self) -> u32 {
+        match self.nodes.first() {
+            Some(root) => root.ray_visibility,
+            None => 0u32
+        }
+    }
+
     fn intersect(&self, ray: &mut Ray, isect: &mut SurfaceInteraction) -> bool {
         let mut hit: bool = false;
 
@@ -342,14 +360,26 @@ impl Primitive for BVHAccel {
         let mut to_visit_offset = 0usize;
         let mut current_node_idx = 0usize;
         let mut nodes_to_visit = [0usize; 64];  // custom stack
+        let ray_type = ray.ray_type as u32;
         loop {
             let node = &self.nodes[current_node_idx];
-            if node.bounds.intersect_p_with_inv(ray, &inv_dir, dir_is_neg) {
+            if node.ray_visibility & ray_type != 0 && node.bounds.intersect_p_with_inv(ray, &inv_dir, dir_is_neg) {
                 if node.n_primitives > 0 {
                     for i in 0..node.n_primitives {
                         if let Some(prim_offset) = &node.primitives_offset {
-                            if self.primitives[prim_offset + i].intersect(ray, isect) {
+                            let primitive = &self.primitives[prim_offset + i];
+                            if primitive.ray_visibility() & ray_type == 0 {
+                                continue;
+                            }
+                            // nested aggregates have already set the primitive that was hit
+                            let prev_primitive = isect.primitive.take();
+                            if primitive.intersect(ray, isect) {
+                                if isect.primitive.is_none() {
+                                    isect.primitive = Some(primitive.clone());
+                                }
                                 hit = true;
+                            } else {
+                                isect.primitive = prev_primitive;
                             }
                         }
                     }
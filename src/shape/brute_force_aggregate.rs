@@ -44,8 +44,14 @@ impl Primitive for BruteForceAggregate {
         for i in 0..self.prims.len(){
             let mut its = SurfaceInteraction::new();
             let prim = &self.prims[i];
+            if prim.ray_visibility() & ray.ray_type as u32 == 0 {
+                continue;
+            }
 
             if prim.intersect(ray, &mut its) {
+                if its.primitive.is_none() {
+                    its.primitive = Some(prim.clone());
+                }
                 hit = true;
                 if its.interaction.time < cur_t {
                     cur_t = its.interaction.time;
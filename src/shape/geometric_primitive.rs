@@ -6,6 +6,10 @@ pub struct GeometricPrimitive {
     material: Option<Arc<dyn Material>>,
     area_light: Option<Arc<dyn AreaLight>>,
     medium_interface: Option<MediumInterface>,
+
+    // used by light linking
+    name: Option<Arc<str>>,
+    ray_visibility: u32
 }
 
 impl GeometricPrimitive {
@@ -14,9 +18,32 @@ impl GeometricPrimitive {
             shape,
             material,
             area_light,
-            medium_interface
+            medium_interface,
+
+            name: None,
+            ray_visibility: RAY_VISIBILITY_ALL
         }
     }
+
+    pub fn name(&self) -> Option<Arc<str>> {
+        self.name.clone()
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = Some(Arc::from(name));
+    }
+
+    pub fn set_visible_to(&mut self, ray_type: RayType, visible: bool) {
+        if visible {
+            self.ray_visibility |= ray_type as u32;
+        } else {
+            self.ray_visibility &= !(ray_type as u32);
+        }
+    }
+
+    pub fn is_visible_to(&self, ray_type: RayType) -> bool {
+        self.ray_visibility & ray_type as u32 != 0
+    }
 }
 
 impl Primitive for GeometricPrimitive {
@@ -32,6 +59,10 @@ impl Primitive for GeometricPrimitive {
         self.material.clone()
     }
 
+    fn ray_visibility(&self) -> u32 {
+        self.ray_visibility
+    }
+
     fn intersect(&self, ray: &mut Ray, isect: &mut SurfaceInteraction) -> bool {
         let mut t_hit: Float = 0.0;
         if !self.shape.intersect(ray, &mut t_hit, isect, false) {
@@ -40,6 +71,7 @@ impl Primitive for GeometricPrimitive {
 
         (*ray).t_max = t_hit;
         (*isect).shape = Some(self.shape.clone());
+        isect.object_name = self.name.clone();
 
         // set medium of isect
         if let Some(mi) = &self.medium_interface {
@@ -9,5 +9,9 @@ pub trait Primitive: Debug {
     fn intersect_p(&self, ray: &mut Ray) -> bool;
     fn get_area_light(&self) -> Option<Arc<dyn AreaLight>>;
     fn get_material(&self) -> Option<Arc<dyn Material>>;
+    // bitwise or of the RayTypes that can hit this primitive
+    fn ray_visibility(&self) -> u32 {
+        RAY_VISIBILITY_ALL
+    }
     fn compute_scattering_function(&self, isect: &mut SurfaceInteraction, mode: TransportMode, allow_multiple_lobes: bool);
 }
\ No newline at end of file
@@ -71,7 +71,7 @@ impl Shape for Sphere {
 
         let a = ray.d.x * ray.d.x + ray.d.y * ray.d.y + ray.d.z * ray.d.z;
         let b = 2.0 * (ray.d.x * ray.o.x + ray.d.y * ray.o.y + ray.d.z * ray.o.z);
-        let c = ray.o.x * ray.o.x + ray.o.y * ray.o.y + ray.o.z * ray.o.z;
+        let c = ray.o.x * ray.o.x + ray.o.y * ray.o.y + ray.o.z * ray.o.z - self.radius * self.radius;
 
         let mut t0: Float = 0.0;
         let mut t1: Float = 0.0;
@@ -164,22 +164,85 @@ impl Shape for Sphere {
     }
 
     fn pdf(&self, _: &Interaction) -> Float {
-        // TODO
         1.0 / self.area()
     }
 
-    fn pdf_ref(&self, _reference: &Interaction, _wi: &Vector3) -> Float {
-        // TODO
-        1.0 / self.area()
+    // uniform over the solid angle the sphere subtends when reference is outside it
+    fn pdf_ref(&self, reference: &Interaction, wi: &Vector3) -> Float {
+        let p_center = self.object_to_world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+        let p_origin = offset_ray_origin(&reference.p, &reference.p_error, &reference.n, &(p_center - reference.p));
+
+        if (p_origin - p_center).norm_squared() <= self.radius * self.radius {
+            // inside, convert the area density of the hit point to solid angle
+            let ray = reference.spawn_ray(wi);
+            let mut t_hit = 0.0;
+            let mut isect = SurfaceInteraction::new();
+            if !self.intersect(&ray, &mut t_hit, &mut isect, false) {
+                return 0.0;
+            }
+
+            let p_hit = self.object_to_world.transform_point(&isect.interaction.p);
+            let n_hit = apply_transform_to_normal(&isect.interaction.n, &self.object_to_world).normalize();
+            let pdf = (reference.p - p_hit).norm_squared() / (n_hit.dot(&(-wi)).abs() * self.area());
+
+            return if pdf.is_infinite() { 0.0 } else { pdf };
+        }
+
+        let sin_theta_max2 = self.radius * self.radius / (reference.p - p_center).norm_squared();
+        let cos_theta_max = (1.0 - sin_theta_max2).max(0.0).sqrt();
+
+        uniform_cone_pdf(cos_theta_max)
     }
 
-    fn sample(&self, _u: &Point2) -> Interaction {
-        // TODO
-        Interaction::new()
+    // uniform over the whole sphere, ignores z and phi limits
+    fn sample(&self, u: &Point2) -> Interaction {
+        let mut p_obj = Point3::from(self.radius * uniform_sample_sphere(u));
+        let mut n = apply_transform_to_normal(&p_obj.coords, &self.object_to_world).normalize();
+        if self.reverse_orientation {
+            n *= -1.0;
+        }
+
+        p_obj *= self.radius / p_obj.coords.norm();
+        let p = self.object_to_world.transform_point(&p_obj);
+        let p_error = gamma(5.0) * p.coords.abs();
+
+        Interaction::init(&p, &Vector3::new(0.0, 0.0, 0.0), &n, &p_error, 0.0, None)
     }
-    
-    fn sample_ref(&self, _reference: &Interaction, _u: &Point2) -> Interaction {
-        // TODO
-        Interaction::new()
+
+    // samples the cone of directions the sphere subtends from reference
+    fn sample_ref(&self, reference: &Interaction, u: &Point2) -> Interaction {
+        let p_center = self.object_to_world.transform_point(&Point3::new(0.0, 0.0, 0.0));
+        let p_origin = offset_ray_origin(&reference.p, &reference.p_error, &reference.n, &(p_center - reference.p));
+
+        if (p_origin - p_center).norm_squared() <= self.radius * self.radius {
+            return self.sample(u);
+        }
+
+        let dc = (reference.p - p_center).norm();
+        let sin_theta_max2 = self.radius * self.radius / (dc * dc);
+        let cos_theta_max = (1.0 - sin_theta_max2).max(0.0).sqrt();
+
+        let wc = (p_center - reference.p).normalize();
+        let mut wc_x = Vector3::new(0.0, 0.0, 0.0);
+        let mut wc_y = Vector3::new(0.0, 0.0, 0.0);
+        coordinate_system(&wc, &mut wc_x, &mut wc_y);
+
+        let cos_theta = (1.0 - u.x) + u.x * cos_theta_max;
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = u.y * 2.0 * PI;
+
+        // angle from the sphere's center to the sampled point
+        let ds = dc * cos_theta - (self.radius * self.radius - dc * dc * sin_theta * sin_theta).max(0.0).sqrt();
+        let cos_alpha = (dc * dc + self.radius * self.radius - ds * ds) / (2.0 * dc * self.radius);
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+
+        let mut n = spherical_direction_in_frame(sin_alpha, cos_alpha, phi, &(-wc_x), &(-wc_y), &(-wc));
+        let p = p_center + self.radius * n;
+        let p_error = gamma(5.0) * p.coords.abs();
+        if self.reverse_orientation {
+            n *= -1.0;
+        }
+
+        Interaction::init(&p, &Vector3::new(0.0, 0.0, 0.0), &n, &p_error, reference.time, None)
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,36 @@
+use crate::common::*;
+
+#[derive(Debug, Clone)]
 pub struct VisibilityTester {
-    
-}
\ No newline at end of file
+    pub p0: Interaction,
+    pub p1: Interaction
+}
+
+impl Default for VisibilityTester {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VisibilityTester {
+    pub fn new() -> Self {
+        Self {
+            p0: Interaction::new(),
+            p1: Interaction::new()
+        }
+    }
+
+    pub fn init(p0: Interaction, p1: Interaction) -> Self {
+        Self {
+            p0,
+            p1
+        }
+    }
+
+    // scene is the top level aggregate, there is no separate scene struct yet
+    pub fn unoccluded(&self, scene: &dyn Primitive) -> bool {
+        let mut ray = self.p0.spawn_ray_to_intersection(&self.p1);
+
+        !scene.intersect_p(&mut ray)
+    }
+}
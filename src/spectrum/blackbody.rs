@@ -0,0 +1,30 @@
+use crate::common::*;
+
+// Planck's law, emitted radiance at wavelength lambda (nm) for temperature t (Kelvin)
+pub fn blackbody(lambda: Float, t: Float) -> Float {
+    if t <= 0.0 {
+        return 0.0;
+    }
+
+    const C: f64 = 299792458.0;
+    const H: f64 = 6.62606957e-34;
+    const KB: f64 = 1.3806488e-23;
+
+    let l = lambda as f64 * 1e-9;
+    let le = (2.0 * H * C * C) / (l.powi(5) * (((H * C) / (l * KB * t as f64)).exp() - 1.0));
+
+    le as Float
+}
+
+// Linear RGB colour of a blackbody at temperature t, with unit luminance
+pub fn blackbody_rgb(t: Float) -> Spectrum {
+    let rgb = spectrum_function_to_rgb(|lambda| blackbody(lambda, t));
+    let rgb = rgb.map(|c| c.max(0.0));
+
+    let y = rgb_y(&rgb);
+    if y > 0.0 {
+        rgb / y
+    } else {
+        Spectrum::new(0.0, 0.0, 0.0)
+    }
+}
@@ -0,0 +1,45 @@
+use crate::common::*;
+
+// Multi lobe gaussian fit of the CIE 1931 2 degree colour matching functions
+// (Wyman, Sloan and Shirley 2013), wavelengths in nm
+
+pub const CIE_LAMBDA_MIN: Float = 360.0;
+pub const CIE_LAMBDA_MAX: Float = 830.0;
+
+// sum of cie_y over the visible range at 1nm steps
+pub const CIE_Y_INTEGRAL: Float = 106.92204;
+
+fn piecewise_gaussian(x: Float, mu: Float, sigma_1: Float, sigma_2: Float) -> Float {
+    let t = (x - mu) / if x < mu { sigma_1 } else { sigma_2 };
+    (-0.5 * t * t).exp()
+}
+
+pub fn cie_x(lambda: Float) -> Float {
+    1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0) + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7) - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2)
+}
+
+pub fn cie_y(lambda: Float) -> Float {
+    0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5) + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1)
+}
+
+pub fn cie_z(lambda: Float) -> Float {
+    1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0) + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8)
+}
+
+// RGB of a spectrum given as a function of wavelength, scaled so a constant 1 maps to Y = 1
+pub fn spectrum_function_to_rgb<F: Fn(Float) -> Float>(f: F) -> Spectrum {
+    let mut x = 0.0;
+    let mut y = 0.0;
+    let mut z = 0.0;
+
+    let mut lambda = CIE_LAMBDA_MIN;
+    while lambda <= CIE_LAMBDA_MAX {
+        let v = f(lambda);
+        x += v * cie_x(lambda);
+        y += v * cie_y(lambda);
+        z += v * cie_z(lambda);
+        lambda += 1.0;
+    }
+
+    from_xyz(x / CIE_Y_INTEGRAL, y / CIE_Y_INTEGRAL, z / CIE_Y_INTEGRAL)
+}
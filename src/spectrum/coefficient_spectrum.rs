@@ -29,9 +29,9 @@ pub fn to_xyz(rgb: &RBGSpectrum, x: &mut Float, y: &mut Float, z: &mut Float) {
 
 // RGB from XYZ
 pub fn from_xyz(x: Float, y: Float, z: Float) -> RBGSpectrum {
-    let r = 0.412453*x + 0.357580*y + 0.180423*z;
-    let g = 0.212671*x + 0.715160*y + 0.072169*z;
-    let b = 0.019334*x + 0.119193*y + 0.950227*z;
+    let r = 3.240479*x - 1.53715*y - 0.498535*z;
+    let g = -0.969256*x + 1.875991*y + 0.041556*z;
+    let b = 0.055648*x - 0.204043*y + 1.057311*z;
 
     from_rgb(r, g, b)
 }
@@ -44,4 +44,12 @@ pub fn gamma_correct(x: Float) -> u8 {
     let gamma_corrected = x.clamp(0.0, 1.0).powf(1.0 / 2.2);
 
     (gamma_corrected * 255.0) as u8
-}
\ No newline at end of file
+}
+// sRGB encoded value to linear
+pub fn srgb_to_linear(v: Float) -> Float {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
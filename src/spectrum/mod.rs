@@ -1,3 +1,7 @@
 pub mod coefficient_spectrum;
+pub mod cie;
+pub mod blackbody;
 
-pub use coefficient_spectrum::{RBGSpectrum, from_rgb, to_rgb, to_xyz, from_xyz, rgb_y, gamma_correct};
\ No newline at end of file
+pub use coefficient_spectrum::{RBGSpectrum, from_rgb, to_rgb, to_xyz, from_xyz, rgb_y, gamma_correct, srgb_to_linear};
+pub use cie::{cie_x, cie_y, cie_z, spectrum_function_to_rgb, CIE_LAMBDA_MIN, CIE_LAMBDA_MAX, CIE_Y_INTEGRAL};
+pub use blackbody::{blackbody, blackbody_rgb};
@@ -0,0 +1,37 @@
+use std::ops::{Add, Mul};
+
+use crate::common::*;
+
+// Bilinear interpolation between four values at the corners of the unit square of (s, t)
+#[derive(Debug, Clone)]
+pub struct BilerpTexture<T> {
+    mapping: Arc<dyn TextureMapping2D>,
+    v00: T,
+    v01: T,
+    v10: T,
+    v11: T
+}
+
+impl<T> BilerpTexture<T> {
+    pub fn init(mapping: Arc<dyn TextureMapping2D>, v00: T, v01: T, v10: T, v11: T) -> Self {
+        Self {
+            mapping,
+            v00,
+            v01,
+            v10,
+            v11
+        }
+    }
+}
+
+impl<T: Debug + Clone + Add<Output = T> + Mul<Float, Output = T>> Texture<T> for BilerpTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let mut dstdx = Vector2::new(0.0, 0.0);
+        let mut dstdy = Vector2::new(0.0, 0.0);
+        let st = self.mapping.map(si, &mut dstdx, &mut dstdy);
+        let (s, t) = (st.x, st.y);
+
+        self.v00.clone() * ((1.0 - s) * (1.0 - t)) + self.v01.clone() * ((1.0 - s) * t) +
+            self.v10.clone() * (s * (1.0 - t)) + self.v11.clone() * (s * t)
+    }
+}
@@ -0,0 +1,114 @@
+use std::ops::{Add, Mul};
+
+use crate::common::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AAMethod {
+    None,
+    // the exact average over a box the size of the lookup footprint
+    ClosedForm
+}
+
+// integral from 0 to x of the square wave (-1)^floor(x), a triangle wave
+fn square_wave_integral(x: Float) -> Float {
+    let f = x - 2.0 * (0.5 * x).floor();
+
+    if f < 1.0 { f } else { 2.0 - f }
+}
+
+// average of (-1)^floor(x) over [x - width, x + width]
+fn filtered_square_wave(x: Float, width: Float) -> Float {
+    if width <= 0.0 {
+        return if (x.floor() as i64) % 2 == 0 { 1.0 } else { -1.0 };
+    }
+
+    (square_wave_integral(x + width) - square_wave_integral(x - width)) / (2.0 * width)
+}
+
+// weight of the first texture where the filtered parity of the cell is p, in [-1, 1]
+fn blend<T: Add<Output = T> + Mul<Float, Output = T>>(parity: Float, tex1: &dyn Texture<T>, tex2: &dyn Texture<T>, si: &SurfaceInteraction) -> T {
+    let w = 0.5 * (1.0 + parity);
+    if w >= 1.0 {
+        return tex1.evaluate(si);
+    }
+    if w <= 0.0 {
+        return tex2.evaluate(si);
+    }
+
+    tex1.evaluate(si) * w + tex2.evaluate(si) * (1.0 - w)
+}
+
+// tex1 on the squares where floor(s) + floor(t) is even and tex2 on the others. The parity is
+// the product of one square wave per axis, so its box filtered value is the product of their
+// averages, which have a closed form
+#[derive(Debug, Clone)]
+pub struct Checkerboard2DTexture<T> {
+    mapping: Arc<dyn TextureMapping2D>,
+    tex1: Arc<dyn Texture<T>>,
+    tex2: Arc<dyn Texture<T>>,
+    aa_method: AAMethod
+}
+
+impl<T> Checkerboard2DTexture<T> {
+    pub fn init(mapping: Arc<dyn TextureMapping2D>, tex1: Arc<dyn Texture<T>>, tex2: Arc<dyn Texture<T>>, aa_method: AAMethod) -> Self {
+        Self {
+            mapping,
+            tex1,
+            tex2,
+            aa_method
+        }
+    }
+}
+
+impl<T: Debug + Add<Output = T> + Mul<Float, Output = T>> Texture<T> for Checkerboard2DTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let mut dstdx = Vector2::new(0.0, 0.0);
+        let mut dstdy = Vector2::new(0.0, 0.0);
+        let st = self.mapping.map(si, &mut dstdx, &mut dstdy);
+
+        // half widths of the footprint along s and t
+        let (ds, dt) = match self.aa_method {
+            AAMethod::None => (0.0, 0.0),
+            AAMethod::ClosedForm => (dstdx.x.abs().max(dstdy.x.abs()), dstdx.y.abs().max(dstdy.y.abs()))
+        };
+        let parity = filtered_square_wave(st.x, ds) * filtered_square_wave(st.y, dt);
+
+        blend(parity, self.tex1.as_ref(), self.tex2.as_ref(), si)
+    }
+}
+
+// the solid version, with cubes of alternating textures
+#[derive(Debug, Clone)]
+pub struct Checkerboard3DTexture<T> {
+    mapping: Arc<dyn TextureMapping3D>,
+    tex1: Arc<dyn Texture<T>>,
+    tex2: Arc<dyn Texture<T>>,
+    aa_method: AAMethod
+}
+
+impl<T> Checkerboard3DTexture<T> {
+    pub fn init(mapping: Arc<dyn TextureMapping3D>, tex1: Arc<dyn Texture<T>>, tex2: Arc<dyn Texture<T>>, aa_method: AAMethod) -> Self {
+        Self {
+            mapping,
+            tex1,
+            tex2,
+            aa_method
+        }
+    }
+}
+
+impl<T: Debug + Add<Output = T> + Mul<Float, Output = T>> Texture<T> for Checkerboard3DTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let mut dpdx = Vector3::new(0.0, 0.0, 0.0);
+        let mut dpdy = Vector3::new(0.0, 0.0, 0.0);
+        let p = self.mapping.map(si, &mut dpdx, &mut dpdy);
+
+        let width = match self.aa_method {
+            AAMethod::None => Vector3::new(0.0, 0.0, 0.0),
+            AAMethod::ClosedForm => dpdx.abs().sup(&dpdy.abs())
+        };
+        let parity = filtered_square_wave(p.x, width.x) * filtered_square_wave(p.y, width.y) * filtered_square_wave(p.z, width.z);
+
+        blend(parity, self.tex1.as_ref(), self.tex2.as_ref(), si)
+    }
+}
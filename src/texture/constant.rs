@@ -0,0 +1,20 @@
+use crate::common::*;
+
+#[derive(Debug, Clone)]
+pub struct ConstantTexture<T> {
+    value: T
+}
+
+impl<T: Debug + Clone> ConstantTexture<T> {
+    pub fn init(value: T) -> Self {
+        Self {
+            value
+        }
+    }
+}
+
+impl<T: Debug + Clone> Texture<T> for ConstantTexture<T> {
+    fn evaluate(&self, _si: &SurfaceInteraction) -> T {
+        self.value.clone()
+    }
+}
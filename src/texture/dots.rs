@@ -0,0 +1,44 @@
+use crate::common::*;
+
+// Polka dots, at most one per unit cell of (s, t) with a position and presence given by noise
+#[derive(Debug, Clone)]
+pub struct DotsTexture<T> {
+    mapping: Arc<dyn TextureMapping2D>,
+    inside: Arc<dyn Texture<T>>,
+    outside: Arc<dyn Texture<T>>
+}
+
+impl<T> DotsTexture<T> {
+    pub fn init(mapping: Arc<dyn TextureMapping2D>, inside: Arc<dyn Texture<T>>, outside: Arc<dyn Texture<T>>) -> Self {
+        Self {
+            mapping,
+            inside,
+            outside
+        }
+    }
+}
+
+impl<T: Debug> Texture<T> for DotsTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let mut dstdx = Vector2::new(0.0, 0.0);
+        let mut dstdy = Vector2::new(0.0, 0.0);
+        let st = self.mapping.map(si, &mut dstdx, &mut dstdy);
+
+        let s_cell = (st.x + 0.5).floor();
+        let t_cell = (st.y + 0.5).floor();
+
+        // about half of the cells have a dot, shifted so it stays inside the cell
+        if noise(s_cell + 0.5, t_cell + 0.5, 0.5) > 0.0 {
+            let radius = 0.35;
+            let max_shift = 0.5 - radius;
+            let s_center = s_cell + max_shift * noise(s_cell + 1.5, t_cell + 2.8, 0.5);
+            let t_center = t_cell + max_shift * noise(s_cell + 4.5, t_cell + 9.8, 0.5);
+            let (ds, dt) = (st.x - s_center, st.y - t_center);
+            if ds * ds + dt * dt < radius * radius {
+                return self.inside.evaluate(si);
+            }
+        }
+
+        self.outside.evaluate(si)
+    }
+}
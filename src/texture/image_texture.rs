@@ -0,0 +1,65 @@
+use std::marker::PhantomData;
+
+use crate::common::*;
+
+// A texture read from an image, filtered through its MIPMap over the footprint the mapping gives
+// from the screen space derivatives of the hit. Images are shared between textures using the same
+// file. Float textures take the luminance of the texels
+#[derive(Debug, Clone)]
+pub struct ImageTexture<T> {
+    mapping: Arc<dyn TextureMapping2D>,
+    mipmap: Arc<MIPMap>,
+    filter: MIPMapFilter,
+    wrap: ImageWrap,
+    max_anisotropy: Float,
+    scale: Float,
+    value: PhantomData<T>
+}
+
+impl<T> ImageTexture<T> {
+    pub fn init(mapping: Arc<dyn TextureMapping2D>, mipmap: Arc<MIPMap>, filter: MIPMapFilter, wrap: ImageWrap) -> Self {
+        Self {
+            mapping,
+            mipmap,
+            filter,
+            wrap,
+            max_anisotropy: 8.0,
+            scale: 1.0,
+            value: PhantomData
+        }
+    }
+
+    // srgb decodes 8 bit images from sRGB to linear, as colour maps want and data maps don't
+    pub fn from_file(path: &str, mapping: Arc<dyn TextureMapping2D>, filter: MIPMapFilter, wrap: ImageWrap, srgb: bool) -> Result<Self, String> {
+        Ok(Self::init(mapping, MIPMap::cached(path, srgb)?, filter, wrap))
+    }
+
+    // bounds the eccentricity of EWA footprints, and so the texels a lookup reads
+    pub fn set_max_anisotropy(&mut self, max_anisotropy: Float) {
+        self.max_anisotropy = max_anisotropy.max(1.0);
+    }
+
+    pub fn set_scale(&mut self, scale: Float) {
+        self.scale = scale;
+    }
+
+    fn lookup(&self, si: &SurfaceInteraction) -> Spectrum {
+        let mut dstdx = Vector2::new(0.0, 0.0);
+        let mut dstdy = Vector2::new(0.0, 0.0);
+        let st = self.mapping.map(si, &mut dstdx, &mut dstdy);
+
+        self.mipmap.lookup(&st, &dstdx, &dstdy, self.filter, self.wrap, self.max_anisotropy) * self.scale
+    }
+}
+
+impl Texture<Spectrum> for ImageTexture<Spectrum> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> Spectrum {
+        self.lookup(si)
+    }
+}
+
+impl Texture<Float> for ImageTexture<Float> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> Float {
+        rgb_y(&self.lookup(si))
+    }
+}
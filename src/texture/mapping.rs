@@ -0,0 +1,187 @@
+use crate::common::*;
+
+// Texture coordinates for a point and their derivatives along the screen, which give the size
+// of the area a lookup has to filter over
+pub trait TextureMapping2D: Debug {
+    fn map(&self, si: &SurfaceInteraction, dstdx: &mut Vector2, dstdy: &mut Vector2) -> Point2;
+}
+
+// the surface parameterization, scaled and offset
+#[derive(Debug, Clone)]
+pub struct UVMapping2D {
+    su: Float,
+    sv: Float,
+    du: Float,
+    dv: Float
+}
+
+impl UVMapping2D {
+    pub fn init(su: Float, sv: Float, du: Float, dv: Float) -> Self {
+        Self {
+            su,
+            sv,
+            du,
+            dv
+        }
+    }
+
+    pub fn new() -> Self {
+        Self::init(1.0, 1.0, 0.0, 0.0)
+    }
+}
+
+impl Default for UVMapping2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextureMapping2D for UVMapping2D {
+    fn map(&self, si: &SurfaceInteraction, dstdx: &mut Vector2, dstdy: &mut Vector2) -> Point2 {
+        *dstdx = Vector2::new(self.su * si.dudx, self.sv * si.dvdx);
+        *dstdy = Vector2::new(self.su * si.dudy, self.sv * si.dvdy);
+
+        Point2::new(self.su * si.uv.x + self.du, self.sv * si.uv.y + self.dv)
+    }
+}
+
+// the derivatives of a mapping without a closed form, by a step along dpdx and dpdy. Periodic
+// marks the coordinates that wrap around from 1 to 0
+fn map_differences(si: &SurfaceInteraction, f: impl Fn(&Point3) -> Point2, periodic: [bool; 2], dstdx: &mut Vector2, dstdy: &mut Vector2) -> Point2 {
+    let p = si.interaction.p;
+    let st = f(&p);
+    let delta = 0.1;
+    let derivative = |dp: &Vector3| {
+        let mut d = f(&(p + delta * dp)) - st;
+        for i in 0..2 {
+            if periodic[i] {
+                d[i] = unwrap_difference(d[i]);
+            }
+        }
+        d / delta
+    };
+    *dstdx = derivative(&si.dpdx);
+    *dstdy = derivative(&si.dpdy);
+
+    st
+}
+
+// a difference across the wrap of a periodic coordinate goes the short way round
+fn unwrap_difference(d: Float) -> Float {
+    if d > 0.5 {
+        d - 1.0
+    } else if d < -0.5 {
+        d + 1.0
+    } else {
+        d
+    }
+}
+
+// theta and phi about the origin of texture space, both scaled to [0, 1]
+#[derive(Debug, Clone)]
+pub struct SphericalMapping2D {
+    world_to_texture: Transform
+}
+
+impl SphericalMapping2D {
+    pub fn init(world_to_texture: Transform) -> Self {
+        Self {
+            world_to_texture
+        }
+    }
+
+    fn sphere(&self, p: &Point3) -> Point2 {
+        let v = self.world_to_texture.transform_point(p).coords.normalize();
+
+        Point2::new(spherical_theta(&v) / PI, spherical_phi(&v) / (2.0 * PI))
+    }
+}
+
+impl TextureMapping2D for SphericalMapping2D {
+    fn map(&self, si: &SurfaceInteraction, dstdx: &mut Vector2, dstdy: &mut Vector2) -> Point2 {
+        map_differences(si, |p| self.sphere(p), [false, true], dstdx, dstdy)
+    }
+}
+
+// the angle around the z axis of texture space scaled to [0, 1], and the height along it
+#[derive(Debug, Clone)]
+pub struct CylindricalMapping2D {
+    world_to_texture: Transform
+}
+
+impl CylindricalMapping2D {
+    pub fn init(world_to_texture: Transform) -> Self {
+        Self {
+            world_to_texture
+        }
+    }
+
+    fn cylinder(&self, p: &Point3) -> Point2 {
+        let v = self.world_to_texture.transform_point(p);
+
+        Point2::new((PI + v.y.atan2(v.x)) / (2.0 * PI), v.z)
+    }
+}
+
+impl TextureMapping2D for CylindricalMapping2D {
+    fn map(&self, si: &SurfaceInteraction, dstdx: &mut Vector2, dstdy: &mut Vector2) -> Point2 {
+        map_differences(si, |p| self.cylinder(p), [true, false], dstdx, dstdy)
+    }
+}
+
+// projection onto the plane spanned by vs and vt, offset by ds and dt
+#[derive(Debug, Clone)]
+pub struct PlanarMapping2D {
+    vs: Vector3,
+    vt: Vector3,
+    ds: Float,
+    dt: Float
+}
+
+impl PlanarMapping2D {
+    pub fn init(vs: &Vector3, vt: &Vector3, ds: Float, dt: Float) -> Self {
+        Self {
+            vs: *vs,
+            vt: *vt,
+            ds,
+            dt
+        }
+    }
+}
+
+impl TextureMapping2D for PlanarMapping2D {
+    fn map(&self, si: &SurfaceInteraction, dstdx: &mut Vector2, dstdy: &mut Vector2) -> Point2 {
+        let v = si.interaction.p.coords;
+        *dstdx = Vector2::new(self.vs.dot(&si.dpdx), self.vt.dot(&si.dpdx));
+        *dstdy = Vector2::new(self.vs.dot(&si.dpdy), self.vt.dot(&si.dpdy));
+
+        Point2::new(self.ds + v.dot(&self.vs), self.dt + v.dot(&self.vt))
+    }
+}
+
+// A point in a 3D texture space for solid textures
+pub trait TextureMapping3D: Debug {
+    fn map(&self, si: &SurfaceInteraction, dpdx: &mut Vector3, dpdy: &mut Vector3) -> Point3;
+}
+
+#[derive(Debug, Clone)]
+pub struct IdentityMapping3D {
+    world_to_texture: Transform
+}
+
+impl IdentityMapping3D {
+    pub fn init(world_to_texture: Transform) -> Self {
+        Self {
+            world_to_texture
+        }
+    }
+}
+
+impl TextureMapping3D for IdentityMapping3D {
+    fn map(&self, si: &SurfaceInteraction, dpdx: &mut Vector3, dpdy: &mut Vector3) -> Point3 {
+        *dpdx = self.world_to_texture.transform_vector(&si.dpdx);
+        *dpdy = self.world_to_texture.transform_vector(&si.dpdy);
+
+        self.world_to_texture.transform_point(&si.interaction.p)
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::common::*;
+use read_image::{read_exr_image, read_png_image};
+
+// What lookups outside [0, 1] see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageWrap {
+    Repeat,
+    Black,
+    Clamp
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MIPMapFilter {
+    // the full resolution image only, aliases when minified
+    Bilinear,
+    // an isotropic footprint, blurry at grazing angles
+    Trilinear,
+    // an elliptical Gaussian fitted to the footprint, Heckbert's elliptically weighted average
+    EWA
+}
+
+#[derive(Debug, Clone)]
+struct MIPLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<Spectrum>
+}
+
+impl MIPLevel {
+    // each texel averages a 2x2 block of the finer level, the last row and column of odd sizes
+    // are shared by two blocks
+    fn downsample(&self) -> Self {
+        let width = self.width.div_ceil(2);
+        let height = self.height.div_ceil(2);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let x0 = 2 * x;
+                let y0 = 2 * y;
+                let x1 = (x0 + 1).min(self.width - 1);
+                let y1 = (y0 + 1).min(self.height - 1);
+                let at = |x: usize, y: usize| self.texels[y * self.width + x];
+                texels.push((at(x0, y0) + at(x1, y0) + at(x0, y1) + at(x1, y1)) * 0.25);
+            }
+        }
+
+        Self { width, height, texels }
+    }
+}
+
+type MIPMapCache = Mutex<HashMap<(String, bool), Arc<MIPMap>>>;
+
+fn mipmap_cache() -> &'static MIPMapCache {
+    static CACHE: OnceLock<MIPMapCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// An image and its successively halved versions down to a single texel, level 0 is the image.
+// Lookups pick the levels whose texels match the size of the footprint
+#[derive(Debug, Clone)]
+pub struct MIPMap {
+    pyramid: Vec<MIPLevel>
+}
+
+impl MIPMap {
+    // texels row by row from t = 0, the bottom of the image
+    pub fn init(texels: Vec<Spectrum>, width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0 && texels.len() == width * height, "MIPMap needs width * height texels");
+
+        let mut pyramid = vec![MIPLevel { width, height, texels }];
+        while pyramid.last().is_some_and(|level| level.width > 1 || level.height > 1) {
+            let coarser = pyramid.last().unwrap().downsample();
+            pyramid.push(coarser);
+        }
+
+        Self { pyramid }
+    }
+
+    // PNG, JPEG and the other formats of the image crate, or EXR. srgb decodes 8 bit formats from
+    // sRGB to linear, EXR is always linear
+    pub fn from_file(path: &str, srgb: bool) -> Result<Self, String> {
+        let is_exr = path.to_lowercase().ends_with(".exr");
+        let (pixels, width, height) = if is_exr { read_exr_image(path)? } else { read_png_image(path, srgb)? };
+        if width == 0 || height == 0 {
+            return Err(format!("Texture image {} is empty", path));
+        }
+
+        // images come top row first
+        let texels = pixels.chunks(width).rev().flatten().copied().collect();
+
+        Ok(Self::init(texels, width, height))
+    }
+
+    // from_file, loading each image only once however many textures use it
+    pub fn cached(path: &str, srgb: bool) -> Result<Arc<Self>, String> {
+        let mut cache = mipmap_cache().lock().unwrap();
+        let key = (path.to_string(), srgb);
+        if let Some(mipmap) = cache.get(&key) {
+            return Ok(mipmap.clone());
+        }
+
+        let mipmap = Arc::new(Self::from_file(path, srgb)?);
+        cache.insert(key, mipmap.clone());
+
+        Ok(mipmap)
+    }
+
+    pub fn levels(&self) -> usize {
+        self.pyramid.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.pyramid[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.pyramid[0].height
+    }
+
+    pub fn texel(&self, level: usize, s: isize, t: isize, wrap: ImageWrap) -> Spectrum {
+        let level = &self.pyramid[level];
+        let (w, h) = (level.width as isize, level.height as isize);
+        let (s, t) = match wrap {
+            ImageWrap::Repeat => (s.rem_euclid(w), t.rem_euclid(h)),
+            ImageWrap::Clamp => (s.clamp(0, w - 1), t.clamp(0, h - 1)),
+            ImageWrap::Black => {
+                if s < 0 || s >= w || t < 0 || t >= h {
+                    return Spectrum::new(0.0, 0.0, 0.0);
+                }
+                (s, t)
+            }
+        };
+
+        level.texels[t as usize * level.width + s as usize]
+    }
+
+    // bilinear interpolation of the texels of one level
+    pub fn bilerp(&self, level: usize, st: &Point2, wrap: ImageWrap) -> Spectrum {
+        let level = level.min(self.levels() - 1);
+        let s = st.x * self.pyramid[level].width as Float - 0.5;
+        let t = st.y * self.pyramid[level].height as Float - 0.5;
+        let (s0, t0) = (s.floor(), t.floor());
+        let (ds, dt) = (s - s0, t - t0);
+        let (s0, t0) = (s0 as isize, t0 as isize);
+
+        self.texel(level, s0, t0, wrap) * ((1.0 - ds) * (1.0 - dt)) + self.texel(level, s0, t0 + 1, wrap) * ((1.0 - ds) * dt) +
+            self.texel(level, s0 + 1, t0, wrap) * (ds * (1.0 - dt)) + self.texel(level, s0 + 1, t0 + 1, wrap) * (ds * dt)
+    }
+
+    // an isotropic lookup over a square footprint of the given width in [0, 1] texture space,
+    // blending the two levels around it
+    pub fn lookup_trilinear(&self, st: &Point2, width: Float, wrap: ImageWrap) -> Spectrum {
+        let levels = self.levels() as Float;
+        let level = levels - 1.0 + width.max(1e-8).log2();
+        if level < 0.0 {
+            return self.bilerp(0, st, wrap);
+        }
+        if level >= levels - 1.0 {
+            return self.texel(self.levels() - 1, 0, 0, wrap);
+        }
+
+        let i = level.floor();
+        let delta = level - i;
+        let i = i as usize;
+
+        self.bilerp(i, st, wrap) * (1.0 - delta) + self.bilerp(i + 1, st, wrap) * delta
+    }
+
+    // an anisotropic lookup over the ellipse with axes dst0 and dst1, whose eccentricity is clamped
+    // to max_anisotropy so the number of texels stays bounded
+    pub fn lookup_ewa(&self, st: &Point2, dst0: &Vector2, dst1: &Vector2, wrap: ImageWrap, max_anisotropy: Float) -> Spectrum {
+        let (mut dst0, mut dst1) = (*dst0, *dst1);
+        if dst0.norm_squared() < dst1.norm_squared() {
+            std::mem::swap(&mut dst0, &mut dst1);
+        }
+
+        let major = dst0.norm();
+        let mut minor = dst1.norm();
+        if minor * max_anisotropy < major && minor > 0.0 {
+            let scale = major / (minor * max_anisotropy);
+            dst1 *= scale;
+            minor *= scale;
+        }
+        if minor == 0.0 {
+            return self.bilerp(0, st, wrap);
+        }
+
+        // the level where the minor axis spans about a texel
+        let lod = (self.levels() as Float - 1.0 + minor.log2()).max(0.0);
+        let i = lod.floor();
+        let delta = lod - i;
+        let i = i as usize;
+
+        self.ewa(i, st, &dst0, &dst1, wrap) * (1.0 - delta) + self.ewa(i + 1, st, &dst0, &dst1, wrap) * delta
+    }
+
+    fn ewa(&self, level: usize, st: &Point2, dst0: &Vector2, dst1: &Vector2, wrap: ImageWrap) -> Spectrum {
+        if level >= self.levels() {
+            return self.texel(self.levels() - 1, 0, 0, wrap);
+        }
+
+        // to texel units of this level
+        let (w, h) = (self.pyramid[level].width as Float, self.pyramid[level].height as Float);
+        let s = st.x * w - 0.5;
+        let t = st.y * h - 0.5;
+        let d0 = Vector2::new(dst0.x * w, dst0.y * h);
+        let d1 = Vector2::new(dst1.x * w, dst1.y * h);
+
+        // implicit ellipse a s^2 + b s t + c t^2 = 1, widened by a texel so it covers one
+        let mut a = d0.y * d0.y + d1.y * d1.y + 1.0;
+        let mut b = -2.0 * (d0.x * d0.y + d1.x * d1.y);
+        let mut c = d0.x * d0.x + d1.x * d1.x + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        // bounding box of the ellipse
+        let det = -b * b + 4.0 * a * c;
+        let inv_det = 1.0 / det;
+        let u_sqrt = (det * c).sqrt();
+        let v_sqrt = (a * det).sqrt();
+        let s0 = (s - 2.0 * inv_det * u_sqrt).ceil() as isize;
+        let s1 = (s + 2.0 * inv_det * u_sqrt).floor() as isize;
+        let t0 = (t - 2.0 * inv_det * v_sqrt).ceil() as isize;
+        let t1 = (t + 2.0 * inv_det * v_sqrt).floor() as isize;
+
+        // a Gaussian of the ellipse radius that falls to zero at its edge
+        let alpha: Float = 2.0;
+        let edge = (-alpha).exp();
+        let mut sum = Spectrum::new(0.0, 0.0, 0.0);
+        let mut sum_weights = 0.0;
+        for it in t0..=t1 {
+            let tt = it as Float - t;
+            for is in s0..=s1 {
+                let ss = is as Float - s;
+                let r2 = a * ss * ss + b * ss * tt + c * tt * tt;
+                if r2 < 1.0 {
+                    let weight = (-alpha * r2).exp() - edge;
+                    sum += self.texel(level, is, it, wrap) * weight;
+                    sum_weights += weight;
+                }
+            }
+        }
+
+        if sum_weights > 0.0 { sum / sum_weights } else { self.bilerp(level, st, wrap) }
+    }
+
+    // filters over the parallelogram spanned by the screen space derivatives of st
+    pub fn lookup(&self, st: &Point2, dstdx: &Vector2, dstdy: &Vector2, filter: MIPMapFilter, wrap: ImageWrap, max_anisotropy: Float) -> Spectrum {
+        match filter {
+            MIPMapFilter::Bilinear => self.bilerp(0, st, wrap),
+            MIPMapFilter::Trilinear => {
+                let width = 2.0 * dstdx.x.abs().max(dstdx.y.abs()).max(dstdy.x.abs()).max(dstdy.y.abs());
+                self.lookup_trilinear(st, width, wrap)
+            }
+            MIPMapFilter::EWA => self.lookup_ewa(st, dstdx, dstdy, wrap, max_anisotropy)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use write_image::write_png_image;
+
+    // 4 by 2 texels numbered 0 to 7 row by row from the bottom
+    fn numbered() -> MIPMap {
+        let texels = (0..8).map(|i| Spectrum::new(i as Float, 0.0, 0.0)).collect();
+        MIPMap::init(texels, 4, 2)
+    }
+
+    #[test]
+    fn wraps_texels_outside_the_image() {
+        let mipmap = numbered();
+        let at = |s: isize, t: isize, wrap: ImageWrap| mipmap.texel(0, s, t, wrap).x;
+
+        assert_eq!(at(1, 1, ImageWrap::Repeat), 5.0);
+        assert_eq!(at(5, 1, ImageWrap::Repeat), 5.0);
+        assert_eq!(at(-3, -1, ImageWrap::Repeat), 5.0);
+
+        assert_eq!(at(7, 0, ImageWrap::Clamp), 3.0);
+        assert_eq!(at(-2, 4, ImageWrap::Clamp), 4.0);
+
+        assert_eq!(at(3, 1, ImageWrap::Black), 7.0);
+        assert_eq!(at(4, 1, ImageWrap::Black), 0.0);
+        assert_eq!(at(0, -1, ImageWrap::Black), 0.0);
+    }
+
+    #[test]
+    fn bilerp_blends_across_the_edge_by_wrap() {
+        let mipmap = numbered();
+        // on the right edge of the top row, halfway between the centres of texel 7 and the one past it
+        let st = Point2::new(1.0, 0.75);
+
+        assert!((mipmap.bilerp(0, &st, ImageWrap::Repeat).x - 5.5).abs() < 1e-5);
+        assert!((mipmap.bilerp(0, &st, ImageWrap::Clamp).x - 7.0).abs() < 1e-5);
+        assert!((mipmap.bilerp(0, &st, ImageWrap::Black).x - 3.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn halves_down_to_one_texel() {
+        let texels = vec![Spectrum::new(1.0, 1.0, 1.0); 5 * 3];
+        let mipmap = MIPMap::init(texels, 5, 3);
+
+        assert_eq!(mipmap.levels(), 4);
+        assert_eq!((mipmap.pyramid[1].width, mipmap.pyramid[1].height), (3, 2));
+        assert_eq!((mipmap.pyramid[3].width, mipmap.pyramid[3].height), (1, 1));
+        assert!((mipmap.pyramid[3].texels[0].x - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn caches_each_image_by_path() {
+        let path = std::env::temp_dir().join(format!("mipmap_cache_{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        // the bottom row is red, the top row black
+        let rgb = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        write_png_image(path, &rgb.to_vec(), 2, 2);
+
+        let a = MIPMap::cached(path, false).unwrap();
+        let b = MIPMap::cached(path, false).unwrap();
+        let srgb = MIPMap::cached(path, true).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &srgb));
+        assert_eq!((a.width(), a.height()), (2, 2));
+        assert_eq!(a.texel(0, 0, 0, ImageWrap::Clamp), Spectrum::new(1.0, 0.0, 0.0));
+        assert_eq!(a.texel(0, 0, 1, ImageWrap::Clamp), Spectrum::new(0.0, 0.0, 0.0));
+
+        // still served from the cache after the file is gone
+        assert!(Arc::ptr_eq(&a, &MIPMap::cached(path, false).unwrap()));
+    }
+
+    #[test]
+    fn missing_images_are_not_cached() {
+        let path = "no/such/texture.png";
+        assert!(MIPMap::cached(path, true).is_err());
+        assert!(!mipmap_cache().lock().unwrap().contains_key(&(path.to_string(), true)));
+    }
+}
@@ -0,0 +1,37 @@
+use std::ops::{Add, Mul};
+
+use crate::common::*;
+
+// Linear blend of two textures, all of the first at amount 0 and all of the second at 1
+#[derive(Debug, Clone)]
+pub struct MixTexture<T> {
+    tex1: Arc<dyn Texture<T>>,
+    tex2: Arc<dyn Texture<T>>,
+    amount: Arc<dyn Texture<Float>>
+}
+
+impl<T> MixTexture<T> {
+    pub fn init(tex1: Arc<dyn Texture<T>>, tex2: Arc<dyn Texture<T>>, amount: Arc<dyn Texture<Float>>) -> Self {
+        Self {
+            tex1,
+            tex2,
+            amount
+        }
+    }
+}
+
+impl<T: Debug + Add<Output = T> + Mul<Float, Output = T>> Texture<T> for MixTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let amount = self.amount.evaluate(si);
+
+        // skips the lookup that has no weight
+        if amount <= 0.0 {
+            return self.tex1.evaluate(si);
+        }
+        if amount >= 1.0 {
+            return self.tex2.evaluate(si);
+        }
+
+        self.tex1.evaluate(si) * (1.0 - amount) + self.tex2.evaluate(si) * amount
+    }
+}
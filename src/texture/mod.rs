@@ -0,0 +1,23 @@
+pub mod texture;
+pub mod mapping;
+pub mod noise;
+pub mod constant;
+pub mod scale;
+pub mod mix_texture;
+pub mod bilerp;
+pub mod checkerboard;
+pub mod dots;
+pub mod mipmap;
+pub mod image_texture;
+
+pub use texture::Texture;
+pub use mapping::{TextureMapping2D, UVMapping2D, SphericalMapping2D, CylindricalMapping2D, PlanarMapping2D, TextureMapping3D, IdentityMapping3D};
+pub use noise::{noise, noise_point};
+pub use constant::ConstantTexture;
+pub use scale::ScaleTexture;
+pub use mix_texture::MixTexture;
+pub use bilerp::BilerpTexture;
+pub use checkerboard::{AAMethod, Checkerboard2DTexture, Checkerboard3DTexture};
+pub use dots::DotsTexture;
+pub use mipmap::{ImageWrap, MIPMapFilter, MIPMap};
+pub use image_texture::ImageTexture;
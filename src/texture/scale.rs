@@ -0,0 +1,27 @@
+use std::ops::Mul;
+
+use crate::common::*;
+
+// One texture multiplied by a scalar one
+#[derive(Debug, Clone)]
+pub struct ScaleTexture<T> {
+    tex: Arc<dyn Texture<T>>,
+    scale: Arc<dyn Texture<Float>>
+}
+
+impl<T> ScaleTexture<T> {
+    pub fn init(tex: Arc<dyn Texture<T>>, scale: Arc<dyn Texture<Float>>) -> Self {
+        Self {
+            tex,
+            scale
+        }
+    }
+}
+
+impl<T: Debug + Mul<Float, Output = T>> Texture<T> for ScaleTexture<T> {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let scale = self.scale.evaluate(si);
+
+        self.tex.evaluate(si) * scale
+    }
+}
@@ -0,0 +1,6 @@
+use crate::common::*;
+
+// A value that varies over a surface, T is Float or Spectrum
+pub trait Texture<T>: Debug {
+    fn evaluate(&self, si: &SurfaceInteraction) -> T;
+}